@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use csv::{ReaderBuilder, Trim};
+
+use crate::normalize::normalize_text_or_star;
+
+/// Accent fields joined onto one lexicon row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccentEntry {
+    /// Accent nucleus position (`0` = 平板).
+    pub accent: usize,
+    pub chain_rule: String,
+    pub chain_flag: String,
+}
+
+/// Accent source rows keyed by `(surface, read, pos)`.
+///
+/// Source files are headerless CSV:
+/// `surface,read,pos,accent[,chain_rule[,chain_flag]]`.
+/// `pos` is an IPADIC POS prefix such as `名詞` or `名詞-固有名詞-地名`, or `*`
+/// to match any POS. Later rows override earlier ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccentDictionary {
+    entries: HashMap<(String, String, String), AccentEntry>,
+}
+
+impl AccentDictionary {
+    pub fn from_paths<P: AsRef<Path>>(paths: &[P]) -> Result<Self> {
+        let mut dictionary = Self::default();
+        for path in paths {
+            let path = path.as_ref();
+            let file = File::open(path)
+                .with_context(|| format!("failed to open accent source {}", path.display()))?;
+            dictionary
+                .extend_from_reader(file)
+                .with_context(|| format!("invalid accent source {}", path.display()))?;
+        }
+        Ok(dictionary)
+    }
+
+    pub fn from_reader<R: Read>(input: R) -> Result<Self> {
+        let mut dictionary = Self::default();
+        dictionary.extend_from_reader(input)?;
+        Ok(dictionary)
+    }
+
+    fn extend_from_reader<R: Read>(&mut self, input: R) -> Result<()> {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .trim(Trim::All)
+            .comment(Some(b'#'))
+            .from_reader(input);
+        for record in reader.records() {
+            let record = record.context("failed to read accent row")?;
+            let line_no = record.position().map_or(0, |position| position.line());
+            if !(4..=6).contains(&record.len()) {
+                return Err(anyhow!(
+                    "invalid accent row at line {}: expected 4 to 6 columns, got {}",
+                    line_no,
+                    record.len()
+                ));
+            }
+            let accent = record[3].parse::<usize>().with_context(|| {
+                format!(
+                    "failed to parse accent='{}' at line {}",
+                    &record[3], line_no
+                )
+            })?;
+
+            self.entries.insert(
+                (
+                    record[0].to_string(),
+                    normalize_text_or_star(&record[1]),
+                    normalize_text_or_star(&record[2]),
+                ),
+                AccentEntry {
+                    accent,
+                    chain_rule: normalize_text_or_star(record.get(4).unwrap_or("")),
+                    chain_flag: normalize_text_or_star(record.get(5).unwrap_or("")),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Looks up the full POS first, then ever shorter prefixes of it, then a `*` entry.
+    pub fn lookup(&self, surface: &str, read: &str, pos: &[String]) -> Option<&AccentEntry> {
+        let key = |pos: String| (surface.to_string(), read.to_string(), pos);
+        (1..=accent_pos_levels(pos))
+            .rev()
+            .map(|levels| pos[..levels].join("-"))
+            .chain(["*".to_string()])
+            .find_map(|pos| self.entries.get(&key(pos)))
+    }
+}
+
+/// The POS of a lexicon row as accent sources write it: levels joined by `-`,
+/// without trailing `*` levels.
+pub fn accent_pos(pos: &[String]) -> String {
+    pos[..accent_pos_levels(pos)].join("-")
+}
+
+fn accent_pos_levels(pos: &[String]) -> usize {
+    pos.iter()
+        .rposition(|level| level != "*")
+        .map_or(0, |i| i + 1)
+}
+
+/// Counts moras in a katakana pronunciation the way jpreprocess does.
+///
+/// Small kana other than `ッ` merge with the preceding mora; `ー`, `ッ` and `ン`
+/// count as one. Returns `None` when the value is not pure katakana.
+pub fn mora_count(pron: &str) -> Option<usize> {
+    if pron == "*" || pron.is_empty() {
+        return None;
+    }
+    let mut count = 0;
+    for c in pron.chars() {
+        match c {
+            'ャ' | 'ュ' | 'ョ' | 'ァ' | 'ィ' | 'ゥ' | 'ェ' | 'ォ' | 'ヮ' if count > 0 => {}
+            'ァ'..='ヺ' | 'ー' => count += 1,
+            _ => return None,
+        }
+    }
+    Some(count)
+}
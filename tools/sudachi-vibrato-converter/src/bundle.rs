@@ -0,0 +1,292 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tar::{Archive, Builder, EntryType, Header};
+use xz2::read::XzDecoder;
+use xz2::write::XzEncoder;
+
+use crate::manifest::BuildManifest;
+use crate::stats::ConversionStats;
+
+pub const METADATA_FILE: &str = "metadata.json";
+pub const DICTIONARY_FILE: &str = "system.dic.zst";
+pub const REWRITE_FILE: &str = "rewrite.def";
+pub const STATS_FILE: &str = "stats.json";
+pub const SPLIT_INFO_FILE: &str = "split_info.csv";
+pub const WORD_UNITS_FILE: &str = "word_units.bin";
+pub const NORMALIZED_FORMS_FILE: &str = "normalized_forms.bin";
+pub const WORD_IDS_FILE: &str = "word_ids.bin";
+pub const SYNONYM_GROUPS_FILE: &str = "synonym_groups.bin";
+pub const SYNONYMS_FILE: &str = "synonyms.txt";
+
+/// Same preset as `tar -J`.
+const XZ_LEVEL: u32 = 6;
+
+/// `metadata.json` of a release bundle.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BundleMetadata {
+    pub sudachidict_repo: String,
+    pub sudachidict_release_tag: String,
+    pub sudachidict_dict_version: String,
+    pub edition: String,
+    pub sudachi_repo: String,
+    pub sudachi_version: String,
+    pub sudachi_tag: String,
+    pub vibrato_ref: String,
+    pub compat_target: String,
+    pub compat_mode: String,
+    pub feature_schema: String,
+    pub rules_profile: String,
+    pub rewrite_def_included: bool,
+    pub lex_rows_written: usize,
+    pub skipped_negative_conn_ids: usize,
+    /// Rows with `-1,-1` connection ids, kept out of the lexicon for A/B splits.
+    #[serde(default)]
+    pub split_only_rows: usize,
+    pub normalized_pos_rows: usize,
+    pub fallback_ctype_rows: usize,
+    pub fallback_cform_rows: usize,
+    pub unresolved_base_refs: usize,
+    pub built_at_utc: String,
+    pub dictionary_file: String,
+    /// Hex SHA-256 of every other file in the bundle, keyed by file name.
+    pub sha256: BTreeMap<String, String>,
+    /// Build manifest the bundle was produced from, when built with `build`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<BuildManifest>,
+}
+
+impl BundleMetadata {
+    /// Copies the feature schema and lexicon counters of a conversion run.
+    pub fn record_stats(&mut self, stats: &ConversionStats) {
+        self.feature_schema = stats.feature_schema.clone();
+        self.lex_rows_written = stats.written;
+        self.skipped_negative_conn_ids = stats.skipped_negative_conn_ids;
+        self.split_only_rows = stats.split_only_rows;
+        self.normalized_pos_rows = stats.normalized_pos_rows;
+        self.fallback_ctype_rows = stats.fallback_ctype_rows;
+        self.fallback_cform_rows = stats.fallback_cform_rows;
+        self.unresolved_base_refs = stats.unresolved_base_refs;
+    }
+}
+
+/// A release bundle: one top-level directory holding `metadata.json` and the files it lists.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bundle {
+    /// Directory name inside the tarball (the asset name without `.tar.xz`).
+    pub root: String,
+    pub metadata: BundleMetadata,
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl Bundle {
+    pub fn new<S: Into<String>>(root: S, metadata: BundleMetadata) -> Self {
+        Self {
+            root: root.into(),
+            metadata,
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a file and records its digest in the metadata.
+    pub fn add_file<S: Into<String>>(&mut self, name: S, data: Vec<u8>) {
+        let name = name.into();
+        if name == REWRITE_FILE {
+            self.metadata.rewrite_def_included = true;
+        }
+        self.metadata.sha256.insert(name.clone(), sha256_hex(&data));
+        self.files.insert(name, data);
+    }
+
+    pub fn file(&self, name: &str) -> Option<&[u8]> {
+        self.files.get(name).map(Vec::as_slice)
+    }
+
+    /// Parses the bundled `stats.json`, if any.
+    pub fn stats(&self) -> Result<Option<ConversionStats>> {
+        self.file(STATS_FILE)
+            .map(|data| {
+                serde_json::from_slice(data)
+                    .with_context(|| format!("failed to parse {} in {}", STATS_FILE, self.root))
+            })
+            .transpose()
+    }
+
+    /// File names other than `metadata.json`, sorted.
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    /// Checks that the dictionary is present and every file matches its listed digest.
+    pub fn validate(&self) -> Result<()> {
+        if !self.files.contains_key(&self.metadata.dictionary_file) {
+            return Err(anyhow!(
+                "bundle {} has no dictionary file {}",
+                self.root,
+                self.metadata.dictionary_file
+            ));
+        }
+        if self.metadata.rewrite_def_included != self.files.contains_key(REWRITE_FILE) {
+            return Err(anyhow!(
+                "bundle {}: rewrite_def_included={} does not match its files",
+                self.root,
+                self.metadata.rewrite_def_included
+            ));
+        }
+        for name in self.files.keys() {
+            if !self.metadata.sha256.contains_key(name) {
+                return Err(anyhow!("bundle {}: {} has no digest", self.root, name));
+            }
+        }
+        for (name, expected) in &self.metadata.sha256 {
+            let data = self
+                .files
+                .get(name)
+                .ok_or_else(|| anyhow!("bundle {}: {} is missing", self.root, name))?;
+            let actual = sha256_hex(data);
+            if &actual != expected {
+                return Err(anyhow!(
+                    "bundle {}: sha256 mismatch for {}: expected {}, got {}",
+                    self.root,
+                    name,
+                    expected,
+                    actual
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a reproducible tar.xz: sorted entries, zero mtimes and root ownership.
+    pub fn write_tar_xz<W: Write>(&self, output: W) -> Result<()> {
+        self.validate()?;
+        let mut metadata = serde_json::to_vec_pretty(&self.metadata)?;
+        metadata.push(b'\n');
+
+        let mut entries: Vec<(&str, &[u8])> = self
+            .files
+            .iter()
+            .map(|(name, data)| (name.as_str(), data.as_slice()))
+            .collect();
+        entries.push((METADATA_FILE, &metadata));
+        entries.sort_by_key(|(name, _)| *name);
+
+        let mut builder = Builder::new(XzEncoder::new(output, XZ_LEVEL));
+        let mut header = deterministic_header(EntryType::Directory, 0o755, 0);
+        builder.append_data(&mut header, format!("{}/", self.root), std::io::empty())?;
+        for (name, data) in entries {
+            let mut header = deterministic_header(EntryType::Regular, 0o644, data.len() as u64);
+            builder
+                .append_data(&mut header, format!("{}/{}", self.root, name), data)
+                .with_context(|| format!("failed to add {} to the bundle", name))?;
+        }
+        builder.into_inner()?.finish()?.flush()?;
+        Ok(())
+    }
+
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let file =
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        self.write_tar_xz(BufWriter::new(file))
+    }
+
+    /// Reads and validates a bundle written by [`Self::write_tar_xz`].
+    pub fn read_tar_xz<R: Read>(input: R) -> Result<Self> {
+        let mut archive = Archive::new(XzDecoder::new(input));
+        let mut root: Option<String> = None;
+        let mut metadata = None;
+        let mut files = BTreeMap::new();
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            let mut components = path.components().filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            });
+            let (Some(dir), name, None) = (components.next(), components.next(), components.next())
+            else {
+                return Err(anyhow!("unexpected bundle entry {}", path.display()));
+            };
+            match &root {
+                Some(root) if *root != dir => {
+                    return Err(anyhow!(
+                        "bundle has more than one top-level directory: {} and {}",
+                        root,
+                        dir
+                    ))
+                }
+                Some(_) => {}
+                None => root = Some(dir),
+            }
+
+            let Some(name) = name else {
+                continue;
+            };
+            if !entry.header().entry_type().is_file() {
+                return Err(anyhow!("unexpected bundle entry {}", path.display()));
+            }
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            if name == METADATA_FILE {
+                metadata = Some(
+                    serde_json::from_slice::<BundleMetadata>(&data)
+                        .context("failed to parse metadata.json")?,
+                );
+            } else {
+                files.insert(name, data);
+            }
+        }
+
+        let bundle = Self {
+            root: root.ok_or_else(|| anyhow!("bundle is empty"))?,
+            metadata: metadata.ok_or_else(|| anyhow!("bundle has no {}", METADATA_FILE))?,
+            files,
+        };
+        bundle.validate()?;
+        Ok(bundle)
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        Self::read_tar_xz(BufReader::new(file))
+            .with_context(|| format!("invalid bundle {}", path.display()))
+    }
+}
+
+fn deterministic_header(entry_type: EntryType, mode: u32, size: u64) -> Header {
+    let mut header = Header::new_gnu();
+    header.set_entry_type(entry_type);
+    header.set_mode(mode);
+    header.set_size(size);
+    header.set_mtime(0);
+    header.set_uid(0);
+    header.set_gid(0);
+    header
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+/// Like [`sha256_hex`], streaming the file instead of loading it.
+pub fn sha256_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut BufReader::new(file), &mut hasher)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,419 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+/// Highest code point Vibrato's char.def accepts.
+pub(crate) const MAX_CODE_POINT: u32 = 0xFFFF;
+/// Most categories Vibrato 0.5 can hold; its `CharInfo` has an 18-bit category set.
+pub(crate) const MAX_CATEGORIES: usize = 18;
+/// Highest `length` Vibrato 0.5 accepts; its `CharInfo` stores it in 4 bits.
+pub(crate) const MAX_CATEGORY_LENGTH: u16 = 15;
+/// Sudachi-only range flag that Vibrato does not support.
+pub(crate) const NOOOVBOW: &str = "NOOOVBOW";
+/// Prefix of the categories [`CharDefinition::emulate_nooovbow`] adds.
+pub const NOOOVBOW_PREFIX: &str = "NOOOVBOW_";
+
+/// A character category line: `NAME INVOKE GROUP LENGTH`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharCategory {
+    pub name: String,
+    /// Always run unknown-word processing, even when a lexicon word matches.
+    pub invoke: bool,
+    /// Group consecutive characters of the category into one unknown word.
+    pub group: bool,
+    /// Also emit unknown words of 1..=`length` characters.
+    pub length: u16,
+}
+
+/// Code points `start..=end` mapped to categories, the first being the base one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharRange {
+    pub start: u32,
+    pub end: u32,
+    pub categories: Vec<String>,
+}
+
+impl CharRange {
+    /// `0xXXXX` or `0xXXXX..0xYYYY`, as written in char.def.
+    pub fn code_points(&self) -> String {
+        if self.start == self.end {
+            format!("0x{:04X}", self.start)
+        } else {
+            format!("0x{:04X}..0x{:04X}", self.start, self.end)
+        }
+    }
+}
+
+/// One definition line of a char.def file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CharDefLine {
+    Category(CharCategory),
+    Range(CharRange),
+}
+
+/// Parses the definition lines of `input` as written, with their 1-based line numbers.
+pub fn parse_char_lines<R: Read>(name: &str, input: R) -> Result<Vec<(usize, CharDefLine)>> {
+    let mut lines = Vec::new();
+    for (index, line) in BufReader::new(input).lines().enumerate() {
+        let line = line.with_context(|| format!("failed to read {}", name))?;
+        let line_no = index + 1;
+        let body = line.split('#').next().unwrap_or("").trim();
+        if body.is_empty() {
+            continue;
+        }
+        let line = if body.starts_with("0x") {
+            CharDefLine::Range(parse_range(body, name, line_no)?)
+        } else {
+            CharDefLine::Category(parse_category(body, name, line_no)?)
+        };
+        lines.push((line_no, line));
+    }
+    Ok(lines)
+}
+
+/// What [`CharDefinition::merge_reader`] read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CharMergeCounts {
+    /// Category and range lines applied.
+    pub lines: usize,
+    /// Range lines whose only category was NOOOVBOW; their flag is still kept.
+    pub dropped_ranges: usize,
+}
+
+/// A parsed char.def.
+///
+/// Later definitions win: a category line replaces the earlier one of the same
+/// name in place, and a range line takes over the code points it covers.
+/// Ranges are kept sorted, non-overlapping and coalesced.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CharDefinition {
+    categories: Vec<CharCategory>,
+    ranges: Vec<CharRange>,
+    /// Code point ranges flagged NOOOVBOW, in line order.
+    no_oov_bow: Vec<(u32, u32)>,
+}
+
+impl CharDefinition {
+    pub fn from_reader<R: Read>(name: &str, input: R) -> Result<Self> {
+        let mut definition = Self::default();
+        definition.merge_reader(name, input)?;
+        Ok(definition)
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut definition = Self::default();
+        definition.merge_path(path)?;
+        Ok(definition)
+    }
+
+    /// [`Self::merge_reader`] for a file, named by its file name in errors.
+    pub fn merge_path<P: AsRef<Path>>(&mut self, path: P) -> Result<CharMergeCounts> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        self.merge_reader(&name, file)
+    }
+
+    /// Applies the lines of `input` on top of this definition.
+    ///
+    /// NOOOVBOW is taken out of the range categories and kept as a flag that
+    /// later lines do not clear, as in Sudachi.
+    pub fn merge_reader<R: Read>(&mut self, name: &str, input: R) -> Result<CharMergeCounts> {
+        let mut counts = CharMergeCounts::default();
+        for (_, line) in parse_char_lines(name, input)? {
+            match line {
+                CharDefLine::Category(category) => self.set_category(category),
+                CharDefLine::Range(mut range) => {
+                    if range.categories.iter().any(|category| category == NOOOVBOW) {
+                        self.no_oov_bow.push((range.start, range.end));
+                    }
+                    range.categories.retain(|category| category != NOOOVBOW);
+                    if range.categories.is_empty() {
+                        counts.dropped_ranges += 1;
+                        continue;
+                    }
+                    self.set_range(range);
+                }
+            }
+            counts.lines += 1;
+        }
+        Ok(counts)
+    }
+
+    /// Applies `other` on top of this definition.
+    pub fn merge(&mut self, other: &CharDefinition) {
+        self.no_oov_bow.extend_from_slice(&other.no_oov_bow);
+        for category in &other.categories {
+            self.set_category(category.clone());
+        }
+        for range in &other.ranges {
+            self.set_range(range.clone());
+        }
+    }
+
+    /// Adds a category, or replaces the one of the same name in place.
+    pub fn set_category(&mut self, category: CharCategory) {
+        match self
+            .categories
+            .iter_mut()
+            .find(|existing| existing.name == category.name)
+        {
+            Some(existing) => *existing = category,
+            None => self.categories.push(category),
+        }
+    }
+
+    /// Maps `range` to its categories, splitting the ranges it overlaps.
+    pub fn set_range(&mut self, mut range: CharRange) {
+        let mut seen = Vec::with_capacity(range.categories.len());
+        range.categories.retain(|category| {
+            let new = !seen.contains(category);
+            seen.push(category.clone());
+            new
+        });
+
+        let mut ranges = Vec::with_capacity(self.ranges.len() + 2);
+        for existing in self.ranges.drain(..) {
+            if existing.end < range.start || existing.start > range.end {
+                ranges.push(existing);
+                continue;
+            }
+            if existing.start < range.start {
+                ranges.push(CharRange {
+                    start: existing.start,
+                    end: range.start - 1,
+                    categories: existing.categories.clone(),
+                });
+            }
+            if existing.end > range.end {
+                ranges.push(CharRange {
+                    start: range.end + 1,
+                    end: existing.end,
+                    categories: existing.categories,
+                });
+            }
+        }
+        ranges.push(range);
+        ranges.sort_by_key(|range| range.start);
+
+        for range in ranges {
+            match self.ranges.last_mut() {
+                Some(last)
+                    if last.end + 1 == range.start && last.categories == range.categories =>
+                {
+                    last.end = range.end;
+                }
+                _ => self.ranges.push(range),
+            }
+        }
+    }
+
+    /// Categories in first-declaration order.
+    pub fn categories(&self) -> &[CharCategory] {
+        &self.categories
+    }
+
+    pub fn category(&self, name: &str) -> Option<&CharCategory> {
+        self.categories
+            .iter()
+            .find(|category| category.name == name)
+    }
+
+    /// Ranges in code point order.
+    pub fn ranges(&self) -> &[CharRange] {
+        &self.ranges
+    }
+
+    /// Code point ranges flagged NOOOVBOW, which Sudachi does not start unknown words at.
+    pub fn no_oov_bow_ranges(&self) -> &[(u32, u32)] {
+        &self.no_oov_bow
+    }
+
+    /// Emulates NOOOVBOW for Vibrato, clearing the flags.
+    ///
+    /// Each flagged code point gets a `NOOOVBOW_<BASE>` category put in front of
+    /// its categories (`DEFAULT` when unmapped), declared `0 0 0`.
+    /// Vibrato then takes invoke, group and length from it, so an unknown word
+    /// starts there only when no lexicon word does, and is one character long,
+    /// while words starting before still group through it via the original
+    /// categories. Returns `(NOOOVBOW_<BASE>, BASE)` pairs in first-use order;
+    /// see [`crate::convert_unk::append_nooovbow_unknown_rows`].
+    pub fn emulate_nooovbow(&mut self) -> Vec<(String, String)> {
+        let mut added: Vec<(String, String)> = Vec::new();
+        for (start, end) in std::mem::take(&mut self.no_oov_bow) {
+            for mut piece in self.split(start, end) {
+                if piece.categories[0].starts_with(NOOOVBOW_PREFIX) {
+                    continue;
+                }
+                let base = piece.categories[0].clone();
+                let compat = format!("{}{}", NOOOVBOW_PREFIX, base);
+                if !added.iter().any(|(name, _)| *name == compat) {
+                    added.push((compat.clone(), base));
+                }
+                piece.categories.insert(0, compat);
+                self.set_range(piece);
+            }
+        }
+        for (compat, _) in &added {
+            self.set_category(CharCategory {
+                name: compat.clone(),
+                invoke: false,
+                group: false,
+                length: 0,
+            });
+        }
+        added
+    }
+
+    /// Splits `start..=end` along the ranges, unmapped pieces getting `DEFAULT`.
+    pub fn split(&self, start: u32, end: u32) -> Vec<CharRange> {
+        let mut pieces = Vec::new();
+        let mut next = u64::from(start);
+        for range in &self.ranges {
+            if range.end < start || range.start > end {
+                continue;
+            }
+            let piece_start = range.start.max(start);
+            if next < u64::from(piece_start) {
+                pieces.push(CharRange {
+                    start: next as u32,
+                    end: piece_start - 1,
+                    categories: vec!["DEFAULT".to_string()],
+                });
+            }
+            let piece_end = range.end.min(end);
+            pieces.push(CharRange {
+                start: piece_start,
+                end: piece_end,
+                categories: range.categories.clone(),
+            });
+            next = u64::from(piece_end) + 1;
+        }
+        if next <= u64::from(end) {
+            pieces.push(CharRange {
+                start: next as u32,
+                end,
+                categories: vec!["DEFAULT".to_string()],
+            });
+        }
+        pieces
+    }
+
+    /// Categories of a code point; `None` when it falls back to `DEFAULT`.
+    pub fn categories_of(&self, code_point: u32) -> Option<&[String]> {
+        let index = self.ranges.partition_point(|range| range.end < code_point);
+        let range = self.ranges.get(index)?;
+        (range.start <= code_point).then_some(range.categories.as_slice())
+    }
+
+    /// Writes the category lines, a blank line, then the range lines.
+    ///
+    /// Comments are not kept, so equal definitions serialize to the same bytes.
+    pub fn write<W: Write>(&self, mut output: W) -> Result<()> {
+        for category in &self.categories {
+            writeln!(
+                output,
+                "{} {} {} {}",
+                category.name,
+                u8::from(category.invoke),
+                u8::from(category.group),
+                category.length
+            )?;
+        }
+        writeln!(output)?;
+        for range in &self.ranges {
+            writeln!(
+                output,
+                "{} {}",
+                range.code_points(),
+                range.categories.join(" ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_category(body: &str, name: &str, line_no: usize) -> Result<CharCategory> {
+    let cols: Vec<&str> = body.split_whitespace().collect();
+    if cols.len() < 4 {
+        return Err(anyhow!(
+            "invalid char category at {} line {}: expected NAME INVOKE GROUP LENGTH, got '{}'",
+            name,
+            line_no,
+            body
+        ));
+    }
+    let flag = |value: &str, column: &str| match value {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(anyhow!(
+            "failed to parse {}='{}' at {} line {}",
+            column,
+            value,
+            name,
+            line_no
+        )),
+    };
+    Ok(CharCategory {
+        name: cols[0].to_string(),
+        invoke: flag(cols[1], "invoke")?,
+        group: flag(cols[2], "group")?,
+        length: cols[3].parse().with_context(|| {
+            format!(
+                "failed to parse length='{}' at {} line {}",
+                cols[3], name, line_no
+            )
+        })?,
+    })
+}
+
+fn parse_range(body: &str, name: &str, line_no: usize) -> Result<CharRange> {
+    let mut cols = body.split_whitespace();
+    let codes = cols.next().unwrap_or("");
+    let code_point = |value: &str| {
+        value
+            .strip_prefix("0x")
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| {
+                anyhow!(
+                    "failed to parse code point='{}' at {} line {}",
+                    value,
+                    name,
+                    line_no
+                )
+            })
+    };
+    let (start, end) = match codes.split_once("..") {
+        Some((start, end)) => (code_point(start)?, code_point(end)?),
+        None => (code_point(codes)?, code_point(codes)?),
+    };
+    if start > end {
+        return Err(anyhow!(
+            "invalid char range {} at {} line {}: start is after end",
+            codes,
+            name,
+            line_no
+        ));
+    }
+
+    let categories: Vec<String> = cols.map(str::to_string).collect();
+    if categories.is_empty() {
+        return Err(anyhow!(
+            "invalid char range {} at {} line {}: no category",
+            codes,
+            name,
+            line_no
+        ));
+    }
+    Ok(CharRange {
+        start,
+        end,
+        categories,
+    })
+}
@@ -2,6 +2,9 @@ use std::path::PathBuf;
 
 use clap::{Args, Parser, Subcommand};
 
+use crate::compile::DEFAULT_SMOKE_TEXT;
+use crate::options::{CharGenMode, ErrorPolicy, NoOovBowPolicy};
+
 #[derive(Debug, Parser)]
 #[command(name = "sudachi-vibrato-converter")]
 #[command(about = "Convert SudachiDict resources to Vibrato/jpreprocess-compatible format")]
@@ -11,14 +14,38 @@ pub struct Cli {
 }
 
 #[derive(Debug, Subcommand)]
+#[allow(clippy::large_enum_variant)]
 pub enum Commands {
     Convert(ConvertArgs),
+    /// Convert Sudachi user dictionary CSVs into a Vibrato user lexicon.
+    ConvertUser(ConvertUserArgs),
+    /// Compile converted resources into a zstd-compressed Vibrato dictionary.
+    Compile(CompileArgs),
+    /// Package a compiled dictionary into the release tar.xz with metadata.json.
+    Package(PackageArgs),
+    /// Run convert, compile, verify and package from a TOML build manifest.
+    Build(BuildArgs),
+    /// Check a char.def/unk.def pair for problems Vibrato rejects or hides.
+    Lint(LintArgs),
+    /// Derive char.def ranges from Unicode scripts and general categories.
+    GenChar(GenCharArgs),
 }
 
 #[derive(Debug, Args)]
 pub struct ConvertArgs {
+    /// Sudachi lexicon CSV; repeat in word-id order (small, core, notcore).
+    #[arg(
+        long = "lex-in",
+        required_unless_present = "dic_in",
+        conflicts_with = "dic_in"
+    )]
+    pub lex_in: Vec<PathBuf>,
+    /// Sudachi binary system dictionary, instead of --lex-in.
     #[arg(long)]
-    pub lex_in: PathBuf,
+    pub dic_in: Option<PathBuf>,
+    /// Writes the connection matrix of --dic-in as matrix.def.
+    #[arg(long, requires = "dic_in")]
+    pub matrix_out: Option<PathBuf>,
     #[arg(long)]
     pub lex_out: PathBuf,
     #[arg(long)]
@@ -31,10 +58,15 @@ pub struct ConvertArgs {
     pub char_out: PathBuf,
     #[arg(long)]
     pub stats_out: PathBuf,
+    /// Writes the JSON stats report with per-category breakdowns and input digests.
+    #[arg(long)]
+    pub stats_json_out: Option<PathBuf>,
     #[arg(long = "lex-append")]
     pub lex_append: Vec<PathBuf>,
     #[arg(long = "char-append")]
     pub char_append: Vec<PathBuf>,
+    /// unk.def rows to append; `*` ids and cost are taken from the first
+    /// `--unk-in` row with the same features.
     #[arg(long = "unk-append")]
     pub unk_append: Vec<PathBuf>,
     #[arg(long, requires = "rewrite_out")]
@@ -43,4 +75,212 @@ pub struct ConvertArgs {
     pub rewrite_out: Option<PathBuf>,
     #[arg(long = "rewrite-append", requires_all = ["rewrite_in", "rewrite_out"])]
     pub rewrite_append: Vec<PathBuf>,
+    /// Sudachi -> IPADIC POS mapping table (defaults to the bundled table).
+    #[arg(long)]
+    pub pos_map: Option<PathBuf>,
+    /// Writes which POS mapping rule each distinct source tuple hit.
+    #[arg(long)]
+    pub pos_map_report: Option<PathBuf>,
+    /// Feature column layout: mecab9, jpreprocess12 or unidic.
+    /// Defaults to mecab9, or jpreprocess12 when --accent-in is given.
+    #[arg(long)]
+    pub feature_schema: Option<String>,
+    /// Accent source CSV (`surface,read,pos,accent[,chain_rule[,chain_flag]]`).
+    #[arg(long = "accent-in")]
+    pub accent_in: Vec<PathBuf>,
+    /// Writes lexicon keys that had no accent source entry.
+    #[arg(long, requires = "accent_in")]
+    pub accent_unmatched_out: Option<PathBuf>,
+    /// Writes every distinct POS/ctype/cform value that fell back, as CSV (or JSON for `.json`).
+    #[arg(long)]
+    pub fallback_report: Option<PathBuf>,
+    /// Fails when POS, ctype or cform fallback rows exceed this share of the rows written.
+    #[arg(long)]
+    pub max_fallback_rate: Option<f64>,
+    /// Writes rows with `-1,-1` connection ids, keyed by Sudachi word id, as `split_info.csv`.
+    #[arg(long)]
+    pub split_info_out: Option<PathBuf>,
+    /// Writes the A/B units of each lexicon row as `word_units.bin`.
+    #[arg(long, requires = "split_info_out")]
+    pub word_units_out: Option<PathBuf>,
+    /// Writes the Sudachi normalized form of each lexicon row as `normalized_forms.bin`.
+    #[arg(long)]
+    pub normalized_forms_out: Option<PathBuf>,
+    /// Writes the Sudachi word id and source file of each lexicon row as `word_ids.bin`.
+    #[arg(long)]
+    pub word_ids_out: Option<PathBuf>,
+    /// Writes the synonym group ids of each lexicon row as `synonym_groups.bin`.
+    #[arg(long)]
+    pub synonym_groups_out: Option<PathBuf>,
+    /// Malformed lexicon/unk rows: fail (default), skip, or collect into --rejects-out.
+    #[arg(long, default_value = "fail")]
+    pub on_error: ErrorPolicy,
+    /// Writes rows dropped under --on-error=collect (`file,line,reason,raw`).
+    #[arg(long, required_if_eq("on_error", "collect"))]
+    pub rejects_out: Option<PathBuf>,
+    /// char.def NOOOVBOW flags: emulate (default) with extra categories and unk rows, or strip.
+    #[arg(long, default_value = "emulate")]
+    pub nooovbow: NoOovBowPolicy,
+}
+
+#[derive(Debug, Args)]
+pub struct ConvertUserArgs {
+    /// Sudachi user dictionary CSV; repeat in word-id order.
+    #[arg(long = "user-in", required = true)]
+    pub user_in: Vec<PathBuf>,
+    /// System lexicon CSV the user dictionary targets; repeat in word-id order.
+    #[arg(
+        long = "lex-in",
+        required_unless_present = "dic_in",
+        conflicts_with = "dic_in",
+        requires = "matrix_in"
+    )]
+    pub lex_in: Vec<PathBuf>,
+    /// matrix.def of the system dictionary given with --lex-in.
+    #[arg(long)]
+    pub matrix_in: Option<PathBuf>,
+    /// Sudachi binary system dictionary, instead of --lex-in/--matrix-in.
+    #[arg(long)]
+    pub dic_in: Option<PathBuf>,
+    /// Vibrato user lexicon CSV.
+    #[arg(long)]
+    pub out: PathBuf,
+    #[arg(long)]
+    pub stats_out: Option<PathBuf>,
+    /// Sudachi -> IPADIC POS mapping table (defaults to the bundled table).
+    #[arg(long)]
+    pub pos_map: Option<PathBuf>,
+    /// Feature column layout; must match the system dictionary.
+    #[arg(long)]
+    pub feature_schema: Option<String>,
+    /// Accent source CSV (`surface,read,pos,accent[,chain_rule[,chain_flag]]`).
+    #[arg(long = "accent-in")]
+    pub accent_in: Vec<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct LintArgs {
+    /// `char.def`, converted or as shipped by Sudachi.
+    #[arg(long)]
+    pub char_in: PathBuf,
+    /// Applied after --char-in in order, like `convert --char-append`.
+    #[arg(long = "char-append")]
+    pub char_append: Vec<PathBuf>,
+    #[arg(long)]
+    pub unk_in: PathBuf,
+    #[arg(long = "unk-append")]
+    pub unk_append: Vec<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct GenCharArgs {
+    /// Sudachi `char.def` the derived ranges are merged into.
+    #[arg(long)]
+    pub char_in: PathBuf,
+    /// Applied after --char-in in order, like `convert --char-append`.
+    #[arg(long = "char-append")]
+    pub char_append: Vec<PathBuf>,
+    /// Writes the changed ranges as char.def lines, to pass as `--char-append`.
+    #[arg(long)]
+    pub out: PathBuf,
+    /// Writes every changed range as CSV (`code_points,count,from,to,written`).
+    #[arg(long)]
+    pub report_out: Option<PathBuf>,
+    /// fill (default) only assigns code points char.def leaves to DEFAULT; override assigns all.
+    #[arg(long, default_value = "fill")]
+    pub mode: CharGenMode,
+    /// Derived category to apply (NUMERIC, ALPHA, HIRAGANA, KATAKANA, KANJI, EMOJI);
+    /// repeatable, defaults to all.
+    #[arg(long = "category")]
+    pub category: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct CompileArgs {
+    /// Converted lexicon (`lex.csv`).
+    #[arg(short = 'l', long)]
+    pub lex_in: PathBuf,
+    #[arg(short = 'm', long)]
+    pub matrix_in: PathBuf,
+    /// Converted `char.def`.
+    #[arg(short = 'c', long)]
+    pub char_in: PathBuf,
+    /// Converted `unk.def`.
+    #[arg(short = 'u', long)]
+    pub unk_in: PathBuf,
+    /// Output `system.dic.zst`.
+    #[arg(short = 'o', long)]
+    pub out: PathBuf,
+    /// Sentence tokenized with the written dictionary (repeatable).
+    #[arg(long = "smoke-text", default_value = DEFAULT_SMOKE_TEXT)]
+    pub smoke_text: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct PackageArgs {
+    /// Compiled `system.dic.zst`.
+    #[arg(long)]
+    pub dict_in: PathBuf,
+    /// JSON stats report written by `convert --stats-json-out`; bundled as `stats.json`.
+    #[arg(long)]
+    pub stats_in: PathBuf,
+    #[arg(long)]
+    pub license_in: PathBuf,
+    #[arg(long)]
+    pub legal_in: PathBuf,
+    #[arg(long)]
+    pub rewrite_in: Option<PathBuf>,
+    /// Extra file to bundle under its own name (e.g. `split_info.csv`); repeatable.
+    #[arg(long = "sidecar-in")]
+    pub sidecar_in: Vec<PathBuf>,
+    /// Output tar.xz; its top-level directory is the file name without `.tar.xz`.
+    #[arg(long)]
+    pub out: PathBuf,
+    #[arg(long)]
+    pub sudachidict_repo: String,
+    #[arg(long)]
+    pub sudachidict_release_tag: String,
+    #[arg(long)]
+    pub dict_version: String,
+    #[arg(long)]
+    pub edition: String,
+    #[arg(long)]
+    pub sudachi_repo: String,
+    #[arg(long)]
+    pub sudachi_version: String,
+    #[arg(long)]
+    pub sudachi_tag: String,
+    #[arg(long)]
+    pub vibrato_ref: String,
+    #[arg(long)]
+    pub compat_target: String,
+    #[arg(long)]
+    pub compat_mode: String,
+    #[arg(long)]
+    pub rules_profile: String,
+    /// Build time recorded in metadata.json (`YYYY-MM-DDTHH:MM:SSZ`).
+    #[arg(long)]
+    pub built_at_utc: String,
+}
+
+#[derive(Debug, Args)]
+pub struct BuildArgs {
+    /// TOML build manifest; input paths are relative to it.
+    #[arg(long)]
+    pub manifest: PathBuf,
+    /// Directory the release tar.xz is written to.
+    #[arg(long)]
+    pub out_dir: PathBuf,
+    /// Directory for intermediate files (defaults to `<out-dir>/work`).
+    #[arg(long)]
+    pub work_dir: Option<PathBuf>,
+    /// Build time for metadata.json; defaults to SOURCE_DATE_EPOCH or now.
+    #[arg(long)]
+    pub built_at_utc: Option<String>,
+    /// Writes release outputs as `key=value` lines (e.g. for `$GITHUB_OUTPUT`).
+    #[arg(long)]
+    pub outputs_out: Option<PathBuf>,
+    /// Writes Markdown release notes generated from metadata.json and stats.json.
+    #[arg(long)]
+    pub release_notes_out: Option<PathBuf>,
 }
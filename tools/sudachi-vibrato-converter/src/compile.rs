@@ -0,0 +1,145 @@
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, Context, Result};
+use csv::ReaderBuilder;
+use vibrato::dictionary::SystemDictionaryBuilder;
+use vibrato::{Dictionary, Tokenizer};
+
+use crate::convert_user::MatrixSize;
+
+/// zstd level used by Vibrato's own `compile` tool.
+const ZSTD_LEVEL: i32 = 19;
+
+/// Version of the `vibrato` crate dictionaries are compiled with; pinned in Cargo.toml.
+pub const VIBRATO_VERSION: &str = "0.5.2";
+
+/// Sentence tokenized after compilation when none is given.
+pub const DEFAULT_SMOKE_TEXT: &str = "東京都に行く";
+
+/// Compiles converted `lex.csv`, `matrix.def`, `char.def` and `unk.def` in-process.
+///
+/// Lexicon and unknown-word rows are checked against the matrix size first, since
+/// Vibrato only reports that *some* row has an invalid connection id.
+pub fn compile_dictionary(
+    lex: &[u8],
+    matrix: &[u8],
+    char_def: &[u8],
+    unk: &[u8],
+) -> Result<Dictionary> {
+    let matrix_size = MatrixSize::from_matrix_def(matrix)?;
+    validate_entries("lex.csv", lex, matrix_size)?;
+    validate_entries("unk.def", unk, matrix_size)?;
+
+    SystemDictionaryBuilder::from_readers(lex, matrix, char_def, unk)
+        .context("failed to compile the Vibrato dictionary")
+}
+
+/// Checks that a release's `vibrato_ref` (`v0.5.2`) names [`VIBRATO_VERSION`],
+/// which decides the format of the dictionary it ships.
+pub fn check_vibrato_ref(vibrato_ref: &str) -> Result<()> {
+    if vibrato_ref.strip_prefix('v').unwrap_or(vibrato_ref) == VIBRATO_VERSION {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "vibrato_ref '{}' does not match the linked vibrato crate {}",
+            vibrato_ref,
+            VIBRATO_VERSION
+        ))
+    }
+}
+
+/// Checks `surface,left_id,right_id,cost,feature...` rows the way Vibrato parses them.
+pub fn validate_entries<R: Read>(name: &str, input: R, matrix_size: MatrixSize) -> Result<usize> {
+    let reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(input);
+
+    let mut rows = 0;
+    for (line_no, record) in reader.into_records().enumerate() {
+        let line_no = line_no + 1;
+        let record = record.with_context(|| format!("failed to read {} line {}", name, line_no))?;
+        if record.len() < 5 {
+            return Err(anyhow!(
+                "invalid row at {} line {}: expected >=5 columns, got {}",
+                name,
+                line_no,
+                record.len()
+            ));
+        }
+
+        let field = |index: usize, column: &str| {
+            let value = record.get(index).unwrap_or("");
+            value.parse::<u16>().with_context(|| {
+                format!(
+                    "failed to parse {}='{}' at {} line {}",
+                    column, value, name, line_no
+                )
+            })
+        };
+        let left = field(1, "left_id")?;
+        let right = field(2, "right_id")?;
+        let cost = record.get(3).unwrap_or("");
+        cost.parse::<i16>().with_context(|| {
+            format!(
+                "failed to parse cost='{}' at {} line {}",
+                cost, name, line_no
+            )
+        })?;
+
+        if !matrix_size.contains(i32::from(left), i32::from(right)) {
+            return Err(anyhow!(
+                "connection ids {},{} exceed the matrix size {}x{} at {} line {}",
+                left,
+                right,
+                matrix_size.right_ids,
+                matrix_size.left_ids,
+                name,
+                line_no
+            ));
+        }
+        rows += 1;
+    }
+    Ok(rows)
+}
+
+/// Writes `system.dic.zst`.
+pub fn write_compressed_dictionary<W: Write>(dictionary: &Dictionary, output: W) -> Result<()> {
+    let mut encoder = zstd::Encoder::new(output, ZSTD_LEVEL)?;
+    dictionary
+        .write(&mut encoder)
+        .context("failed to write the Vibrato dictionary")?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
+pub fn read_compressed_dictionary<R: Read>(input: R) -> Result<Dictionary> {
+    let decoder = zstd::Decoder::new(input)?;
+    Dictionary::read(decoder).context("failed to read the Vibrato dictionary")
+}
+
+/// Tokenizes each text and returns the surfaces per text.
+///
+/// Fails when a text yields no tokens or the surfaces do not cover it.
+pub fn smoke_tokenize(dictionary: Dictionary, texts: &[&str]) -> Result<Vec<Vec<String>>> {
+    let tokenizer = Tokenizer::new(dictionary);
+    let mut worker = tokenizer.new_worker();
+
+    let mut results = Vec::with_capacity(texts.len());
+    for text in texts {
+        worker.reset_sentence(*text);
+        worker.tokenize();
+        let surfaces: Vec<String> = (0..worker.num_tokens())
+            .map(|i| worker.token(i).surface().to_string())
+            .collect();
+        if surfaces.is_empty() || surfaces.concat() != *text {
+            return Err(anyhow!(
+                "smoke tokenization of '{}' failed: got [{}]",
+                text,
+                surfaces.join("|")
+            ));
+        }
+        results.push(surfaces);
+    }
+    Ok(results)
+}
@@ -1,109 +1,376 @@
+use std::collections::BTreeMap;
 use std::io::{Read, Write};
+use std::path::Path;
 
 use anyhow::{anyhow, Context, Result};
-use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use csv::{StringRecord, WriterBuilder};
 
-use crate::normalize::{normalize_cform, normalize_ctype, normalize_pos, normalize_text_or_star};
-use crate::stats::ConversionStats;
+use crate::accent::{accent_pos, mora_count, AccentEntry};
+use crate::lexicon::{DictionaryForm, LexiconSource, WordIndex};
+use crate::normalize::{
+    infer_cform, infer_ctype, normalize_text_or_star, pronunciation_from_reading, PosRule,
+};
+use crate::normalized_form::NormalizedForms;
+use crate::options::ConvertOptions;
+use crate::schema::FeatureRow;
+use crate::split_info::{SplitInfo, SplitOnlyEntry};
+use crate::stats::{ConversionStats, PosMappingHit};
+use crate::synonyms::SynonymGroups;
+use crate::word_ids::WordIds;
+use crate::word_units::{PendingSplits, WordUnits};
+
+/// Per-row tables collected next to the lexicon, each only when its
+/// `collect_*` option is set.
+#[derive(Debug, Default)]
+pub struct LexiconSidecars {
+    /// Split-only rows, for `split_info.csv`.
+    pub split_info: SplitInfo,
+    /// A/B units of the written rows.
+    pub word_units: WordUnits,
+    pub normalized_forms: NormalizedForms,
+    /// Sudachi word id and source of the written and appended rows.
+    pub word_ids: WordIds,
+    pub synonym_groups: SynonymGroups,
+    /// Unmatched `(surface, read, pos)` accent keys with row counts.
+    pub unmatched_accent: BTreeMap<[String; 3], usize>,
+}
+
+impl LexiconSidecars {
+    pub fn write_unmatched_accent_report<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = WriterBuilder::new().from_path(path)?;
+        writer.write_record(["surface", "read", "pos", "rows"])?;
+        for (key, rows) in &self.unmatched_accent {
+            let rows = rows.to_string();
+            writer.write_record([key[0].as_str(), &key[1], &key[2], &rows])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
 
 pub fn convert_lexicon<R: Read, W: Write>(
     input: R,
     output: W,
     stats: &mut ConversionStats,
-) -> Result<()> {
-    let mut reader = ReaderBuilder::new()
-        .has_headers(false)
-        .flexible(true)
-        .from_reader(input);
+) -> Result<LexiconSidecars> {
+    convert_lexicon_with_options(input, output, &ConvertOptions::default(), stats)
+}
+
+pub fn convert_lexicon_with_options<R: Read, W: Write>(
+    mut input: R,
+    output: W,
+    options: &ConvertOptions,
+    stats: &mut ConversionStats,
+) -> Result<LexiconSidecars> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+    convert_lexicon_sources(
+        &[LexiconSource::new("lex.csv", data)],
+        output,
+        options,
+        stats,
+    )
+}
+
+/// Converts several Sudachi lexicon files as one lexicon.
+///
+/// Sources are read twice: the first pass indexes every row by Sudachi word id
+/// so that dictionary-form references (`col13`) can point into any source.
+pub fn convert_lexicon_sources<W: Write>(
+    sources: &[LexiconSource],
+    output: W,
+    options: &ConvertOptions,
+    stats: &mut ConversionStats,
+) -> Result<LexiconSidecars> {
+    stats.feature_schema = options.feature_schema.name().to_string();
+    let word_index = WordIndex::build(sources)?;
     let mut writer = WriterBuilder::new().has_headers(false).from_writer(output);
+    let mut sidecars = LexiconSidecars::default();
 
-    for (line_no, record) in reader.records().enumerate() {
-        let record =
-            record.with_context(|| format!("failed to read lex row at line {}", line_no + 1))?;
+    let mut word_id = 0;
+    let mut pending = Vec::new();
+    for source in sources {
+        for record in source.records() {
+            let (line_no, record) = record?;
+            let written = convert_row(
+                &source.name,
+                line_no,
+                word_id,
+                &record,
+                &word_index,
+                options,
+                &mut writer,
+                stats,
+                &mut sidecars,
+            )?;
+            if written && options.collect_word_ids {
+                sidecars.word_ids.push(&source.name, Some(word_id as u32))?;
+            }
+            if written && options.collect_normalized_forms {
+                sidecars
+                    .normalized_forms
+                    .push(record.get(0).unwrap_or(""), record.get(12).unwrap_or(""));
+            }
+            if written && options.collect_synonym_groups {
+                stats.invalid_synonym_group_ids +=
+                    sidecars.synonym_groups.push(record.get(18).unwrap_or(""));
+            }
+            if written && options.collect_word_units {
+                pending.push(PendingSplits {
+                    word_id: word_id as u32,
+                    surface: record.get(0).unwrap_or("").to_string(),
+                    a: record.get(15).unwrap_or("").to_string(),
+                    b: record.get(16).unwrap_or("").to_string(),
+                });
+            }
+            word_id += 1;
+        }
+    }
+    if options.collect_word_units {
+        let (word_units, unresolved_rows) =
+            WordUnits::resolve(&pending, &word_index, &sidecars.split_info);
+        sidecars.word_units = word_units;
+        stats.unresolved_split_rows += unresolved_rows;
+    }
 
-        if record.is_empty() {
-            continue;
+    writer.flush()?;
+    Ok(sidecars)
+}
+
+/// Converts one row; returns whether it was written to the lexicon.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn convert_row<W: Write>(
+    source: &str,
+    line_no: usize,
+    word_id: usize,
+    record: &StringRecord,
+    word_index: &WordIndex,
+    options: &ConvertOptions,
+    writer: &mut csv::Writer<W>,
+    stats: &mut ConversionStats,
+    sidecars: &mut LexiconSidecars,
+) -> Result<bool> {
+    let (left, right) = match parse_connection_ids(record, source, line_no) {
+        Ok(ids) => ids,
+        Err(error) => {
+            stats.reject(options.on_error, source, line_no, record, error)?;
+            return Ok(false);
         }
-        if record.len() < 11 {
-            return Err(anyhow!(
-                "invalid lex row at line {}: expected >=11 columns, got {}",
-                line_no + 1,
-                record.len()
-            ));
+    };
+
+    if left == -1 && right == -1 {
+        // Only referenced from A/B split columns: not written, but mapped so its
+        // fallbacks are counted and split_info.csv can carry it.
+        stats.split_only_rows += 1;
+        stats.record_skipped("split_only");
+        let features = feature_row(record, word_index, options, true, stats, sidecars);
+        if options.collect_split_info || options.collect_word_units {
+            sidecars
+                .split_info
+                .insert(SplitOnlyEntry::new(word_id, record, &features, options));
         }
+        return Ok(false);
+    }
+    if left < 0 || right < 0 {
+        stats.skipped_negative_conn_ids += 1;
+        stats.record_skipped("negative_conn_ids");
+        return Ok(false);
+    }
 
-        let left = parse_i32(&record, 1, "left_id", line_no + 1)?;
-        let right = parse_i32(&record, 2, "right_id", line_no + 1)?;
-        let _cost = parse_i32(&record, 3, "cost", line_no + 1)?;
+    let features = feature_row(record, word_index, options, false, stats, sidecars);
+    let mut output_row = vec![
+        features.surface.clone(),
+        record.get(1).unwrap_or("").to_string(),
+        record.get(2).unwrap_or("").to_string(),
+        record.get(3).unwrap_or("").to_string(),
+    ];
+    output_row.extend(options.feature_schema.features(&features));
 
-        if left < 0 || right < 0 {
-            stats.skipped_negative_conn_ids += 1;
-            continue;
-        }
+    writer
+        .write_record(&output_row)
+        .with_context(|| format!("failed to write lex row at {} line {}", source, line_no))?;
+    stats.written += 1;
+
+    Ok(true)
+}
+
+/// Maps POS, conjugation, base and pronunciation of a row, counting fallbacks in `stats`.
+///
+/// A `split_only` row is not a lexicon row: only its fallbacks are counted.
+fn feature_row(
+    record: &StringRecord,
+    word_index: &WordIndex,
+    options: &ConvertOptions,
+    split_only: bool,
+    stats: &mut ConversionStats,
+    sidecars: &mut LexiconSidecars,
+) -> FeatureRow {
+    let original_pos = [
+        normalize_text_or_star(record.get(5).unwrap_or("")),
+        normalize_text_or_star(record.get(6).unwrap_or("")),
+        normalize_text_or_star(record.get(7).unwrap_or("")),
+        normalize_text_or_star(record.get(8).unwrap_or("")),
+    ];
 
-        let original_pos = [
-            normalize_text_or_star(record.get(5).unwrap_or("")),
-            normalize_text_or_star(record.get(6).unwrap_or("")),
-            normalize_text_or_star(record.get(7).unwrap_or("")),
-            normalize_text_or_star(record.get(8).unwrap_or("")),
-        ];
-
-        let normalized_pos = normalize_pos(
-            record.get(5).unwrap_or(""),
-            record.get(6).unwrap_or(""),
-            record.get(7).unwrap_or(""),
-            record.get(8).unwrap_or(""),
-        );
+    let (normalized_pos, rule) = options.pos_mapping.map([
+        &original_pos[0],
+        &original_pos[1],
+        &original_pos[2],
+        &original_pos[3],
+    ]);
+    if !split_only {
+        let hit = stats
+            .pos_mapping
+            .entry(original_pos.join(","))
+            .or_insert_with(|| PosMappingHit {
+                source: original_pos.clone(),
+                target: normalized_pos.clone(),
+                rule_line: rule.map(|rule| rule.line),
+                rows: 0,
+            });
+        hit.rows += 1;
         if original_pos != normalized_pos {
             stats.normalized_pos_rows += 1;
         }
+    }
+    let surface = record.get(0).unwrap_or("");
+    if rule.is_none_or(PosRule::is_catch_all) {
+        stats.fallback_pos_rows += 1;
+        stats
+            .fallback_pos_values
+            .entry(original_pos.join(","))
+            .or_default()
+            .record(surface);
+    }
 
-        let (ctype, ctype_fallback) = normalize_ctype(record.get(9).unwrap_or(""));
-        if ctype_fallback {
-            stats.fallback_ctype_rows += 1;
+    let base = match word_index.resolve_dictionary_form(record.get(13).unwrap_or("*")) {
+        DictionaryForm::Surface(surface) => normalize_text_or_star(surface),
+        DictionaryForm::Itself => normalize_text_or_star(record.get(4).unwrap_or("")),
+        DictionaryForm::Unresolved => {
+            if !split_only {
+                stats.unresolved_base_refs += 1;
+            }
+            normalize_text_or_star(record.get(4).unwrap_or(""))
         }
+    };
+    let read = normalize_text_or_star(record.get(11).unwrap_or(""));
+
+    let (ctype, ctype_fallback) = infer_ctype(record.get(9).unwrap_or(""), &base, &read);
+    if ctype_fallback {
+        stats.fallback_ctype_rows += 1;
+        stats
+            .fallback_ctype_values
+            .entry(normalize_text_or_star(record.get(9).unwrap_or("")))
+            .or_default()
+            .record(surface);
+    }
+
+    let (cform, cform_fallback) = infer_cform(record.get(10).unwrap_or(""), &ctype, surface);
+    if cform_fallback {
+        stats.fallback_cform_rows += 1;
+        stats
+            .fallback_cform_values
+            .entry(normalize_text_or_star(record.get(10).unwrap_or("")))
+            .or_default()
+            .record(surface);
+    }
+
+    let pron = pronunciation_from_reading(&read, &normalized_pos[0]);
+
+    let accent = if split_only {
+        // Carried into split_info.csv without counting it as joined.
+        options
+            .accent
+            .lookup(surface, &read, &normalized_pos)
+            .filter(|_| mora_count(&pron).is_some())
+            .cloned()
+    } else {
+        join_accent(
+            surface,
+            &read,
+            &pron,
+            &normalized_pos,
+            options,
+            stats,
+            sidecars,
+        )
+    };
+    FeatureRow {
+        surface: surface.to_string(),
+        source_pos: original_pos,
+        source_ctype: normalize_text_or_star(record.get(9).unwrap_or("")),
+        source_cform: normalize_text_or_star(record.get(10).unwrap_or("")),
+        pos: normalized_pos,
+        ctype,
+        cform,
+        base,
+        read,
+        pron,
+        accent,
+    }
+}
 
-        let (cform, cform_fallback) = normalize_cform(record.get(10).unwrap_or(""));
-        if cform_fallback {
-            stats.fallback_cform_rows += 1;
+/// Counts a row as matched only when it gets an accent, i.e. its `pron` has a mora count.
+fn join_accent(
+    surface: &str,
+    read: &str,
+    pron: &str,
+    pos: &[String],
+    options: &ConvertOptions,
+    stats: &mut ConversionStats,
+    sidecars: &mut LexiconSidecars,
+) -> Option<AccentEntry> {
+    if options.accent.is_empty() {
+        return None;
+    }
+    match options.accent.lookup(surface, read, pos) {
+        Some(entry) if mora_count(pron).is_some() => {
+            stats.accent_matched_rows += 1;
+            Some(entry.clone())
+        }
+        _ => {
+            stats.accent_unmatched_rows += 1;
+            if options.collect_unmatched_accent {
+                let key = [surface.to_string(), read.to_string(), accent_pos(pos)];
+                *sidecars.unmatched_accent.entry(key).or_default() += 1;
+            }
+            None
         }
+    }
+}
 
-        let base = normalize_text_or_star(record.get(4).unwrap_or(""));
-        let read = normalize_text_or_star(record.get(11).unwrap_or(""));
-        let pron = read.clone();
-
-        let output_row = vec![
-            record.get(0).unwrap_or("").to_string(),
-            record.get(1).unwrap_or("").to_string(),
-            record.get(2).unwrap_or("").to_string(),
-            record.get(3).unwrap_or("").to_string(),
-            normalized_pos[0].clone(),
-            normalized_pos[1].clone(),
-            normalized_pos[2].clone(),
-            normalized_pos[3].clone(),
-            ctype,
-            cform,
-            base,
-            read,
-            pron,
-        ];
-
-        writer
-            .write_record(&output_row)
-            .with_context(|| format!("failed to write lex row at line {}", line_no + 1))?;
-        stats.written += 1;
+/// Checks the column count and parses `left_id`, `right_id` and `cost`.
+fn parse_connection_ids(record: &StringRecord, source: &str, line_no: usize) -> Result<(i32, i32)> {
+    if record.len() < 11 {
+        return Err(anyhow!(
+            "invalid lex row at {} line {}: expected >=11 columns, got {}",
+            source,
+            line_no,
+            record.len()
+        ));
     }
 
-    writer.flush()?;
-    Ok(())
+    let left = parse_i32(record, 1, "left_id", source, line_no)?;
+    let right = parse_i32(record, 2, "right_id", source, line_no)?;
+    parse_i32(record, 3, "cost", source, line_no)?;
+    Ok((left, right))
 }
 
-fn parse_i32(record: &StringRecord, index: usize, name: &str, line_no: usize) -> Result<i32> {
+pub(crate) fn parse_i32(
+    record: &StringRecord,
+    index: usize,
+    name: &str,
+    source: &str,
+    line_no: usize,
+) -> Result<i32> {
     let value = record
         .get(index)
-        .ok_or_else(|| anyhow!("missing {} at line {}", name, line_no))?
+        .ok_or_else(|| anyhow!("missing {} at {} line {}", name, source, line_no))?
         .trim();
-    value
-        .parse::<i32>()
-        .with_context(|| format!("failed to parse {}='{}' at line {}", name, value, line_no))
+    value.parse::<i32>().with_context(|| {
+        format!(
+            "failed to parse {}='{}' at {} line {}",
+            name, value, source, line_no
+        )
+    })
 }
@@ -3,14 +3,41 @@ use std::io::{Read, Write};
 use anyhow::{anyhow, Context, Result};
 use csv::{ReaderBuilder, StringRecord, WriterBuilder};
 
-use crate::normalize::{normalize_cform, normalize_ctype, normalize_pos};
+use crate::normalize::{normalize_cform, normalize_ctype, normalize_text_or_star};
+use crate::options::ConvertOptions;
+use crate::schema::FeatureRow;
+use crate::stats::ConversionStats;
 
-pub fn convert_unknown_dictionary<R: Read, W: Write>(input: R, output: W) -> Result<()> {
+/// Returns the number of rows written.
+pub fn convert_unknown_dictionary<R: Read, W: Write>(input: R, output: W) -> Result<usize> {
+    convert_unknown_dictionary_with_options(input, output, &ConvertOptions::default())
+}
+
+/// Malformed rows dropped under a lenient `on_error` policy are not reported;
+/// use [`convert_unknown_dictionary_with_stats`] for that.
+pub fn convert_unknown_dictionary_with_options<R: Read, W: Write>(
+    input: R,
+    output: W,
+    options: &ConvertOptions,
+) -> Result<usize> {
+    let mut stats = ConversionStats::default();
+    convert_unknown_dictionary_with_stats("unk.def", input, output, options, &mut stats)
+}
+
+/// Converts `source` (named in rejected rows), recording them in `stats`.
+pub fn convert_unknown_dictionary_with_stats<R: Read, W: Write>(
+    source: &str,
+    input: R,
+    output: W,
+    options: &ConvertOptions,
+    stats: &mut ConversionStats,
+) -> Result<usize> {
     let mut reader = ReaderBuilder::new()
         .has_headers(false)
         .flexible(true)
         .from_reader(input);
     let mut writer = WriterBuilder::new().has_headers(false).from_writer(output);
+    let mut written = 0;
 
     for (line_no, record) in reader.records().enumerate() {
         let record =
@@ -25,49 +52,186 @@ pub fn convert_unknown_dictionary<R: Read, W: Write>(input: R, output: W) -> Res
             continue;
         }
 
-        if record.len() < 10 {
-            return Err(anyhow!(
-                "invalid unk row at line {}: expected >=10 columns, got {}",
-                line_no + 1,
-                record.len()
-            ));
+        if let Err(error) = validate_row(&record, line_no + 1) {
+            stats.reject(options.on_error, source, line_no + 1, &record, error)?;
+            continue;
         }
 
-        parse_i32(&record, 1, "left_id", line_no + 1)?;
-        parse_i32(&record, 2, "right_id", line_no + 1)?;
-        parse_i32(&record, 3, "cost", line_no + 1)?;
-
-        let normalized_pos = normalize_pos(
-            record.get(4).unwrap_or(""),
-            record.get(5).unwrap_or(""),
-            record.get(6).unwrap_or(""),
-            record.get(7).unwrap_or(""),
-        );
+        let source_pos: [String; 4] =
+            std::array::from_fn(|i| normalize_text_or_star(record.get(4 + i).unwrap_or("")));
+        let (normalized_pos, _) = options.pos_mapping.map([
+            &source_pos[0],
+            &source_pos[1],
+            &source_pos[2],
+            &source_pos[3],
+        ]);
         let (ctype, _) = normalize_ctype(record.get(8).unwrap_or(""));
         let (cform, _) = normalize_cform(record.get(9).unwrap_or(""));
 
-        let output_row = vec![
+        let features = FeatureRow {
+            surface: "*".to_string(),
+            source_pos,
+            source_ctype: normalize_text_or_star(record.get(8).unwrap_or("")),
+            source_cform: normalize_text_or_star(record.get(9).unwrap_or("")),
+            pos: normalized_pos,
+            ctype,
+            cform,
+            base: "*".to_string(),
+            read: "*".to_string(),
+            pron: "*".to_string(),
+            accent: None,
+        };
+        let mut output_row = vec![
             record.get(0).unwrap_or("").to_string(),
             record.get(1).unwrap_or("").to_string(),
             record.get(2).unwrap_or("").to_string(),
             record.get(3).unwrap_or("").to_string(),
-            normalized_pos[0].clone(),
-            normalized_pos[1].clone(),
-            normalized_pos[2].clone(),
-            normalized_pos[3].clone(),
-            ctype,
-            cform,
-            "*".to_string(),
-            "*".to_string(),
-            "*".to_string(),
         ];
+        output_row.extend(options.feature_schema.features(&features));
 
         writer
             .write_record(&output_row)
             .with_context(|| format!("failed to write unk row at line {}", line_no + 1))?;
+        written += 1;
+    }
+
+    writer.flush()?;
+    Ok(written)
+}
+
+/// Word cost of the rows [`append_nooovbow_unknown_rows`] adds.
+pub const NOOOVBOW_COST: i16 = i16::MAX;
+
+/// Appends, for each `(NOOOVBOW_<BASE>, BASE)` category pair from
+/// [`crate::CharDefinition::emulate_nooovbow`], a copy of the `BASE` rows of the
+/// already converted `unk` with the category renamed and the cost set to
+/// [`NOOOVBOW_COST`].
+///
+/// Returns the number of rows appended.
+pub fn append_nooovbow_unknown_rows<W: Write>(
+    unk: &[u8],
+    categories: &[(String, String)],
+    output: W,
+) -> Result<usize> {
+    let records = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(unk)
+        .into_records()
+        .collect::<Result<Vec<StringRecord>, _>>()
+        .context("failed to read converted unk rows")?;
+    let mut writer = WriterBuilder::new().has_headers(false).from_writer(output);
+    let mut appended = 0;
+
+    for (compat, base) in categories {
+        let rows: Vec<&StringRecord> = records
+            .iter()
+            .filter(|record| record.get(0) == Some(base.as_str()))
+            .collect();
+        if rows.is_empty() {
+            return Err(anyhow!(
+                "no unk row for category {} to derive {} from",
+                base,
+                compat
+            ));
+        }
+        for record in rows {
+            let mut output_row: Vec<String> = record.iter().map(str::to_string).collect();
+            output_row[0] = compat.clone();
+            output_row[3] = NOOOVBOW_COST.to_string();
+            writer
+                .write_record(&output_row)
+                .with_context(|| format!("failed to write unk row for {}", compat))?;
+            appended += 1;
+        }
     }
 
     writer.flush()?;
+    Ok(appended)
+}
+
+/// Fills `*` `left_id`, `right_id` and `cost` fields of `append` rows from the
+/// first `base` row with the same POS, conjugation type and form, so a rules
+/// profile can add categories without pinning one release's connection ids.
+///
+/// Lines are kept one for one, so rejected rows keep their line numbers.
+pub fn resolve_unknown_append_ids(base: &[u8], append: &[u8]) -> Result<Vec<u8>> {
+    let base_rows = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .comment(Some(b'#'))
+        .from_reader(base)
+        .into_records()
+        .collect::<Result<Vec<StringRecord>, _>>()
+        .context("failed to read base unk rows")?;
+    let features = |record: &StringRecord| -> Vec<String> {
+        (4..10)
+            .map(|i| normalize_text_or_star(record.get(i).unwrap_or("")))
+            .collect()
+    };
+
+    let append = std::str::from_utf8(append).context("unk append rows are not UTF-8")?;
+    let mut output = Vec::with_capacity(append.len());
+    for (line_no, line) in append.lines().enumerate() {
+        let record = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(line.as_bytes())
+            .into_records()
+            .next()
+            .transpose()
+            .with_context(|| format!("failed to read unk row at line {}", line_no + 1))?;
+        let unresolved = record.as_ref().filter(|record| {
+            !line.trim_start().starts_with('#')
+                && (1..4).any(|i| record.get(i).map(str::trim) == Some("*"))
+        });
+        let Some(record) = unresolved else {
+            output.extend_from_slice(line.as_bytes());
+            output.push(b'\n');
+            continue;
+        };
+
+        let wanted = features(record);
+        let template = base_rows
+            .iter()
+            .find(|row| features(row) == wanted)
+            .ok_or_else(|| {
+                anyhow!(
+                    "no unk row with features {} to take the ids of {} at line {} from",
+                    wanted.join(","),
+                    record.get(0).unwrap_or(""),
+                    line_no + 1
+                )
+            })?;
+        let fields: Vec<&str> = record
+            .iter()
+            .enumerate()
+            .map(|(i, field)| match i {
+                1..=3 if field.trim() == "*" => template.get(i).unwrap_or(field),
+                _ => field,
+            })
+            .collect();
+        let mut writer = WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(&mut output);
+        writer.write_record(&fields)?;
+        writer.flush()?;
+    }
+    Ok(output)
+}
+
+fn validate_row(record: &StringRecord, line_no: usize) -> Result<()> {
+    if record.len() < 10 {
+        return Err(anyhow!(
+            "invalid unk row at line {}: expected >=10 columns, got {}",
+            line_no,
+            record.len()
+        ));
+    }
+
+    parse_i32(record, 1, "left_id", line_no)?;
+    parse_i32(record, 2, "right_id", line_no)?;
+    parse_i32(record, 3, "cost", line_no)?;
     Ok(())
 }
 
@@ -0,0 +1,243 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use csv::{StringRecord, WriterBuilder};
+
+use crate::convert_lex::{convert_row, parse_i32, LexiconSidecars};
+use crate::lexicon::{LexiconSource, WordIndex};
+use crate::normalize::normalize_text_or_star;
+use crate::options::ConvertOptions;
+use crate::stats::ConversionStats;
+use crate::sudachi_dic::Grammar;
+
+/// Dimensions of a connection matrix, as in the `matrix.def` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatrixSize {
+    /// Bounds the right id of the preceding word (first header value).
+    pub right_ids: usize,
+    /// Bounds the left id of the following word (second header value).
+    pub left_ids: usize,
+}
+
+impl MatrixSize {
+    pub fn from_grammar(grammar: &Grammar) -> Self {
+        Self {
+            right_ids: grammar.left_size,
+            left_ids: grammar.right_size,
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        Self::from_matrix_def(file).with_context(|| format!("invalid {}", path.display()))
+    }
+
+    /// Reads only the header line of a `matrix.def`.
+    pub fn from_matrix_def<R: Read>(input: R) -> Result<Self> {
+        let mut header = String::new();
+        BufReader::new(input).read_line(&mut header)?;
+        let sizes: Vec<&str> = header.split_whitespace().collect();
+        if sizes.len() != 2 {
+            return Err(anyhow!(
+                "invalid matrix.def header '{}': expected 2 values",
+                header.trim()
+            ));
+        }
+        let parse = |value: &str| {
+            value
+                .parse::<usize>()
+                .with_context(|| format!("failed to parse matrix size '{}'", value))
+        };
+        Ok(Self {
+            right_ids: parse(sizes[0])?,
+            left_ids: parse(sizes[1])?,
+        })
+    }
+
+    pub fn contains(&self, left_id: i32, right_id: i32) -> bool {
+        (left_id as usize) < self.left_ids && (right_id as usize) < self.right_ids
+    }
+}
+
+/// The system dictionary a user dictionary is resolved against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemLexicon {
+    pub matrix_size: MatrixSize,
+    word_index: WordIndex,
+    connection_ids: HashMap<[String; 6], (i32, i32)>,
+}
+
+impl SystemLexicon {
+    /// Indexes the system lexicon rows (in word-id order) and their POS → connection ids.
+    ///
+    /// A POS tuple (`col5..col10`) that appears with several id pairs resolves to
+    /// the most frequent pair, and to the smallest one on ties.
+    pub fn from_sources(sources: &[LexiconSource], matrix_size: MatrixSize) -> Result<Self> {
+        let mut pairs: HashMap<[String; 6], BTreeMap<(i32, i32), usize>> = HashMap::new();
+        for source in sources {
+            for record in source.records() {
+                let (line_no, record) = record?;
+                let left = parse_i32(&record, 1, "left_id", &source.name, line_no)?;
+                let right = parse_i32(&record, 2, "right_id", &source.name, line_no)?;
+                if left < 0 || right < 0 {
+                    continue;
+                }
+                *pairs
+                    .entry(pos_key(&record))
+                    .or_default()
+                    .entry((left, right))
+                    .or_default() += 1;
+            }
+        }
+
+        let connection_ids = pairs
+            .into_iter()
+            .map(|(pos, counts)| {
+                let (ids, _) = counts.into_iter().fold(((0, 0), 0), |best, (ids, count)| {
+                    if count > best.1 {
+                        (ids, count)
+                    } else {
+                        best
+                    }
+                });
+                (pos, ids)
+            })
+            .collect();
+
+        Ok(Self {
+            matrix_size,
+            word_index: WordIndex::build(sources)?,
+            connection_ids,
+        })
+    }
+
+    /// `(left_id, right_id)` assigned to a POS tuple (`pos1..4,ctype,cform`).
+    pub fn connection_ids(&self, pos: &[&str; 6]) -> Option<(i32, i32)> {
+        self.connection_ids
+            .get(&pos.map(normalize_text_or_star))
+            .copied()
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.word_index.len()
+    }
+}
+
+/// Converts Sudachi user dictionary CSVs into a Vibrato user lexicon.
+///
+/// `left_id`/`right_id` given as `*` (or empty) are taken from the system
+/// dictionary's assignment for the row's POS. Ids outside `system.matrix_size`
+/// are rejected. Dictionary-form references (`col13`) use system word ids, or
+/// `U<n>` for the n-th user row.
+pub fn convert_user_lexicon<W: Write>(
+    sources: &[LexiconSource],
+    system: &SystemLexicon,
+    output: W,
+    options: &ConvertOptions,
+    stats: &mut ConversionStats,
+) -> Result<()> {
+    stats.feature_schema = options.feature_schema.name().to_string();
+    let mut word_index = system.word_index.clone();
+    word_index.extend(sources)?;
+    let mut writer = WriterBuilder::new().has_headers(false).from_writer(output);
+
+    let mut word_id = system.word_count();
+    for source in sources {
+        for record in source.records() {
+            let (line_no, record) = record?;
+            let record = resolve_user_row(&source.name, line_no, &record, system, stats)?;
+            convert_row(
+                &source.name,
+                line_no,
+                word_id,
+                &record,
+                &word_index,
+                options,
+                &mut writer,
+                stats,
+                &mut LexiconSidecars::default(),
+            )?;
+            word_id += 1;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn resolve_user_row(
+    source: &str,
+    line_no: usize,
+    record: &StringRecord,
+    system: &SystemLexicon,
+    stats: &mut ConversionStats,
+) -> Result<StringRecord> {
+    if record.len() < 11 {
+        return Err(anyhow!(
+            "invalid user lex row at {} line {}: expected >=11 columns, got {}",
+            source,
+            line_no,
+            record.len()
+        ));
+    }
+
+    let mut fields: Vec<String> = record.iter().map(str::to_string).collect();
+    if is_unset(&fields[1]) || is_unset(&fields[2]) {
+        let pos = pos_key(record);
+        let (left, right) = system
+            .connection_ids(&[&pos[0], &pos[1], &pos[2], &pos[3], &pos[4], &pos[5]])
+            .ok_or_else(|| {
+                anyhow!(
+                    "no connection id for POS '{}' in the system dictionary at {} line {}",
+                    pos.join(","),
+                    source,
+                    line_no
+                )
+            })?;
+        if is_unset(&fields[1]) {
+            fields[1] = left.to_string();
+        }
+        if is_unset(&fields[2]) {
+            fields[2] = right.to_string();
+        }
+        stats.resolved_connection_id_rows += 1;
+    }
+
+    let record = StringRecord::from(fields.clone());
+    let left = parse_i32(&record, 1, "left_id", source, line_no)?;
+    let right = parse_i32(&record, 2, "right_id", source, line_no)?;
+    if left >= 0 && right >= 0 && !system.matrix_size.contains(left, right) {
+        return Err(anyhow!(
+            "connection ids {},{} exceed the system matrix size {}x{} at {} line {}",
+            left,
+            right,
+            system.matrix_size.right_ids,
+            system.matrix_size.left_ids,
+            source,
+            line_no
+        ));
+    }
+
+    if let Some(user_id) = fields
+        .get(13)
+        .and_then(|value| value.trim().strip_prefix('U'))
+    {
+        if let Ok(user_id) = user_id.parse::<usize>() {
+            fields[13] = (system.word_count() + user_id).to_string();
+        }
+    }
+    Ok(StringRecord::from(fields))
+}
+
+fn is_unset(value: &str) -> bool {
+    matches!(value.trim(), "" | "*")
+}
+
+fn pos_key(record: &StringRecord) -> [String; 6] {
+    [5, 6, 7, 8, 9, 10].map(|index| normalize_text_or_star(record.get(index).unwrap_or("")))
+}
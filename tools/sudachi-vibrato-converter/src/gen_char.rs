@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::{anyhow, Context, Result};
+use regex_syntax::hir::{Class, HirKind};
+
+use crate::char_def::{CharCategory, CharDefinition, CharRange, MAX_CODE_POINT};
+use crate::options::CharGenMode;
+
+/// A char.def category derived from a Unicode script or general category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnicodeCategory {
+    pub name: &'static str,
+    /// Character class in regex syntax, resolved with the tables embedded in `regex-syntax`.
+    pub class: &'static str,
+    /// Declaration used when char.def does not declare the category.
+    pub invoke: bool,
+    pub group: bool,
+    pub length: u16,
+}
+
+/// Categories `gen-char` derives, in application order; later ones win.
+pub const UNICODE_CATEGORIES: &[UnicodeCategory] = &[
+    UnicodeCategory {
+        name: "NUMERIC",
+        class: r"\p{Nd}",
+        invoke: true,
+        group: true,
+        length: 0,
+    },
+    UnicodeCategory {
+        name: "ALPHA",
+        class: r"[\p{Latin}&&\p{L}]",
+        invoke: true,
+        group: true,
+        length: 0,
+    },
+    UnicodeCategory {
+        name: "HIRAGANA",
+        class: r"\p{Hiragana}",
+        invoke: false,
+        group: true,
+        length: 2,
+    },
+    UnicodeCategory {
+        name: "KATAKANA",
+        class: r"\p{Katakana}",
+        invoke: true,
+        group: true,
+        length: 2,
+    },
+    UnicodeCategory {
+        name: "KANJI",
+        class: r"\p{Han}",
+        invoke: false,
+        group: false,
+        length: 2,
+    },
+    UnicodeCategory {
+        name: "EMOJI",
+        class: r"\p{Extended_Pictographic}",
+        invoke: true,
+        group: true,
+        length: 0,
+    },
+];
+
+/// Looks up a category of [`UNICODE_CATEGORIES`] by name.
+pub fn unicode_category(name: &str) -> Option<&'static UnicodeCategory> {
+    UNICODE_CATEGORIES
+        .iter()
+        .find(|category| category.name == name)
+}
+
+impl UnicodeCategory {
+    /// Code point ranges of [`Self::class`], sorted and non-overlapping.
+    pub fn ranges(&self) -> Result<Vec<(u32, u32)>> {
+        let hir = regex_syntax::parse(self.class)
+            .with_context(|| format!("failed to parse class '{}' of {}", self.class, self.name))?;
+        match hir.kind() {
+            HirKind::Class(Class::Unicode(class)) => Ok(class
+                .ranges()
+                .iter()
+                .map(|range| (u32::from(range.start()), u32::from(range.end())))
+                .collect()),
+            _ => Err(anyhow!(
+                "class '{}' of {} is not a character class",
+                self.class,
+                self.name
+            )),
+        }
+    }
+
+    pub fn declaration(&self) -> CharCategory {
+        CharCategory {
+            name: self.name.to_string(),
+            invoke: self.invoke,
+            group: self.group,
+            length: self.length,
+        }
+    }
+}
+
+/// Code points `start..=end` whose categories `gen-char` changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CategoryChange {
+    pub start: u32,
+    pub end: u32,
+    /// Categories before, `DEFAULT` when unmapped.
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+}
+
+impl CategoryChange {
+    pub fn count(&self) -> u32 {
+        self.end - self.start + 1
+    }
+
+    /// Beyond U+FFFF, where Vibrato 0.5 gives every code point U+0000's categories.
+    pub fn beyond_bmp(&self) -> bool {
+        self.start > MAX_CODE_POINT
+    }
+
+    pub fn code_points(&self) -> String {
+        CharRange {
+            start: self.start,
+            end: self.end,
+            categories: Vec::new(),
+        }
+        .code_points()
+    }
+}
+
+/// What [`generate_char_definition`] derived.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CharCoverage {
+    /// char.def lines to apply on top of the input, e.g. with `--char-append`.
+    /// Holds the changes up to U+FFFF and declarations for new categories.
+    pub overlay: CharDefinition,
+    /// Changed code points in code point order, including those beyond U+FFFF.
+    pub changes: Vec<CategoryChange>,
+    /// Derived categories the input does not declare; they need unk.def rows.
+    pub new_categories: Vec<String>,
+}
+
+impl CharCoverage {
+    /// One summary line per category transition, then one per new category.
+    pub fn lines(&self) -> Vec<String> {
+        let mut transitions: BTreeMap<(bool, String, String), (u32, usize)> = BTreeMap::new();
+        for change in &self.changes {
+            let entry = transitions
+                .entry((
+                    change.beyond_bmp(),
+                    change.from.join(" "),
+                    change.to.join(" "),
+                ))
+                .or_default();
+            entry.0 += change.count();
+            entry.1 += 1;
+        }
+        let mut lines: Vec<String> = transitions
+            .into_iter()
+            .map(|((beyond_bmp, from, to), (code_points, ranges))| {
+                format!(
+                    "{}{} -> {}: {} code points in {} ranges",
+                    if beyond_bmp {
+                        "beyond U+FFFF, not written: "
+                    } else {
+                        ""
+                    },
+                    from,
+                    to,
+                    code_points,
+                    ranges
+                )
+            })
+            .collect();
+        for category in &self.new_categories {
+            lines.push(format!("new category {} needs an unk.def row", category));
+        }
+        lines
+    }
+
+    /// Writes the changes as CSV: `code_points,count,from,to,written`.
+    pub fn write_csv<W: Write>(&self, output: W) -> Result<()> {
+        let mut writer = csv::Writer::from_writer(output);
+        writer.write_record(["code_points", "count", "from", "to", "written"])?;
+        for change in &self.changes {
+            writer.write_record([
+                change.code_points(),
+                change.count().to_string(),
+                change.from.join(" "),
+                change.to.join(" "),
+                (!change.beyond_bmp()).to_string(),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Derives `categories` from the Unicode tables and merges them into `base`.
+///
+/// A derived category becomes the base category of a code point and the
+/// existing ones but `DEFAULT` follow it; under [`CharGenMode::Fill`] only
+/// code points `base` leaves to `DEFAULT` are touched. Changes beyond U+FFFF
+/// are reported but kept out of the overlay, as Vibrato 0.5 rejects them.
+pub fn generate_char_definition(
+    base: &CharDefinition,
+    categories: &[&UnicodeCategory],
+    mode: CharGenMode,
+) -> Result<CharCoverage> {
+    let mut derived = CharDefinition::default();
+    for category in categories {
+        for (start, end) in category.ranges()? {
+            derived.set_range(CharRange {
+                start,
+                end,
+                categories: vec![category.name.to_string()],
+            });
+        }
+    }
+
+    let mut coverage = CharCoverage::default();
+    for range in derived.ranges() {
+        let name = &range.categories[0];
+        let mut pieces = Vec::new();
+        if range.start <= MAX_CODE_POINT {
+            pieces.extend(base.split(range.start, range.end.min(MAX_CODE_POINT)));
+        }
+        if range.end > MAX_CODE_POINT {
+            pieces.extend(base.split(range.start.max(MAX_CODE_POINT + 1), range.end));
+        }
+
+        for piece in pieces {
+            let unmapped = piece.categories == ["DEFAULT"];
+            if mode == CharGenMode::Fill && !unmapped {
+                continue;
+            }
+            let mut to = vec![name.clone()];
+            to.extend(
+                piece
+                    .categories
+                    .iter()
+                    .filter(|category| *category != name && *category != "DEFAULT")
+                    .cloned(),
+            );
+            if to == piece.categories {
+                continue;
+            }
+            let change = CategoryChange {
+                start: piece.start,
+                end: piece.end,
+                from: piece.categories,
+                to,
+            };
+            if !change.beyond_bmp() {
+                coverage.overlay.set_range(CharRange {
+                    start: change.start,
+                    end: change.end,
+                    categories: change.to.clone(),
+                });
+            }
+            match coverage.changes.last_mut() {
+                Some(last)
+                    if last.end + 1 == change.start
+                        && last.from == change.from
+                        && last.to == change.to
+                        && last.beyond_bmp() == change.beyond_bmp() =>
+                {
+                    last.end = change.end;
+                }
+                _ => coverage.changes.push(change),
+            }
+        }
+    }
+
+    for category in categories {
+        let used = coverage
+            .overlay
+            .ranges()
+            .iter()
+            .any(|range| range.categories[0] == category.name);
+        if used && base.category(category.name).is_none() {
+            coverage.overlay.set_category(category.declaration());
+            coverage.new_categories.push(category.name.to_string());
+        }
+    }
+    Ok(coverage)
+}
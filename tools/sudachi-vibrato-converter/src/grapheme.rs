@@ -0,0 +1,155 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use once_cell::sync::Lazy;
+use regex_syntax::hir::{Class, HirKind};
+use unicode_segmentation::UnicodeSegmentation;
+use vibrato::dictionary::LexType;
+use vibrato::token::Token;
+use vibrato::tokenizer::worker::Worker;
+
+/// Code points that make a grapheme cluster an emoji; flags are made of
+/// regional indicators only.
+static EMOJI_RANGES: Lazy<Vec<(char, char)>> = Lazy::new(|| {
+    let hir = regex_syntax::parse(r"[\p{Extended_Pictographic}\p{Regional_Indicator}]")
+        .expect("emoji class parses");
+    match hir.kind() {
+        HirKind::Class(Class::Unicode(class)) => class
+            .ranges()
+            .iter()
+            .map(|range| (range.start(), range.end()))
+            .collect(),
+        _ => unreachable!("emoji class is a character class"),
+    }
+});
+
+fn is_emoji_cluster(cluster: &str) -> bool {
+    cluster.chars().any(|c| {
+        EMOJI_RANGES
+            .binary_search_by(|&(start, end)| {
+                if end < c {
+                    Ordering::Less
+                } else if start > c {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            })
+            .is_ok()
+    })
+}
+
+/// Tokens merged, or cut, so that no token boundary falls inside an extended
+/// grapheme cluster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphemeToken {
+    pub surface: String,
+    pub range_char: Range<usize>,
+    pub range_byte: Range<usize>,
+    /// Feature of the first merged token.
+    pub feature: String,
+    /// Indices of the tokens it covers, in whole or in part.
+    pub tokens: Range<usize>,
+}
+
+/// Groups tokens, given by their byte ranges in `sentence`, so that every group
+/// ends at an extended grapheme cluster boundary.
+///
+/// Returns the token index range of each group. Only adjacent tokens are
+/// grouped, so a gap such as an ignored space always ends a group.
+pub fn grapheme_groups(sentence: &str, ranges: &[Range<usize>]) -> Vec<Range<usize>> {
+    let boundaries = cluster_boundaries(sentence);
+    let mut groups = Vec::new();
+    let mut start = 0;
+    for (i, range) in ranges.iter().enumerate() {
+        let adjacent = ranges
+            .get(i + 1)
+            .is_some_and(|next| next.start == range.end);
+        if !adjacent || boundaries.binary_search(&range.end).is_ok() {
+            groups.push(start..i + 1);
+            start = i + 1;
+        }
+    }
+    groups
+}
+
+/// Byte offsets of the extended grapheme cluster boundaries, both ends included.
+fn cluster_boundaries(sentence: &str) -> Vec<usize> {
+    sentence
+        .grapheme_indices(true)
+        .map(|(offset, _)| offset)
+        .chain([sentence.len()])
+        .collect()
+}
+
+/// Cuts `range` at the cluster boundaries inside it that have an emoji cluster
+/// on either side.
+fn cut_emoji_clusters(
+    sentence: &str,
+    boundaries: &[usize],
+    range: Range<usize>,
+) -> Vec<Range<usize>> {
+    let mut pieces = Vec::new();
+    let mut start = range.start;
+    for (i, &boundary) in boundaries.iter().enumerate() {
+        if boundary <= range.start || boundary >= range.end {
+            continue;
+        }
+        let before = &sentence[boundaries[i - 1]..boundary];
+        let after = &sentence[boundary..boundaries[i + 1]];
+        if is_emoji_cluster(before) || is_emoji_cluster(after) {
+            pieces.push(start..boundary);
+            start = boundary;
+        }
+    }
+    pieces.push(start..range.end);
+    pieces
+}
+
+/// Char offset of `byte` within `token`.
+fn char_offset(sentence: &str, token: &Token<'_, '_>, byte: usize) -> usize {
+    token.range_char().start + sentence[token.range_byte().start..byte].chars().count()
+}
+
+/// Merges the tokens of the last sentence `worker` tokenized, which must be
+/// `sentence`, into whole extended grapheme clusters.
+///
+/// Meant for the `emoji-grapheme` rules profile, which yields ZWJ sequences,
+/// flags, skin tones and keycaps one code point per token. Emoji beyond
+/// U+FFFF, which Vibrato 0.5 cannot give a category, are first cut out of the
+/// unknown words they were grouped into, so each ends up in a token of its own.
+pub fn merge_grapheme_clusters(sentence: &str, worker: &Worker<'_>) -> Vec<GraphemeToken> {
+    let boundaries = cluster_boundaries(sentence);
+    let mut pieces: Vec<(usize, Range<usize>)> = Vec::new();
+    for i in 0..worker.num_tokens() {
+        let token = worker.token(i);
+        if token.lex_type() == LexType::Unknown {
+            pieces.extend(
+                cut_emoji_clusters(sentence, &boundaries, token.range_byte())
+                    .into_iter()
+                    .map(|piece| (i, piece)),
+            );
+        } else {
+            pieces.push((i, token.range_byte()));
+        }
+    }
+    let ranges: Vec<Range<usize>> = pieces.iter().map(|(_, range)| range.clone()).collect();
+    grapheme_groups(sentence, &ranges)
+        .into_iter()
+        .map(|group| {
+            let (first_index, first_range) = &pieces[group.start];
+            let (last_index, last_range) = &pieces[group.end - 1];
+            let first = worker.token(*first_index);
+            let last = worker.token(*last_index);
+            let range_byte = first_range.start..last_range.end;
+            GraphemeToken {
+                surface: sentence[range_byte.clone()].to_string(),
+                range_char: char_offset(sentence, &first, range_byte.start)
+                    ..char_offset(sentence, &last, range_byte.end),
+                range_byte,
+                feature: first.feature().to_string(),
+                tokens: *first_index..*last_index + 1,
+            }
+        })
+        .collect()
+}
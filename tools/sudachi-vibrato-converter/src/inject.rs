@@ -2,35 +2,126 @@ use std::fs::File;
 use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use csv::{ReaderBuilder, WriterBuilder};
 
-use crate::convert_unk::convert_unknown_dictionary;
+use crate::convert_unk::convert_unknown_dictionary_with_options;
+use crate::normalize::{normalize_text_or_star, pronunciation_from_reading};
+use crate::options::ConvertOptions;
+use crate::schema::FeatureRow;
 
-pub fn append_text_files_as_lines<W: Write>(output: &mut W, files: &[PathBuf]) -> Result<()> {
+/// Returns the number of lines appended.
+pub fn append_text_files_as_lines<W: Write>(output: &mut W, files: &[PathBuf]) -> Result<usize> {
+    let mut appended = 0;
     for path in files {
         let file = File::open(path)?;
-        append_text_as_lines(BufReader::new(file), output)?;
+        appended += append_text_as_lines(BufReader::new(file), output)?;
     }
-    Ok(())
+    Ok(appended)
 }
 
-fn append_text_as_lines<R: BufRead, W: Write>(reader: R, output: &mut W) -> Result<()> {
+fn append_text_as_lines<R: BufRead, W: Write>(reader: R, output: &mut W) -> Result<usize> {
+    let mut appended = 0;
     for line in reader.lines() {
         let mut line = line?;
         if line.ends_with('\r') {
             line.pop();
         }
         writeln!(output, "{line}")?;
+        appended += 1;
     }
-    Ok(())
+    Ok(appended)
+}
+
+pub fn append_lexicon_rows<W: Write>(output: &mut W, files: &[PathBuf]) -> Result<usize> {
+    append_lexicon_rows_with_options(output, files, &ConvertOptions::default())
+}
+
+/// Appends pre-normalized MeCab-9 lexicon rows, laid out in the selected feature schema.
+///
+/// `pron` is derived from `read` when it is missing, `*` or a copy of `read`.
+/// Columns after `pron` are dropped; accent entries are joined but not counted.
+/// Returns the number of rows appended.
+pub fn append_lexicon_rows_with_options<W: Write>(
+    output: &mut W,
+    files: &[PathBuf],
+    options: &ConvertOptions,
+) -> Result<usize> {
+    let mut writer = WriterBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_writer(&mut *output);
+    let mut appended = 0;
+
+    for path in files {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+
+        for (line_no, record) in reader.records().enumerate() {
+            let record = record.with_context(|| {
+                format!("failed to read {} at line {}", path.display(), line_no + 1)
+            })?;
+            if record.len() < 12 {
+                return Err(anyhow!(
+                    "invalid lex append row at {} line {}: expected >=12 columns, got {}",
+                    path.display(),
+                    line_no + 1,
+                    record.len()
+                ));
+            }
+
+            let field = |index: usize| normalize_text_or_star(record.get(index).unwrap_or(""));
+            let pos: [String; 4] = std::array::from_fn(|i| field(4 + i));
+            let read = field(11);
+            let pron = match record.get(12).map(str::trim) {
+                Some(pron) if !pron.is_empty() && pron != "*" && pron != read => pron.to_string(),
+                _ => pronunciation_from_reading(&read, &pos[0]),
+            };
+
+            let surface = record.get(0).unwrap_or("");
+            let accent = options.accent.lookup(surface, &read, &pos).cloned();
+            let features = FeatureRow {
+                surface: surface.to_string(),
+                source_pos: pos.clone(),
+                source_ctype: field(8),
+                source_cform: field(9),
+                pos,
+                ctype: field(8),
+                cform: field(9),
+                base: field(10),
+                read,
+                pron,
+                accent,
+            };
+            let mut row: Vec<String> = record.iter().take(4).map(str::to_string).collect();
+            row.extend(options.feature_schema.features(&features));
+            writer.write_record(&row)?;
+            appended += 1;
+        }
+    }
+
+    writer.flush()?;
+    Ok(appended)
+}
+
+pub fn append_unknown_definitions<W: Write>(output: &mut W, files: &[PathBuf]) -> Result<usize> {
+    append_unknown_definitions_with_options(output, files, &ConvertOptions::default())
 }
 
-pub fn append_unknown_definitions<W: Write>(output: &mut W, files: &[PathBuf]) -> Result<()> {
+pub fn append_unknown_definitions_with_options<W: Write>(
+    output: &mut W,
+    files: &[PathBuf],
+    options: &ConvertOptions,
+) -> Result<usize> {
+    let mut appended = 0;
     for path in files {
         let input = BufReader::new(File::open(path)?);
-        convert_unknown_dictionary(input, &mut *output)?;
+        appended += convert_unknown_dictionary_with_options(input, &mut *output, options)?;
     }
-    Ok(())
+    Ok(appended)
 }
 
 pub fn write_rewrite_definition(
@@ -0,0 +1,119 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use csv::{ReaderBuilder, StringRecord};
+
+/// A raw Sudachi lexicon CSV held in memory so that it can be scanned more than once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexiconSource {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+impl LexiconSource {
+    pub fn new<S: Into<String>>(name: S, data: Vec<u8>) -> Self {
+        Self {
+            name: name.into(),
+            data,
+        }
+    }
+
+    /// Reads `path`, naming the source after its file name.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        Ok(Self::new(name, data))
+    }
+
+    /// Iterates non-empty records as `(1-based line, record)`.
+    pub fn records(&self) -> impl Iterator<Item = Result<(usize, StringRecord)>> + '_ {
+        let reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(self.data.as_slice());
+
+        reader
+            .into_records()
+            .enumerate()
+            .map(move |(line_no, record)| {
+                let record = record.with_context(|| {
+                    format!(
+                        "failed to read lex row at {} line {}",
+                        self.name,
+                        line_no + 1
+                    )
+                })?;
+                Ok((line_no + 1, record))
+            })
+            .filter(|record| !matches!(record, Ok((_, record)) if record.is_empty()))
+    }
+}
+
+/// Surfaces of every lexicon row, indexed by Sudachi word id.
+///
+/// Sudachi numbers rows in input order across all lexicon files, so the sources
+/// must be given in the same order as they were concatenated upstream
+/// (small, core, notcore).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WordIndex {
+    surfaces: Vec<String>,
+}
+
+impl WordIndex {
+    pub fn build(sources: &[LexiconSource]) -> Result<Self> {
+        let mut index = Self::default();
+        index.extend(sources)?;
+        Ok(index)
+    }
+
+    /// Appends the rows of `sources` after the rows already indexed.
+    pub fn extend(&mut self, sources: &[LexiconSource]) -> Result<()> {
+        for source in sources {
+            for record in source.records() {
+                let (_, record) = record?;
+                self.surfaces.push(record.get(0).unwrap_or("").to_string());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.surfaces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.surfaces.is_empty()
+    }
+
+    pub fn surface(&self, word_id: usize) -> Option<&str> {
+        self.surfaces.get(word_id).map(String::as_str)
+    }
+
+    /// Resolves a dictionary-form word id column (Sudachi `col13`).
+    pub fn resolve_dictionary_form(&self, value: &str) -> DictionaryForm<'_> {
+        let value = value.trim();
+        if value.is_empty() || value == "*" {
+            return DictionaryForm::Itself;
+        }
+        value
+            .parse::<usize>()
+            .ok()
+            .and_then(|word_id| self.surface(word_id))
+            .map_or(DictionaryForm::Unresolved, DictionaryForm::Surface)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DictionaryForm<'a> {
+    /// The row is its own dictionary form (`*`).
+    Itself,
+    /// Surface of the referenced row.
+    Surface(&'a str),
+    /// The reference is not a known word id.
+    Unresolved,
+}
@@ -1,15 +1,76 @@
+pub mod accent;
+pub mod bundle;
+pub mod char_def;
 pub mod cli;
+pub mod compile;
 pub mod convert_char;
 pub mod convert_lex;
 pub mod convert_unk;
+pub mod convert_user;
+pub mod gen_char;
+pub mod grapheme;
 pub mod inject;
+pub mod lexicon;
+pub mod lint;
+pub mod manifest;
 pub mod normalize;
+pub mod normalized_form;
+pub mod options;
+pub mod pipeline;
+pub mod schema;
+mod sidecar;
+pub mod split_info;
 pub mod stats;
+pub mod sudachi_dic;
+pub mod synonyms;
+pub mod word_ids;
+pub mod word_units;
 
+pub use accent::{mora_count, AccentDictionary, AccentEntry};
+pub use bundle::{Bundle, BundleMetadata};
+pub use char_def::{
+    parse_char_lines, CharCategory, CharDefLine, CharDefinition, CharMergeCounts, CharRange,
+};
+pub use compile::{
+    compile_dictionary, read_compressed_dictionary, smoke_tokenize, validate_entries,
+    write_compressed_dictionary,
+};
 pub use convert_char::convert_char_definition;
-pub use convert_lex::convert_lexicon;
-pub use convert_unk::convert_unknown_dictionary;
+pub use convert_lex::{
+    convert_lexicon, convert_lexicon_sources, convert_lexicon_with_options, LexiconSidecars,
+};
+pub use convert_unk::{
+    append_nooovbow_unknown_rows, convert_unknown_dictionary,
+    convert_unknown_dictionary_with_options, convert_unknown_dictionary_with_stats,
+    resolve_unknown_append_ids,
+};
+pub use convert_user::{convert_user_lexicon, MatrixSize, SystemLexicon};
+pub use gen_char::{
+    generate_char_definition, unicode_category, CategoryChange, CharCoverage, UnicodeCategory,
+    UNICODE_CATEGORIES,
+};
+pub use grapheme::{grapheme_groups, merge_grapheme_clusters, GraphemeToken};
 pub use inject::{
-    append_text_files_as_lines, append_unknown_definitions, write_rewrite_definition,
+    append_lexicon_rows, append_lexicon_rows_with_options, append_text_files_as_lines,
+    append_unknown_definitions, append_unknown_definitions_with_options, write_rewrite_definition,
+};
+pub use lexicon::{DictionaryForm, LexiconSource, WordIndex};
+pub use lint::{lint, lint_paths, LintLocation, LintReport, RangeOverlap};
+pub use manifest::{BuildInputs, BuildManifest, BuildSettings, SourceInfo};
+pub use normalize::{infer_cform, infer_ctype, pronunciation_from_reading, PosMapping, PosRule};
+pub use normalized_form::NormalizedForms;
+pub use options::{CharGenMode, ConvertOptions, ErrorPolicy, NoOovBowPolicy};
+pub use schema::{
+    feature_schema_by_name, FeatureRow, FeatureSchema, Jpreprocess12, Mecab9, Unidic,
+};
+pub use split_info::{SplitInfo, SplitOnlyEntry};
+pub use stats::{ConversionStats, FallbackEntry, FallbackHit, PosMappingHit, RejectedRow};
+pub use sudachi_dic::{DictionaryHeader, Grammar, SudachiDictionary, SudachiWord};
+pub use synonyms::{
+    ExpansionControl, LexemeForm, Synonym, SynonymDictionary, SynonymExpander, SynonymGroups,
+    TaigenYougen,
+};
+pub use word_ids::{WordIds, WordOrigin};
+pub use word_units::{
+    Resplitter, SplitMode, SplitToken, SplitWord, UnitTarget, WordUnit, WordUnits,
 };
-pub use stats::ConversionStats;
@@ -0,0 +1,289 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use csv::ReaderBuilder;
+
+use crate::char_def::{
+    parse_char_lines, CharDefLine, CharDefinition, CharRange, MAX_CATEGORIES, MAX_CATEGORY_LENGTH,
+    MAX_CODE_POINT, NOOOVBOW,
+};
+
+/// A definition line, located by file name and 1-based line number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintLocation {
+    pub source: String,
+    pub line: usize,
+}
+
+impl fmt::Display for LintLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} line {}", self.source, self.line)
+    }
+}
+
+/// A range line that re-maps code points an earlier one mapped to other categories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeOverlap {
+    pub earlier: (LintLocation, CharRange),
+    pub later: (LintLocation, CharRange),
+    /// Code points both lines cover; they resolve to the later line's categories.
+    pub start: u32,
+    pub end: u32,
+}
+
+/// Problems found in a char.def/unk.def pair.
+///
+/// Everything but overlaps and unused categories makes Vibrato reject, panic on
+/// or mis-read the pair, or leaves characters without unknown-word candidates.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintReport {
+    /// No char.def line declares `DEFAULT`.
+    pub missing_default: bool,
+    /// Range lines naming a category no char.def line declares.
+    pub undeclared_categories: Vec<(LintLocation, String)>,
+    /// Range lines beyond U+FFFF.
+    pub out_of_range: Vec<(LintLocation, CharRange)>,
+    /// Category lines with a `length` above 15, which Vibrato panics on.
+    pub invalid_lengths: Vec<(LintLocation, String, u16)>,
+    /// Category lines declaring a category past the 18th, which Vibrato
+    /// cannot tell apart from the bits after its category set.
+    pub excess_categories: Vec<(LintLocation, String)>,
+    /// unk.def rows for categories char.def does not declare.
+    pub unknown_unk_categories: Vec<(LintLocation, String)>,
+    /// Declared categories with no unk.def row.
+    pub categories_without_unk: Vec<String>,
+    /// Declared categories no range maps a code point to, `DEFAULT` aside.
+    pub unused_categories: Vec<String>,
+    pub overlapping_ranges: Vec<RangeOverlap>,
+}
+
+impl LintReport {
+    /// Number of findings that are errors.
+    pub fn errors(&self) -> usize {
+        usize::from(self.missing_default)
+            + self.undeclared_categories.len()
+            + self.out_of_range.len()
+            + self.invalid_lengths.len()
+            + self.excess_categories.len()
+            + self.unknown_unk_categories.len()
+            + self.categories_without_unk.len()
+    }
+
+    /// One line per finding, errors first.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.missing_default {
+            lines.push("error: category DEFAULT is not declared".to_string());
+        }
+        for (location, category) in &self.undeclared_categories {
+            lines.push(format!(
+                "error: {}: undeclared category {}",
+                location, category
+            ));
+        }
+        for (location, range) in &self.out_of_range {
+            lines.push(format!(
+                "error: {}: range {} is beyond U+FFFF",
+                location,
+                range.code_points()
+            ));
+        }
+        for (location, category, length) in &self.invalid_lengths {
+            lines.push(format!(
+                "error: {}: category {} has length {}, above {}",
+                location, category, length, MAX_CATEGORY_LENGTH
+            ));
+        }
+        for (location, category) in &self.excess_categories {
+            lines.push(format!(
+                "error: {}: category {} is beyond the {} categories Vibrato supports",
+                location, category, MAX_CATEGORIES
+            ));
+        }
+        for (location, category) in &self.unknown_unk_categories {
+            lines.push(format!(
+                "error: {}: unk.def row for undeclared category {}",
+                location, category
+            ));
+        }
+        for category in &self.categories_without_unk {
+            lines.push(format!("error: category {} has no unk.def row", category));
+        }
+        for category in &self.unused_categories {
+            lines.push(format!(
+                "warning: category {} is declared but no range uses it",
+                category
+            ));
+        }
+        for overlap in &self.overlapping_ranges {
+            let (earlier_at, earlier) = &overlap.earlier;
+            let (later_at, later) = &overlap.later;
+            let span = CharRange {
+                start: overlap.start,
+                end: overlap.end,
+                categories: Vec::new(),
+            };
+            lines.push(format!(
+                "overlap: {}: {} re-maps {} from {} ({}) to {}",
+                later_at,
+                later.code_points(),
+                span.code_points(),
+                earlier.categories.join(" "),
+                earlier_at,
+                later.categories.join(" ")
+            ));
+        }
+        lines
+    }
+}
+
+/// Lints char.def files and unk.def files, each applied in order like
+/// `--char-append`/`--unk-append`.
+pub fn lint_paths(char_files: &[PathBuf], unk_files: &[PathBuf]) -> Result<LintReport> {
+    let mut chars = Vec::with_capacity(char_files.len());
+    for path in char_files {
+        chars.push((file_name(path), open(path)?));
+    }
+    let mut unks = Vec::with_capacity(unk_files.len());
+    for path in unk_files {
+        unks.push((file_name(path), open(path)?));
+    }
+    lint(chars, unks)
+}
+
+/// Lints named char.def and unk.def inputs.
+pub fn lint<C: Read, U: Read>(
+    char_inputs: Vec<(String, C)>,
+    unk_inputs: Vec<(String, U)>,
+) -> Result<LintReport> {
+    let mut report = LintReport::default();
+    let mut definition = CharDefinition::default();
+    let mut ranges: Vec<(LintLocation, CharRange)> = Vec::new();
+
+    for (source, input) in char_inputs {
+        for (line, entry) in parse_char_lines(&source, input)? {
+            let location = LintLocation {
+                source: source.clone(),
+                line,
+            };
+            match entry {
+                CharDefLine::Category(category) => {
+                    if category.length > MAX_CATEGORY_LENGTH {
+                        report.invalid_lengths.push((
+                            location.clone(),
+                            category.name.clone(),
+                            category.length,
+                        ));
+                    }
+                    if definition.category(&category.name).is_none()
+                        && definition.categories().len() >= MAX_CATEGORIES
+                    {
+                        report
+                            .excess_categories
+                            .push((location, category.name.clone()));
+                    }
+                    definition.set_category(category);
+                }
+                CharDefLine::Range(mut range) => {
+                    // Stripped by the conversion, so raw Sudachi files lint like converted ones.
+                    range.categories.retain(|category| category != NOOOVBOW);
+                    if range.categories.is_empty() {
+                        continue;
+                    }
+                    if range.end > MAX_CODE_POINT {
+                        report.out_of_range.push((location.clone(), range.clone()));
+                    }
+                    for (earlier_at, earlier) in &ranges {
+                        let start = earlier.start.max(range.start);
+                        let end = earlier.end.min(range.end);
+                        if start <= end && earlier.categories != range.categories {
+                            report.overlapping_ranges.push(RangeOverlap {
+                                earlier: (earlier_at.clone(), earlier.clone()),
+                                later: (location.clone(), range.clone()),
+                                start,
+                                end,
+                            });
+                        }
+                    }
+                    definition.set_range(range.clone());
+                    ranges.push((location, range));
+                }
+            }
+        }
+    }
+
+    let declared: BTreeSet<&str> = definition
+        .categories()
+        .iter()
+        .map(|category| category.name.as_str())
+        .collect();
+    report.missing_default = !declared.contains("DEFAULT");
+    for (location, range) in &ranges {
+        for category in &range.categories {
+            if !declared.contains(category.as_str()) {
+                report
+                    .undeclared_categories
+                    .push((location.clone(), category.clone()));
+            }
+        }
+    }
+
+    let mut with_unk = BTreeSet::new();
+    for (source, input) in unk_inputs {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .comment(Some(b'#'))
+            .from_reader(input);
+        for record in reader.records() {
+            let record = record.with_context(|| format!("failed to read {}", source))?;
+            let category = record.get(0).unwrap_or("").trim();
+            if category.is_empty() {
+                continue;
+            }
+            if declared.contains(category) {
+                with_unk.insert(category.to_string());
+            } else {
+                let line = record.position().map_or(0, |position| position.line());
+                report.unknown_unk_categories.push((
+                    LintLocation {
+                        source: source.clone(),
+                        line: line as usize,
+                    },
+                    category.to_string(),
+                ));
+            }
+        }
+    }
+
+    let used: BTreeSet<&str> = definition
+        .ranges()
+        .iter()
+        .flat_map(|range| range.categories.iter().map(String::as_str))
+        .collect();
+    for category in definition.categories() {
+        let name = category.name.as_str();
+        if !with_unk.contains(name) {
+            report.categories_without_unk.push(name.to_string());
+        }
+        if name != "DEFAULT" && !used.contains(name) {
+            report.unused_categories.push(name.to_string());
+        }
+    }
+    Ok(report)
+}
+
+fn open(path: &Path) -> Result<File> {
+    File::open(path).with_context(|| format!("failed to open {}", path.display()))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
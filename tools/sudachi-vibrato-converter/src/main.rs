@@ -1,13 +1,11 @@
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::fs;
 
 use anyhow::Result;
 use clap::Parser;
 
 use sudachi_vibrato_converter::cli::{Cli, Commands};
-use sudachi_vibrato_converter::{
-    append_text_files_as_lines, append_unknown_definitions, convert_char_definition,
-    convert_lexicon, convert_unknown_dictionary, write_rewrite_definition, ConversionStats,
+use sudachi_vibrato_converter::pipeline::{
+    run_build, run_compile, run_convert, run_convert_user, run_gen_char, run_lint, run_package,
 };
 
 fn main() -> Result<()> {
@@ -15,30 +13,34 @@ fn main() -> Result<()> {
 
     match cli.command {
         Commands::Convert(args) => {
-            let mut stats = ConversionStats::default();
-
-            let lex_in = BufReader::new(File::open(&args.lex_in)?);
-            let mut lex_out = BufWriter::new(File::create(&args.lex_out)?);
-            convert_lexicon(lex_in, &mut lex_out, &mut stats)?;
-            append_text_files_as_lines(&mut lex_out, &args.lex_append)?;
-
-            let unk_in = BufReader::new(File::open(&args.unk_in)?);
-            let mut unk_out = BufWriter::new(File::create(&args.unk_out)?);
-            convert_unknown_dictionary(unk_in, &mut unk_out)?;
-            append_unknown_definitions(&mut unk_out, &args.unk_append)?;
-
-            let char_in = BufReader::new(File::open(&args.char_in)?);
-            let mut char_out = BufWriter::new(File::create(&args.char_out)?);
-            convert_char_definition(char_in, &mut char_out)?;
-            append_text_files_as_lines(&mut char_out, &args.char_append)?;
-
-            if let (Some(rewrite_in), Some(rewrite_out)) =
-                (args.rewrite_in.as_deref(), args.rewrite_out.as_deref())
-            {
-                write_rewrite_definition(rewrite_in, rewrite_out, &args.rewrite_append)?;
+            run_convert(&args)?;
+        }
+        Commands::ConvertUser(args) => {
+            run_convert_user(&args)?;
+        }
+        Commands::Compile(args) => {
+            for (text, surfaces) in args.smoke_text.iter().zip(run_compile(&args)?) {
+                println!("[compile] smoke: {} -> {}", text, surfaces.join("|"));
+            }
+        }
+        Commands::Package(args) => {
+            run_package(&args, None)?;
+        }
+        Commands::Lint(args) => {
+            run_lint(&args)?;
+        }
+        Commands::GenChar(args) => {
+            run_gen_char(&args)?;
+        }
+        Commands::Build(args) => {
+            let outputs = run_build(&args)?;
+            println!("[build] asset path: {}", outputs.asset_path.display());
+            if let Some(outputs_out) = args.outputs_out.as_deref() {
+                outputs.write_env_file(outputs_out)?;
+            }
+            if let Some(notes_out) = args.release_notes_out.as_deref() {
+                fs::write(notes_out, outputs.release_notes())?;
             }
-
-            stats.write_env_file(&args.stats_out)?;
         }
     }
 
@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::compile::{check_vibrato_ref, DEFAULT_SMOKE_TEXT};
+
+/// A TOML build manifest driving `build`.
+///
+/// `[build]` and `[source]` are echoed into `metadata.json`; `[inputs]` holds
+/// local paths (relative to the manifest) and is not.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildManifest {
+    pub build: BuildSettings,
+    pub source: SourceInfo,
+    #[serde(default, skip_serializing)]
+    pub inputs: BuildInputs,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildSettings {
+    pub edition: String,
+    pub vibrato_ref: String,
+    pub compat_target: String,
+    pub compat_mode: String,
+    pub feature_schema: String,
+    /// Directory under `inputs.rules_dir` holding the append files.
+    pub rules_profile: String,
+    #[serde(default = "default_smoke_text")]
+    pub smoke_text: Vec<String>,
+    /// Fails the build when POS, ctype or cform fallbacks exceed this share of lexicon rows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_fallback_rate: Option<f64>,
+}
+
+/// Where the raw resources came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SourceInfo {
+    pub sudachidict_repo: String,
+    pub sudachidict_release_tag: String,
+    pub dict_version: String,
+    pub sudachi_repo: String,
+    pub sudachi_version: String,
+    pub sudachi_tag: String,
+}
+
+/// Local input files; either `lex` (with `matrix`) or `dic` is required.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildInputs {
+    #[serde(default)]
+    pub lex: Vec<PathBuf>,
+    pub dic: Option<PathBuf>,
+    pub matrix: Option<PathBuf>,
+    pub char: PathBuf,
+    pub unk: PathBuf,
+    pub rewrite: Option<PathBuf>,
+    pub license: PathBuf,
+    pub legal: PathBuf,
+    #[serde(default = "default_rules_dir")]
+    pub rules_dir: PathBuf,
+    pub pos_map: Option<PathBuf>,
+    #[serde(default)]
+    pub accent: Vec<PathBuf>,
+    /// SudachiDict `synonyms.txt`, bundled as is.
+    pub synonyms: Option<PathBuf>,
+}
+
+impl Default for BuildInputs {
+    fn default() -> Self {
+        Self {
+            lex: Vec::new(),
+            dic: None,
+            matrix: None,
+            char: PathBuf::new(),
+            unk: PathBuf::new(),
+            rewrite: None,
+            license: PathBuf::new(),
+            legal: PathBuf::new(),
+            rules_dir: default_rules_dir(),
+            pos_map: None,
+            accent: Vec::new(),
+            synonyms: None,
+        }
+    }
+}
+
+fn default_smoke_text() -> Vec<String> {
+    vec![DEFAULT_SMOKE_TEXT.to_string()]
+}
+
+fn default_rules_dir() -> PathBuf {
+    PathBuf::from("rules")
+}
+
+impl BuildManifest {
+    /// Parses a manifest and resolves its input paths against the manifest directory.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let mut manifest = Self::from_toml(&content)
+            .with_context(|| format!("invalid build manifest {}", path.display()))?;
+        manifest.resolve_paths(path.parent().unwrap_or_else(|| Path::new("")));
+        Ok(manifest)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        let manifest: Self = toml::from_str(content)?;
+        check_vibrato_ref(&manifest.build.vibrato_ref).context("invalid build.vibrato_ref")?;
+        let inputs = &manifest.inputs;
+        match (inputs.lex.is_empty(), &inputs.dic, &inputs.matrix) {
+            (false, Some(_), _) => Err(anyhow!("inputs.lex and inputs.dic are exclusive")),
+            (true, None, _) => Err(anyhow!("one of inputs.lex or inputs.dic is required")),
+            (false, None, None) => Err(anyhow!("inputs.lex requires inputs.matrix")),
+            _ => Ok(manifest),
+        }
+    }
+
+    fn resolve_paths(&mut self, base: &Path) {
+        let inputs = &mut self.inputs;
+        let resolve = |path: &mut PathBuf| *path = base.join(&*path);
+        inputs.lex.iter_mut().for_each(resolve);
+        inputs.accent.iter_mut().for_each(resolve);
+        [&mut inputs.char, &mut inputs.unk, &mut inputs.license]
+            .into_iter()
+            .chain([&mut inputs.legal, &mut inputs.rules_dir])
+            .for_each(resolve);
+        [
+            &mut inputs.dic,
+            &mut inputs.matrix,
+            &mut inputs.rewrite,
+            &mut inputs.pos_map,
+            &mut inputs.synonyms,
+        ]
+        .into_iter()
+        .flatten()
+        .for_each(resolve);
+    }
+
+    /// `sudachidict-<version>-<edition>+vibrato-v0_5_2.tar.xz`
+    pub fn asset_name(&self) -> String {
+        format!(
+            "sudachidict-{}-{}+vibrato-{}.tar.xz",
+            self.source.dict_version,
+            self.build.edition,
+            self.vibrato_tag()
+        )
+    }
+
+    /// `sudachi-<version>-<edition>-vibrato-v0_5_2`
+    pub fn release_tag(&self) -> String {
+        format!(
+            "sudachi-{}-{}-vibrato-{}",
+            self.source.dict_version,
+            self.build.edition,
+            self.vibrato_tag()
+        )
+    }
+
+    pub fn release_title(&self) -> String {
+        format!(
+            "SudachiDict {} {} (Vibrato {})",
+            self.source.dict_version, self.build.edition, self.build.vibrato_ref
+        )
+    }
+
+    fn vibrato_tag(&self) -> String {
+        self.build.vibrato_ref.replace('.', "_")
+    }
+}
@@ -1,6 +1,16 @@
+pub mod cform;
+pub mod ctype;
+pub mod pos_map;
+pub mod pron;
+
 use once_cell::sync::Lazy;
 use std::collections::HashSet;
 
+pub use cform::infer_cform;
+pub use ctype::infer_ctype;
+pub use pos_map::{PosMapping, PosRule};
+pub use pron::pronunciation_from_reading;
+
 static ALLOWED_CTYPE: Lazy<HashSet<&'static str>> = Lazy::new(|| {
     [
         "*",
@@ -118,105 +128,7 @@ static ALLOWED_CFORM: Lazy<HashSet<&'static str>> = Lazy::new(|| {
 });
 
 pub fn normalize_pos(pos0: &str, pos1: &str, pos2: &str, pos3: &str) -> [String; 4] {
-    let pos0 = pos0.trim();
-    let pos1 = pos1.trim();
-    let pos2 = pos2.trim();
-    let pos3 = pos3.trim();
-
-    match pos0 {
-        "名詞" | "代名詞" | "形状詞" | "接尾辞" => [
-            "名詞".to_string(),
-            if is_numeric_subpos(pos1, pos2, pos3) {
-                "数".to_string()
-            } else {
-                "一般".to_string()
-            },
-            "*".to_string(),
-            "*".to_string(),
-        ],
-        "動詞" => [
-            "動詞".to_string(),
-            "自立".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-        ],
-        "形容詞" => [
-            "形容詞".to_string(),
-            "自立".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-        ],
-        "助詞" => [
-            "助詞".to_string(),
-            "格助詞".to_string(),
-            "一般".to_string(),
-            "*".to_string(),
-        ],
-        "助動詞" => [
-            "助動詞".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-        ],
-        "副詞" => [
-            "副詞".to_string(),
-            "一般".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-        ],
-        "接続詞" => [
-            "接続詞".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-        ],
-        "連体詞" => [
-            "連体詞".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-        ],
-        "感動詞" => [
-            "感動詞".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-        ],
-        "接頭辞" | "接頭詞" => [
-            "接頭詞".to_string(),
-            "名詞接続".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-        ],
-        "記号" | "補助記号" | "空白" => [
-            "記号".to_string(),
-            if is_numeric_subpos(pos1, pos2, pos3) {
-                "数".to_string()
-            } else {
-                "一般".to_string()
-            },
-            "*".to_string(),
-            "*".to_string(),
-        ],
-        "フィラー" => [
-            "フィラー".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-        ],
-        _ => [
-            "その他".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-            "*".to_string(),
-        ],
-    }
-}
-
-fn is_numeric_subpos(pos1: &str, pos2: &str, pos3: &str) -> bool {
-    [pos1, pos2, pos3]
-        .into_iter()
-        .any(|v| matches!(v, "数" | "数詞"))
+    PosMapping::default_table().map([pos0, pos1, pos2, pos3]).0
 }
 
 pub fn normalize_ctype(value: &str) -> (String, bool) {
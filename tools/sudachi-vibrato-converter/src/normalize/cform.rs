@@ -0,0 +1,78 @@
+use crate::normalize::{normalize_cform, normalize_text_or_star};
+
+/// Infers the IPADIC conjugation form from Sudachi's `col10`.
+///
+/// Sudachi splits forms into a main form and a sub-variant (`連用形-促音便`).
+/// IPADIC instead names forms after what may follow them, so the mapping also
+/// needs the IPADIC `ctype` (already inferred) and the surface ending. Values
+/// this does not recognize go through [`normalize_cform`].
+/// Returns `(cform, fell_back_to_star)`.
+pub fn infer_cform(value: &str, ctype: &str, surface: &str) -> (String, bool) {
+    let src = normalize_text_or_star(value);
+    let canonical: String = src.chars().filter(|c| !c.is_whitespace()).collect();
+
+    match infer_known(&canonical, ctype, surface) {
+        Some(cform) => (cform.to_string(), false),
+        None => normalize_cform(&src),
+    }
+}
+
+fn infer_known(canonical: &str, ctype: &str, surface: &str) -> Option<&'static str> {
+    let (form, variant) = canonical.split_once('-').unwrap_or((canonical, ""));
+    let group = ctype.split_once('・').map_or(ctype, |(group, _)| group);
+    let adjective_like = group == "形容詞" || ctype == "特殊・ナイ" || ctype == "特殊・タイ";
+
+    let cform = match (form, variant) {
+        ("終止形" | "連体形", "撥音便") => "体言接続特殊",
+        ("連体形", "省略") => "体言接続特殊２",
+        ("連体形", _) if group == "形容詞" && surface.ends_with('き') => "体言接続",
+        ("連体形", _) if ctype == "特殊・ダ" && surface.ends_with('な') => "体言接続",
+        ("終止形" | "連体形", _) => "基本形",
+
+        ("連用形", "イ音便") if ctype == "五段・ラ行特殊" => "連用形",
+        ("連用形", "イ音便" | "促音便" | "撥音便") => "連用タ接続",
+        ("連用形", "ウ音便") if adjective_like => "連用ゴザイ接続",
+        ("連用形", "ウ音便") => "連用タ接続",
+        ("連用形", "ニ") => "連用ニ接続",
+        // IPADIC has `ないで` (見ないで) as a form of ない; Sudachi rows spelled so map to it.
+        ("連用形", _) if ctype == "特殊・ナイ" && surface.ends_with("ないで") => {
+            "連用デ接続"
+        }
+        ("連用形", _) if adjective_like && surface.ends_with('く') => "連用テ接続",
+        ("連用形", _) if adjective_like && surface.ends_with("かっ") => "連用タ接続",
+        ("連用形", _) if ctype == "特殊・ヌ" && surface.ends_with('ず') => "連用ニ接続",
+        ("連用形", _) => "連用形",
+
+        ("未然形", "撥音便") => "未然特殊",
+        ("未然形", "サ") => "未然レル接続",
+        ("未然形", "セ") => "未然ヌ接続",
+        ("未然形", _) => "未然形",
+        ("意志推量形", _) => "未然ウ接続",
+
+        ("仮定形", "融合") if group == "形容詞" && surface.ends_with("きゃ") => {
+            "仮定縮約２"
+        }
+        ("仮定形", "融合") => "仮定縮約１",
+        ("仮定形" | "已然形", _) => "仮定形",
+
+        ("命令形", _) => imperative(group, ctype, surface),
+        ("語幹", _) if group == "形容詞" => "ガル接続",
+        _ => return None,
+    };
+    Some(cform)
+}
+
+/// IPADIC names imperatives after their ending (`書け`, `起きろ`, `起きよ`, `来い`).
+fn imperative(group: &str, ctype: &str, surface: &str) -> &'static str {
+    if surface.ends_with('ろ') || surface.ends_with('ロ') {
+        "命令ｒｏ"
+    } else if surface.ends_with('よ') || surface.ends_with('ヨ') {
+        "命令ｙｏ"
+    } else if surface.ends_with('い') || surface.ends_with('イ') {
+        "命令ｉ"
+    } else if group == "一段" || ctype.starts_with("サ変") {
+        "命令ｙｏ"
+    } else {
+        "命令ｅ"
+    }
+}
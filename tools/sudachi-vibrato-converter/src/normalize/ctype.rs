@@ -0,0 +1,171 @@
+use crate::normalize::{normalize_ctype, normalize_text_or_star, pron::vowel_of};
+
+const IKU: &[&str] = &["行く", "いく", "逝く", "往く"];
+const RA_SPECIAL: &[&str] = &[
+    "くださる",
+    "下さる",
+    "なさる",
+    "為さる",
+    "いらっしゃる",
+    "おっしゃる",
+    "仰る",
+    "仰有る",
+    "ござる",
+    "御座る",
+];
+const RA_ARU: &[&str] = &["ある", "有る", "在る"];
+const WA_U_ONBIN: &[&str] = &["問う", "請う", "乞う"];
+
+/// Sudachi `助動詞-*` and `文語助動詞-*` suffixes that map to an IPADIC type one to one.
+const AUXILIARY: &[(&str, &str)] = &[
+    ("タ", "特殊・タ"),
+    ("ダ", "特殊・ダ"),
+    ("デス", "特殊・デス"),
+    ("ドス", "特殊・ドス"),
+    ("ジャ", "特殊・ジャ"),
+    ("マス", "特殊・マス"),
+    ("ナイ", "特殊・ナイ"),
+    ("タイ", "特殊・タイ"),
+    ("ヌ", "特殊・ヌ"),
+    ("ヤ", "特殊・ヤ"),
+    ("レル", "一段"),
+    ("ラシイ", "形容詞・イ段"),
+    ("ベシ", "文語・ベシ"),
+    ("ゴトシ", "文語・ゴトシ"),
+    ("ナリ", "文語・ナリ"),
+    ("マジ", "文語・マジ"),
+    ("シム", "文語・シム"),
+    ("キ", "文語・キ"),
+    ("ケリ", "文語・ケリ"),
+    ("ル", "文語・ル"),
+    ("リ", "文語・リ"),
+];
+
+/// Infers the IPADIC conjugation type from Sudachi's `col9` and the dictionary form.
+///
+/// Sudachi collapses several IPADIC types (e.g. `五段-カ行` covers both イ音便 and
+/// 行く's 促音便), so the lemma decides the variant. `read` is only consulted to
+/// tell `行く` (イク) from `行く` (ユク). Values this does not recognize go through
+/// [`normalize_ctype`]. Returns `(ctype, fell_back_to_star)`.
+pub fn infer_ctype(value: &str, base: &str, read: &str) -> (String, bool) {
+    let src = normalize_text_or_star(value);
+    let canonical: String = src
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '・' | '－' | '−' => '-',
+            _ => c,
+        })
+        .collect();
+
+    match infer_known(&canonical, base, read) {
+        Some(ctype) => (ctype.to_string(), false),
+        None => normalize_ctype(&src),
+    }
+}
+
+fn infer_known(canonical: &str, base: &str, read: &str) -> Option<&'static str> {
+    let (group, row) = canonical.split_once('-').unwrap_or((canonical, ""));
+
+    let ctype = match (group, row) {
+        ("五段", "カ行") if ends_with_any(base, IKU) || base.ends_with("ゆく") => {
+            if base.ends_with("ゆく") || read.contains('ユ') {
+                "五段・カ行促音便ユク"
+            } else {
+                "五段・カ行促音便"
+            }
+        }
+        ("五段", "カ行") => "五段・カ行イ音便",
+        ("五段", "ガ行") => "五段・ガ行",
+        ("五段", "サ行") => "五段・サ行",
+        ("五段", "タ行") => "五段・タ行",
+        ("五段", "ナ行") => "五段・ナ行",
+        ("五段", "バ行") => "五段・バ行",
+        ("五段", "マ行") => "五段・マ行",
+        ("五段", "ラ行") if ends_with_any(base, RA_SPECIAL) => "五段・ラ行特殊",
+        ("五段", "ラ行") if RA_ARU.contains(&base) => "五段・ラ行アル",
+        ("五段", "ラ行") => "五段・ラ行",
+        ("五段", "ワア行") | ("五段", "ワ行")
+            if ends_with_any(base, WA_U_ONBIN) || base == "とう" || base == "こう" =>
+        {
+            "五段・ワ行ウ音便"
+        }
+        ("五段", "ワア行") | ("五段", "ワ行") => "五段・ワ行促音便",
+        ("上一段", _) | ("下一段", _) if ends_with_any(base, &["くれる", "呉れる"]) => {
+            "一段・クレル"
+        }
+        ("上一段", _) | ("下一段", _) => "一段",
+        ("カ行変格", _) if base.ends_with("来る") => "カ変・来ル",
+        ("カ行変格", _) => "カ変・クル",
+        ("サ行変格", _) if ends_with_any(base, &["ずる", "ズル"]) => "サ変・−ズル",
+        ("サ行変格", _) if base == "する" || base == "為る" => "サ変・スル",
+        ("サ行変格", _) => "サ変・−スル",
+        ("形容詞", _) => adjective_ctype(base),
+        ("無変化型", _) => "不変化型",
+        ("助動詞", suffix) | ("文語助動詞", suffix) => AUXILIARY
+            .iter()
+            .find(|(name, _)| *name == suffix)
+            .map(|(_, ctype)| *ctype)?,
+        ("文語四段", row) => classical("四段", row)?,
+        ("文語下二段", row) => classical("下二", row)?,
+        ("文語上二段", row) => classical("上二", row)?,
+        ("文語ラ行変格", _) => "ラ変",
+        _ => return None,
+    };
+    Some(ctype)
+}
+
+/// IPADIC splits adjectives by the vowel of the mora before the final `い`.
+fn adjective_ctype(base: &str) -> &'static str {
+    if base == "いい" || base == "イイ" {
+        return "形容詞・イイ";
+    }
+    let mut chars = base.chars().rev();
+    let stem_end = match (chars.next(), chars.next()) {
+        (Some('い'), Some(c)) => c,
+        _ => return "形容詞・アウオ段",
+    };
+    if vowel_of(stem_end) == Some('i') {
+        "形容詞・イ段"
+    } else {
+        "形容詞・アウオ段"
+    }
+}
+
+fn classical(group: &str, row: &str) -> Option<&'static str> {
+    const TYPES: &[&str] = &[
+        "四段・カ行",
+        "四段・ガ行",
+        "四段・サ行",
+        "四段・タ行",
+        "四段・バ行",
+        "四段・マ行",
+        "四段・ラ行",
+        "四段・ハ行",
+        "下二・ア行",
+        "下二・カ行",
+        "下二・ガ行",
+        "下二・サ行",
+        "下二・ザ行",
+        "下二・タ行",
+        "下二・ダ行",
+        "下二・ナ行",
+        "下二・ハ行",
+        "下二・バ行",
+        "下二・マ行",
+        "下二・ヤ行",
+        "下二・ラ行",
+        "下二・ワ行",
+        "上二・ダ行",
+        "上二・ハ行",
+    ];
+    TYPES.iter().copied().find(|ctype| {
+        ctype
+            .split_once('・')
+            .is_some_and(|(g, r)| g == group && r == row)
+    })
+}
+
+fn ends_with_any(value: &str, suffixes: &[&str]) -> bool {
+    suffixes.iter().any(|suffix| value.ends_with(suffix))
+}
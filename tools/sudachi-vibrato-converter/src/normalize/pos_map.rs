@@ -0,0 +1,153 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+
+use crate::normalize::normalize_text_or_star;
+
+const DEFAULT_TABLE: &str = include_str!("../../data/sudachi-ipadic-pos.csv");
+
+static DEFAULT_MAPPING: Lazy<PosMapping> = Lazy::new(|| {
+    PosMapping::from_reader(DEFAULT_TABLE.as_bytes())
+        .expect("bundled data/sudachi-ipadic-pos.csv must be valid")
+});
+
+const FALLBACK_POS: [&str; 4] = ["その他", "*", "*", "*"];
+
+/// One line of a POS mapping table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PosRule {
+    /// 1-based line number in the table file.
+    pub line: usize,
+    pub source: [String; 4],
+    pub target: [String; 4],
+    pub priority: i32,
+}
+
+impl PosRule {
+    fn matches(&self, pos: &[&str; 4]) -> bool {
+        self.source
+            .iter()
+            .zip(pos)
+            .all(|(pattern, value)| pattern == "*" || pattern == value)
+    }
+
+    /// Whether the rule only matches through wildcards (e.g. `*,*,*,*`).
+    pub fn is_catch_all(&self) -> bool {
+        self.specificity() == 0
+    }
+
+    fn specificity(&self) -> usize {
+        self.source.iter().filter(|pattern| *pattern != "*").count()
+    }
+}
+
+/// Sudachi 4-level POS to IPADIC 4-level POS mapping table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PosMapping {
+    rules: Vec<PosRule>,
+}
+
+impl Default for PosMapping {
+    fn default() -> Self {
+        Self::default_table().clone()
+    }
+}
+
+impl PosMapping {
+    /// The table bundled from `data/sudachi-ipadic-pos.csv`.
+    pub fn default_table() -> &'static PosMapping {
+        &DEFAULT_MAPPING
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("failed to open pos map {}", path.display()))?;
+        Self::from_reader(file).with_context(|| format!("invalid pos map {}", path.display()))
+    }
+
+    pub fn from_reader<R: Read>(input: R) -> Result<Self> {
+        let reader = BufReader::new(input);
+        let mut rules = Vec::new();
+
+        for (line_no, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() != 8 && fields.len() != 9 {
+                return Err(anyhow!(
+                    "invalid pos map row at line {}: expected 8 or 9 columns, got {}",
+                    line_no + 1,
+                    fields.len()
+                ));
+            }
+
+            let priority = match fields.get(8) {
+                Some(value) => value.parse::<i32>().with_context(|| {
+                    format!(
+                        "failed to parse priority='{}' at line {}",
+                        value,
+                        line_no + 1
+                    )
+                })?,
+                None => 0,
+            };
+
+            rules.push(PosRule {
+                line: line_no + 1,
+                source: std::array::from_fn(|i| normalize_text_or_star(fields[i])),
+                target: std::array::from_fn(|i| normalize_text_or_star(fields[i + 4])),
+                priority,
+            });
+        }
+
+        Ok(Self { rules })
+    }
+
+    pub fn rules(&self) -> &[PosRule] {
+        &self.rules
+    }
+
+    /// Returns the rule that wins for `pos`, or `None` when nothing matches.
+    pub fn resolve(&self, pos: [&str; 4]) -> Option<&PosRule> {
+        let mut best: Option<&PosRule> = None;
+        for rule in self.rules.iter().filter(|rule| rule.matches(&pos)) {
+            best = match best {
+                Some(current)
+                    if (current.priority, current.specificity())
+                        >= (rule.priority, rule.specificity()) =>
+                {
+                    Some(current)
+                }
+                _ => Some(rule),
+            };
+        }
+        best
+    }
+
+    /// Maps a source tuple, returning the IPADIC tuple and the winning rule.
+    ///
+    /// Tuples that match no rule become `その他,*,*,*`.
+    pub fn map(&self, pos: [&str; 4]) -> ([String; 4], Option<&PosRule>) {
+        let pos = pos.map(|value| {
+            let value = value.trim();
+            if value.is_empty() {
+                "*"
+            } else {
+                value
+            }
+        });
+
+        match self.resolve(pos) {
+            Some(rule) => (rule.target.clone(), Some(rule)),
+            None => (FALLBACK_POS.map(str::to_string), None),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+/// Fixed expressions whose trailing `ハ` is the topic particle.
+const WA_EXPRESSIONS: &[(&str, &str)] = &[
+    ("コンニチハ", "コンニチワ"),
+    ("コンバンハ", "コンバンワ"),
+    ("デハ", "デワ"),
+    ("ソレデハ", "ソレデワ"),
+    ("デハナイ", "デワナイ"),
+];
+
+/// Derives an IPADIC-style pronunciation from a katakana reading.
+///
+/// - particles: `ハ` -> `ワ`, `ヘ` -> `エ` when `pos1` is `助詞`
+/// - `ヅ` -> `ズ`, `ヂ` -> `ジ`
+/// - long vowels: `オウ`/`オオ`/`ウウ`/`エイ`/`エエ`/`アア` -> `ー`, except a verb-final `ウ`
+pub fn pronunciation_from_reading(read: &str, pos1: &str) -> String {
+    if read == "*" {
+        return read.to_string();
+    }
+
+    if let Some((_, pron)) = WA_EXPRESSIONS.iter().find(|(reading, _)| *reading == read) {
+        return pron.to_string();
+    }
+
+    let mut chars: Vec<char> = read.chars().collect();
+    if pos1 == "助詞" {
+        match chars.as_slice() {
+            ['ヘ'] => chars[0] = 'エ',
+            [.., 'ハ'] => *chars.last_mut().unwrap() = 'ワ',
+            _ => {}
+        }
+    }
+
+    let last = chars.len().saturating_sub(1);
+    let mut pron = String::with_capacity(read.len());
+    let mut prev_vowel = None;
+    for (i, c) in chars.into_iter().enumerate() {
+        let c = match c {
+            'ヅ' => 'ズ',
+            'ヂ' => 'ジ',
+            _ => c,
+        };
+        let keep_verb_ending = i == last && c == 'ウ' && pos1 == "動詞";
+        if !keep_verb_ending && lengthens(prev_vowel, c) {
+            pron.push('ー');
+            continue;
+        }
+        pron.push(c);
+        prev_vowel = match c {
+            'ー' => prev_vowel,
+            _ => vowel_of(c),
+        };
+    }
+    pron
+}
+
+fn lengthens(prev_vowel: Option<char>, c: char) -> bool {
+    matches!(
+        (prev_vowel, c),
+        (Some('o'), 'ウ' | 'オ') | (Some('e'), 'イ' | 'エ') | (Some('u'), 'ウ') | (Some('a'), 'ア')
+    )
+}
+
+/// Vowel of a hiragana or katakana character; `None` for `ン`, `ッ` and non-kana.
+pub(crate) fn vowel_of(c: char) -> Option<char> {
+    const ROWS: [(char, &str); 5] = [
+        ('a', "アカガサザタダナハバパマヤラワャァヮヵ"),
+        ('i', "イキギシジチヂニヒビピミリヰィ"),
+        ('u', "ウクグスズツヅヌフブプムユルュゥヴ"),
+        ('e', "エケゲセゼテデネヘベペメレヱェヶ"),
+        ('o', "オコゴソゾトドノホボポモヨロヲョォ"),
+    ];
+    let c = match c {
+        'ぁ'..='ゖ' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+        _ => c,
+    };
+    ROWS.iter()
+        .find(|(_, kana)| kana.contains(c))
+        .map(|(vowel, _)| *vowel)
+}
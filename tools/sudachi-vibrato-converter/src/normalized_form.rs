@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use vibrato::dictionary::LexType;
+use vibrato::token::Token;
+
+use crate::sidecar::{write_header, SidecarReader, StringTable};
+use crate::split_info::SplitInfo;
+use crate::word_units::{SplitToken, SplitWord};
+
+const MAGIC: &[u8; 4] = b"SVNF";
+const VERSION: u32 = 1;
+
+/// Sudachi normalized form (`col12`) of each converted lexicon row, stored as
+/// `normalized_forms.bin`.
+///
+/// Forms equal to the surface are stored empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NormalizedForms {
+    forms: StringTable,
+}
+
+impl NormalizedForms {
+    /// Appends the next row.
+    pub(crate) fn push(&mut self, surface: &str, normalized_form: &str) {
+        let normalized_form = normalized_form.trim();
+        if normalized_form.is_empty() || normalized_form == "*" || normalized_form == surface {
+            self.forms.push("");
+        } else {
+            self.forms.push(normalized_form);
+        }
+    }
+
+    /// Number of rows covered.
+    pub fn len(&self) -> usize {
+        self.forms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.forms.len() == 0
+    }
+
+    /// Normalized form of a system lexicon row; `None` when it is the surface.
+    pub fn get(&self, row: u32) -> Option<&str> {
+        self.forms.get(row as usize).filter(|form| !form.is_empty())
+    }
+
+    /// Normalized form of a token, or its surface for unknown, user and appended words.
+    pub fn of_token<'a>(&'a self, token: &Token<'a, '_>) -> &'a str {
+        let word_idx = token.word_idx();
+        match word_idx.lex_type {
+            LexType::System => self.get(word_idx.word_id).unwrap_or(token.surface()),
+            _ => token.surface(),
+        }
+    }
+
+    /// Like [`Self::of_token`] for a token from [`crate::Resplitter`].
+    pub fn of_split_token<'a>(
+        &'a self,
+        token: &'a SplitToken,
+        split_info: &'a SplitInfo,
+    ) -> &'a str {
+        let form = match token.word {
+            SplitWord::Lexicon(word_idx) if word_idx.lex_type == LexType::System => {
+                self.get(word_idx.word_id)
+            }
+            SplitWord::Lexicon(_) => None,
+            SplitWord::SplitOnly(word_id) => split_info
+                .get(word_id)
+                .map(|entry| entry.normalized_form.as_str())
+                .filter(|form| *form != "*"),
+        };
+        form.unwrap_or(&token.surface)
+    }
+
+    /// `SVNF`, version, then the forms as a string table.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        write_header(&mut out, MAGIC, VERSION);
+        self.forms.write(&mut out)?;
+        Ok(out)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = SidecarReader::new(bytes, MAGIC, VERSION)?;
+        let forms = StringTable::read(&mut reader)?;
+        reader.finish()?;
+        Ok(Self { forms })
+    }
+
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_bytes()?)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::from_bytes(&bytes)
+            .with_context(|| format!("invalid normalized forms {}", path.display()))
+    }
+}
@@ -0,0 +1,134 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Error};
+
+use crate::accent::AccentDictionary;
+use crate::normalize::PosMapping;
+use crate::schema::{FeatureSchema, Mecab9};
+
+/// Settings shared by the lexicon and unknown-word converters.
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    pub pos_mapping: PosMapping,
+    pub feature_schema: Arc<dyn FeatureSchema>,
+    /// Joined onto lexicon rows; empty means no accent join.
+    pub accent: AccentDictionary,
+    /// Keep unmatched accent keys in the returned `LexiconSidecars` for
+    /// `write_unmatched_accent_report`.
+    pub collect_unmatched_accent: bool,
+    /// What to do with malformed lexicon and unk rows.
+    pub on_error: ErrorPolicy,
+    /// Keep split-only rows in the returned `LexiconSidecars` for `split_info.csv`.
+    pub collect_split_info: bool,
+    /// Keep the A/B units of written rows in the returned `LexiconSidecars` for `word_units.bin`.
+    ///
+    /// Split-only rows are collected too, since units may refer to them.
+    pub collect_word_units: bool,
+    /// Keep the normalized forms of written rows in the returned `LexiconSidecars` for
+    /// `normalized_forms.bin`.
+    pub collect_normalized_forms: bool,
+    /// Keep the Sudachi word id and source of written rows in the returned
+    /// `LexiconSidecars` for `word_ids.bin`.
+    pub collect_word_ids: bool,
+    /// Keep the synonym group ids of written rows in the returned `LexiconSidecars` for
+    /// `synonym_groups.bin`.
+    pub collect_synonym_groups: bool,
+}
+
+/// Handling of rows with too few columns or unparsable ids.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Abort on the first malformed row.
+    #[default]
+    Fail,
+    /// Drop malformed rows, counting them in `rejected_rows`.
+    Skip,
+    /// Like `Skip`, and keep the rows for `write_rejects_report`.
+    Collect,
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "fail" => Ok(Self::Fail),
+            "skip" => Ok(Self::Skip),
+            "collect" => Ok(Self::Collect),
+            _ => Err(anyhow!(
+                "unknown error policy '{}': expected fail, skip or collect",
+                value
+            )),
+        }
+    }
+}
+
+/// Handling of Sudachi's NOOOVBOW char.def flag, which Vibrato lacks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NoOovBowPolicy {
+    /// Add `NOOOVBOW_<BASE>` categories and unk.def rows so unknown words do not
+    /// start at flagged characters (see [`crate::CharDefinition::emulate_nooovbow`]).
+    #[default]
+    Emulate,
+    /// Drop the flag.
+    Strip,
+}
+
+impl FromStr for NoOovBowPolicy {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "emulate" => Ok(Self::Emulate),
+            "strip" => Ok(Self::Strip),
+            _ => Err(anyhow!(
+                "unknown NOOOVBOW policy '{}': expected emulate or strip",
+                value
+            )),
+        }
+    }
+}
+
+/// Which code points `gen-char` may assign a Unicode-derived category to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CharGenMode {
+    /// Only code points char.def leaves to `DEFAULT`.
+    #[default]
+    Fill,
+    /// Every code point; the derived category becomes the base one and the
+    /// existing categories are kept after it.
+    Override,
+}
+
+impl FromStr for CharGenMode {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "fill" => Ok(Self::Fill),
+            "override" => Ok(Self::Override),
+            _ => Err(anyhow!(
+                "unknown gen-char mode '{}': expected fill or override",
+                value
+            )),
+        }
+    }
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            pos_mapping: PosMapping::default(),
+            feature_schema: Arc::new(Mecab9),
+            accent: AccentDictionary::default(),
+            collect_unmatched_accent: false,
+            on_error: ErrorPolicy::Fail,
+            collect_split_info: false,
+            collect_word_units: false,
+            collect_normalized_forms: false,
+            collect_word_ids: false,
+            collect_synonym_groups: false,
+        }
+    }
+}
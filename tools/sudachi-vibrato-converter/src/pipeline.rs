@@ -0,0 +1,750 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::accent::AccentDictionary;
+use crate::bundle::{
+    Bundle, BundleMetadata, DICTIONARY_FILE, NORMALIZED_FORMS_FILE, REWRITE_FILE, SPLIT_INFO_FILE,
+    STATS_FILE, SYNONYMS_FILE, SYNONYM_GROUPS_FILE, WORD_IDS_FILE, WORD_UNITS_FILE,
+};
+use crate::char_def::CharDefinition;
+use crate::cli::{
+    BuildArgs, CompileArgs, ConvertArgs, ConvertUserArgs, GenCharArgs, LintArgs, PackageArgs,
+};
+use crate::compile::{
+    check_vibrato_ref, compile_dictionary, read_compressed_dictionary, smoke_tokenize,
+    write_compressed_dictionary,
+};
+use crate::convert_lex::{convert_lexicon_sources, LexiconSidecars};
+use crate::convert_unk::{
+    append_nooovbow_unknown_rows, convert_unknown_dictionary_with_stats, resolve_unknown_append_ids,
+};
+use crate::convert_user::{convert_user_lexicon, MatrixSize, SystemLexicon};
+use crate::gen_char::{
+    generate_char_definition, unicode_category, CharCoverage, UNICODE_CATEGORIES,
+};
+use crate::inject::{append_lexicon_rows_with_options, write_rewrite_definition};
+use crate::lexicon::LexiconSource;
+use crate::lint::{lint_paths, LintReport};
+use crate::manifest::BuildManifest;
+use crate::normalize::PosMapping;
+use crate::options::{ConvertOptions, ErrorPolicy, NoOovBowPolicy};
+use crate::schema::{feature_schema_by_name, FeatureSchema, Jpreprocess12};
+use crate::stats::ConversionStats;
+use crate::sudachi_dic::SudachiDictionary;
+use crate::synonyms::SynonymDictionary;
+
+/// Returns the stats and the sidecar tables the run collected.
+pub fn run_convert(args: &ConvertArgs) -> Result<(ConversionStats, LexiconSidecars)> {
+    let mut stats = ConversionStats::default();
+    let mut options = convert_options(
+        args.feature_schema.as_deref(),
+        &args.accent_in,
+        args.accent_unmatched_out.is_some(),
+        args.pos_map.as_deref(),
+    )?;
+    options.on_error = args.on_error;
+    options.collect_split_info = args.split_info_out.is_some();
+    options.collect_word_units = args.word_units_out.is_some();
+    options.collect_normalized_forms = args.normalized_forms_out.is_some();
+    options.collect_word_ids = args.word_ids_out.is_some();
+    options.collect_synonym_groups = args.synonym_groups_out.is_some();
+
+    let lex_in = match args.dic_in.as_deref() {
+        Some(dic_in) => {
+            stats.record_input("dic", dic_in)?;
+            let dictionary = SudachiDictionary::from_path(dic_in)?;
+            if let Some(matrix_out) = args.matrix_out.as_deref() {
+                dictionary
+                    .grammar
+                    .write_matrix_def(BufWriter::new(File::create(matrix_out)?))?;
+            }
+            vec![dictionary.to_lexicon_source(file_name(dic_in))?]
+        }
+        None => read_lexicon_sources(&args.lex_in)?,
+    };
+    record_inputs(&mut stats, args)?;
+    let mut lex_out = BufWriter::new(File::create(&args.lex_out)?);
+    let mut sidecars = convert_lexicon_sources(&lex_in, &mut lex_out, &options, &mut stats)?;
+    for path in &args.lex_append {
+        let appended =
+            append_lexicon_rows_with_options(&mut lex_out, slice::from_ref(path), &options)?;
+        stats.lex_appended_rows += appended;
+        if options.collect_word_ids {
+            let source = file_name(path);
+            for _ in 0..appended {
+                sidecars.word_ids.push(&source, None)?;
+            }
+        }
+    }
+    lex_out.flush()?;
+
+    // Buffered until NOOOVBOW rows derived from it are appended.
+    let mut unk_out = Vec::new();
+    let unk_base = read(&args.unk_in)?;
+    stats.unk_rows = convert_unknown_dictionary_with_stats(
+        &file_name(&args.unk_in),
+        unk_base.as_slice(),
+        &mut unk_out,
+        &options,
+        &mut stats,
+    )?;
+    for path in &args.unk_append {
+        let rows = resolve_unknown_append_ids(&unk_base, &read(path)?)
+            .with_context(|| format!("failed to resolve ids in {}", path.display()))?;
+        stats.unk_appended_rows += convert_unknown_dictionary_with_stats(
+            &file_name(path),
+            rows.as_slice(),
+            &mut unk_out,
+            &options,
+            &mut stats,
+        )?;
+    }
+
+    let mut char_def = CharDefinition::default();
+    stats.char_dropped_ranges = char_def.merge_path(&args.char_in)?.dropped_ranges;
+    for path in &args.char_append {
+        stats.char_appended_lines += char_def.merge_path(path)?.lines;
+    }
+    stats.char_nooovbow_ranges = char_def.no_oov_bow_ranges().len();
+    if args.nooovbow == NoOovBowPolicy::Emulate {
+        let categories = char_def.emulate_nooovbow();
+        let mut rows = Vec::new();
+        stats.unk_nooovbow_rows = append_nooovbow_unknown_rows(&unk_out, &categories, &mut rows)?;
+        unk_out.extend(rows);
+    }
+    fs::write(&args.unk_out, &unk_out)
+        .with_context(|| format!("failed to write {}", args.unk_out.display()))?;
+    let mut char_out = BufWriter::new(File::create(&args.char_out)?);
+    char_def.write(&mut char_out)?;
+    char_out.flush()?;
+
+    if let (Some(rewrite_in), Some(rewrite_out)) =
+        (args.rewrite_in.as_deref(), args.rewrite_out.as_deref())
+    {
+        write_rewrite_definition(rewrite_in, rewrite_out, &args.rewrite_append)?;
+    }
+
+    stats.write_env_file(&args.stats_out)?;
+    if let Some(stats_json_out) = args.stats_json_out.as_deref() {
+        stats.write_json_file(stats_json_out)?;
+    }
+    if let Some(report) = args.pos_map_report.as_deref() {
+        stats.write_pos_mapping_report(report)?;
+    }
+    if let Some(report) = args.accent_unmatched_out.as_deref() {
+        sidecars.write_unmatched_accent_report(report)?;
+    }
+    if let Some(split_info_out) = args.split_info_out.as_deref() {
+        sidecars.split_info.write_to_path(split_info_out)?;
+    }
+    if let Some(word_units_out) = args.word_units_out.as_deref() {
+        sidecars.word_units.write_to_path(word_units_out)?;
+    }
+    if let Some(normalized_forms_out) = args.normalized_forms_out.as_deref() {
+        sidecars
+            .normalized_forms
+            .write_to_path(normalized_forms_out)?;
+    }
+    if let Some(word_ids_out) = args.word_ids_out.as_deref() {
+        sidecars.word_ids.write_to_path(word_ids_out)?;
+    }
+    if let Some(synonym_groups_out) = args.synonym_groups_out.as_deref() {
+        sidecars.synonym_groups.write_to_path(synonym_groups_out)?;
+    }
+    if let Some(report) = args.rejects_out.as_deref() {
+        stats.write_rejects_report(report)?;
+    }
+    if let Some(report) = args.fallback_report.as_deref() {
+        stats.write_fallback_report(report)?;
+    }
+    if let Some(max_rate) = args.max_fallback_rate {
+        stats.check_fallback_rate(max_rate)?;
+    }
+    Ok((stats, sidecars))
+}
+
+/// Records the digest of every input but the Sudachi binary dictionary.
+fn record_inputs(stats: &mut ConversionStats, args: &ConvertArgs) -> Result<()> {
+    let singles = [
+        ("unk", Some(&args.unk_in)),
+        ("char", Some(&args.char_in)),
+        ("rewrite", args.rewrite_in.as_ref()),
+        ("pos_map", args.pos_map.as_ref()),
+    ];
+    let lists = [
+        ("lex", &args.lex_in),
+        ("lex_append", &args.lex_append),
+        ("unk_append", &args.unk_append),
+        ("char_append", &args.char_append),
+        ("rewrite_append", &args.rewrite_append),
+        ("accent", &args.accent_in),
+    ];
+    for (role, path) in singles {
+        if let Some(path) = path {
+            stats.record_input(role, path)?;
+        }
+    }
+    for (role, paths) in lists {
+        for path in paths {
+            stats.record_input(role, path)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn run_convert_user(args: &ConvertUserArgs) -> Result<ConversionStats> {
+    let options = convert_options(
+        args.feature_schema.as_deref(),
+        &args.accent_in,
+        false,
+        args.pos_map.as_deref(),
+    )?;
+
+    let system = match (args.dic_in.as_deref(), args.matrix_in.as_deref()) {
+        (Some(dic_in), _) => {
+            let dictionary = SudachiDictionary::from_path(dic_in)?;
+            SystemLexicon::from_sources(
+                &[dictionary.to_lexicon_source(file_name(dic_in))?],
+                MatrixSize::from_grammar(&dictionary.grammar),
+            )?
+        }
+        (None, Some(matrix_in)) => SystemLexicon::from_sources(
+            &read_lexicon_sources(&args.lex_in)?,
+            MatrixSize::from_path(matrix_in)?,
+        )?,
+        (None, None) => return Err(anyhow!("--lex-in requires --matrix-in")),
+    };
+
+    let mut stats = ConversionStats::default();
+    let user_in = read_lexicon_sources(&args.user_in)?;
+    let mut out = BufWriter::new(File::create(&args.out)?);
+    convert_user_lexicon(&user_in, &system, &mut out, &options, &mut stats)?;
+    out.flush()?;
+
+    if let Some(stats_out) = args.stats_out.as_deref() {
+        stats.write_env_file(stats_out)?;
+    }
+    Ok(stats)
+}
+
+/// Lints `--char-in`/`--unk-in` with their appends, printing every finding.
+///
+/// Fails when the report has errors.
+pub fn run_lint(args: &LintArgs) -> Result<LintReport> {
+    let char_files: Vec<PathBuf> = std::iter::once(args.char_in.clone())
+        .chain(args.char_append.iter().cloned())
+        .collect();
+    let unk_files: Vec<PathBuf> = std::iter::once(args.unk_in.clone())
+        .chain(args.unk_append.iter().cloned())
+        .collect();
+    let report = lint_paths(&char_files, &unk_files)?;
+    print_lint_report(&report, "[lint]")?;
+    Ok(report)
+}
+
+fn print_lint_report(report: &LintReport, prefix: &str) -> Result<()> {
+    for line in report.lines() {
+        println!("{} {}", prefix, line);
+    }
+    match report.errors() {
+        0 => Ok(()),
+        errors => Err(anyhow!("char.def/unk.def lint found {} errors", errors)),
+    }
+}
+
+/// Merges Unicode-derived categories into `--char-in` with its appends, writes
+/// the changed ranges and prints the coverage summary.
+pub fn run_gen_char(args: &GenCharArgs) -> Result<CharCoverage> {
+    let categories = if args.category.is_empty() {
+        UNICODE_CATEGORIES.iter().collect()
+    } else {
+        args.category
+            .iter()
+            .map(|name| {
+                unicode_category(name)
+                    .ok_or_else(|| anyhow!("unknown gen-char category '{}'", name))
+            })
+            .collect::<Result<Vec<_>>>()?
+    };
+    let mut base = CharDefinition::from_path(&args.char_in)?;
+    for path in &args.char_append {
+        base.merge_path(path)?;
+    }
+    let coverage = generate_char_definition(&base, &categories, args.mode)?;
+
+    let mut out = BufWriter::new(File::create(&args.out)?);
+    coverage.overlay.write(&mut out)?;
+    out.flush()?;
+    if let Some(report_out) = args.report_out.as_deref() {
+        coverage.write_csv(BufWriter::new(File::create(report_out)?))?;
+    }
+    for line in coverage.lines() {
+        println!("[gen-char] {}", line);
+    }
+    Ok(coverage)
+}
+
+/// Compiles, writes `system.dic.zst`, then smoke-tokenizes the written file.
+///
+/// Returns the surfaces of each smoke text.
+pub fn run_compile(args: &CompileArgs) -> Result<Vec<Vec<String>>> {
+    let dictionary = compile_dictionary(
+        &read(&args.lex_in)?,
+        &read(&args.matrix_in)?,
+        &read(&args.char_in)?,
+        &read(&args.unk_in)?,
+    )?;
+    write_compressed_dictionary(&dictionary, BufWriter::new(File::create(&args.out)?))?;
+
+    let written = read_compressed_dictionary(BufReader::new(File::open(&args.out)?))?;
+    let texts: Vec<&str> = args.smoke_text.iter().map(String::as_str).collect();
+    smoke_tokenize(written, &texts)
+}
+
+/// Writes the release tarball; `manifest` is echoed into the metadata when given.
+pub fn run_package(args: &PackageArgs, manifest: Option<&BuildManifest>) -> Result<Bundle> {
+    let root = file_name(&args.out);
+    let root = root
+        .strip_suffix(".tar.xz")
+        .ok_or_else(|| anyhow!("--out must end with .tar.xz, got {}", args.out.display()))?;
+    check_vibrato_ref(&args.vibrato_ref)?;
+
+    let mut metadata = BundleMetadata {
+        sudachidict_repo: args.sudachidict_repo.clone(),
+        sudachidict_release_tag: args.sudachidict_release_tag.clone(),
+        sudachidict_dict_version: args.dict_version.clone(),
+        edition: args.edition.clone(),
+        sudachi_repo: args.sudachi_repo.clone(),
+        sudachi_version: args.sudachi_version.clone(),
+        sudachi_tag: args.sudachi_tag.clone(),
+        vibrato_ref: args.vibrato_ref.clone(),
+        compat_target: args.compat_target.clone(),
+        compat_mode: args.compat_mode.clone(),
+        rules_profile: args.rules_profile.clone(),
+        built_at_utc: args.built_at_utc.clone(),
+        dictionary_file: DICTIONARY_FILE.to_string(),
+        manifest: manifest.cloned(),
+        ..BundleMetadata::default()
+    };
+    metadata.record_stats(&ConversionStats::read_json_file(&args.stats_in)?);
+
+    let mut bundle = Bundle::new(root, metadata);
+    bundle.add_file(STATS_FILE, read(&args.stats_in)?);
+    bundle.add_file(DICTIONARY_FILE, read(&args.dict_in)?);
+    bundle.add_file("LICENSE-2.0.txt", read(&args.license_in)?);
+    bundle.add_file("LEGAL", read(&args.legal_in)?);
+    if let Some(rewrite_in) = args.rewrite_in.as_deref() {
+        bundle.add_file(REWRITE_FILE, read(rewrite_in)?);
+    }
+    for path in &args.sidecar_in {
+        bundle.add_file(file_name(path), read(path)?);
+    }
+    bundle.write_to_path(&args.out)?;
+    Ok(bundle)
+}
+
+/// What a `build` run produced, in the keys the release workflow consumes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildOutputs {
+    pub release_tag: String,
+    pub release_title: String,
+    pub asset_path: PathBuf,
+    pub metadata: BundleMetadata,
+    /// The bundled `stats.json`.
+    pub stats: ConversionStats,
+}
+
+impl BuildOutputs {
+    /// Writes `key=value` lines, suitable for `$GITHUB_OUTPUT`.
+    pub fn write_env_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let metadata = &self.metadata;
+        let mut file = File::create(path)?;
+        writeln!(file, "release_tag={}", self.release_tag)?;
+        writeln!(file, "release_title={}", self.release_title)?;
+        writeln!(file, "asset_path={}", self.asset_path.display())?;
+        writeln!(file, "dict_version={}", metadata.sudachidict_dict_version)?;
+        writeln!(file, "edition={}", metadata.edition)?;
+        writeln!(
+            file,
+            "sudachidict_release_tag={}",
+            metadata.sudachidict_release_tag
+        )?;
+        writeln!(file, "sudachi_version={}", metadata.sudachi_version)?;
+        writeln!(file, "vibrato_ref={}", metadata.vibrato_ref)?;
+        writeln!(file, "compat_target={}", metadata.compat_target)?;
+        writeln!(file, "compat_mode={}", metadata.compat_mode)?;
+        writeln!(file, "feature_schema={}", metadata.feature_schema)?;
+        writeln!(file, "rules_profile={}", metadata.rules_profile)?;
+        writeln!(
+            file,
+            "rewrite_def_included={}",
+            metadata.rewrite_def_included
+        )?;
+        writeln!(file, "normalized_pos_rows={}", metadata.normalized_pos_rows)?;
+        writeln!(file, "fallback_ctype_rows={}", metadata.fallback_ctype_rows)?;
+        writeln!(file, "fallback_cform_rows={}", metadata.fallback_cform_rows)?;
+        writeln!(file, "built_at_utc={}", metadata.built_at_utc)?;
+        Ok(())
+    }
+
+    /// Markdown release notes: the metadata summary, then the stats breakdowns.
+    pub fn release_notes(&self) -> String {
+        let metadata = &self.metadata;
+        let stats = &self.stats;
+        let mut notes = String::new();
+        let mut line = |text: String| {
+            notes.push_str(&text);
+            notes.push('\n');
+        };
+
+        for (key, value) in [
+            ("dict_version", metadata.sudachidict_dict_version.as_str()),
+            ("edition", &metadata.edition),
+            ("sudachidict_release_tag", &metadata.sudachidict_release_tag),
+            ("sudachi_version", &metadata.sudachi_version),
+            ("vibrato_ref", &metadata.vibrato_ref),
+            ("compat_target", &metadata.compat_target),
+            ("compat_mode", &metadata.compat_mode),
+            ("feature_schema", &metadata.feature_schema),
+            ("rules_profile", &metadata.rules_profile),
+            ("built_at_utc", &metadata.built_at_utc),
+        ] {
+            line(format!("- {}: {}", key, value));
+        }
+        line(format!(
+            "- rewrite_def_included: {}",
+            metadata.rewrite_def_included
+        ));
+
+        line(String::new());
+        line("### Conversion".to_string());
+        line(String::new());
+        for (key, value) in [
+            ("lex_rows_written", stats.written),
+            ("lex_appended_rows", stats.lex_appended_rows),
+            ("normalized_pos_rows", stats.normalized_pos_rows),
+            ("fallback_ctype_rows", stats.fallback_ctype_rows),
+            ("fallback_cform_rows", stats.fallback_cform_rows),
+            ("fallback_pos_rows", stats.fallback_pos_rows),
+            ("unresolved_base_refs", stats.unresolved_base_refs),
+            ("split_only_rows", stats.split_only_rows),
+            ("unresolved_split_rows", stats.unresolved_split_rows),
+            ("invalid_synonym_group_ids", stats.invalid_synonym_group_ids),
+            ("unk_rows", stats.unk_rows),
+            ("unk_appended_rows", stats.unk_appended_rows),
+            ("char_dropped_ranges", stats.char_dropped_ranges),
+            ("char_nooovbow_ranges", stats.char_nooovbow_ranges),
+            ("unk_nooovbow_rows", stats.unk_nooovbow_rows),
+            ("char_appended_lines", stats.char_appended_lines),
+        ] {
+            line(format!("- {}: {}", key, value));
+        }
+        for (reason, rows) in &stats.skipped_rows {
+            line(format!("- skipped_rows ({}): {}", reason, rows));
+        }
+        for (file, rows) in &stats.rejected_rows {
+            line(format!("- rejected_rows ({}): {}", file, rows));
+        }
+
+        let report = stats.fallback_report();
+        for kind in ["pos", "ctype", "cform"] {
+            let mut entries = report.iter().filter(|entry| entry.kind == kind).peekable();
+            if entries.peek().is_none() {
+                continue;
+            }
+            line(String::new());
+            line(format!("### Unmapped {} values", kind));
+            line(String::new());
+            line("| value | rows | examples |".to_string());
+            line("| --- | ---: | --- |".to_string());
+            for entry in entries.take(RELEASE_NOTES_FALLBACK_VALUES) {
+                line(format!(
+                    "| `{}` | {} | {} |",
+                    entry.value,
+                    entry.rows,
+                    entry.examples.join(", ")
+                ));
+            }
+        }
+
+        line(String::new());
+        line("### Inputs".to_string());
+        line(String::new());
+        line("| role | file | sha256 |".to_string());
+        line("| --- | --- | --- |".to_string());
+        for input in &stats.inputs {
+            line(format!(
+                "| {} | {} | `{}` |",
+                input.role, input.file_name, input.sha256
+            ));
+        }
+        notes
+    }
+}
+
+/// Most frequent fallback values listed per table in the release notes.
+const RELEASE_NOTES_FALLBACK_VALUES: usize = 20;
+
+/// Runs convert → compile → verify → package as described by a build manifest.
+pub fn run_build(args: &BuildArgs) -> Result<BuildOutputs> {
+    let manifest = BuildManifest::from_path(&args.manifest)?;
+    let inputs = &manifest.inputs;
+    let work_dir = args
+        .work_dir
+        .clone()
+        .unwrap_or_else(|| args.out_dir.join("work"));
+    fs::create_dir_all(&work_dir)
+        .with_context(|| format!("failed to create {}", work_dir.display()))?;
+    fs::create_dir_all(&args.out_dir)
+        .with_context(|| format!("failed to create {}", args.out_dir.display()))?;
+
+    let rules_dir = inputs.rules_dir.join(&manifest.build.rules_profile);
+    let rule = |name: &str| -> Result<PathBuf> {
+        let path = rules_dir.join(name);
+        if path.is_file() {
+            Ok(path)
+        } else {
+            Err(anyhow!("missing rules file: {}", path.display()))
+        }
+    };
+
+    println!("[build] convert");
+    let matrix = match &inputs.matrix {
+        Some(matrix) => matrix.clone(),
+        None => work_dir.join("matrix.def"),
+    };
+    let stats_json_out = work_dir.join(STATS_FILE);
+    let convert = ConvertArgs {
+        lex_in: inputs.lex.clone(),
+        dic_in: inputs.dic.clone(),
+        matrix_out: inputs.matrix.is_none().then(|| work_dir.join("matrix.def")),
+        lex_out: work_dir.join("lex.csv"),
+        unk_in: inputs.unk.clone(),
+        unk_out: work_dir.join("unk.def"),
+        char_in: inputs.char.clone(),
+        char_out: work_dir.join("char.def"),
+        stats_out: work_dir.join("normalization_stats.env"),
+        stats_json_out: Some(stats_json_out.clone()),
+        lex_append: vec![rule("lex.append.csv")?],
+        char_append: vec![rule("char.append.def")?],
+        unk_append: vec![rule("unk.append.def")?],
+        rewrite_in: inputs.rewrite.clone(),
+        rewrite_out: inputs
+            .rewrite
+            .as_ref()
+            .map(|_| work_dir.join("rewrite.def")),
+        rewrite_append: match inputs.rewrite {
+            Some(_) => vec![rule("rewrite.append.def")?],
+            None => Vec::new(),
+        },
+        pos_map: inputs.pos_map.clone(),
+        pos_map_report: None,
+        feature_schema: Some(manifest.build.feature_schema.clone()),
+        accent_in: inputs.accent.clone(),
+        accent_unmatched_out: None,
+        fallback_report: Some(work_dir.join("fallback_report.csv")),
+        max_fallback_rate: manifest.build.max_fallback_rate,
+        split_info_out: Some(work_dir.join(SPLIT_INFO_FILE)),
+        word_units_out: Some(work_dir.join(WORD_UNITS_FILE)),
+        normalized_forms_out: Some(work_dir.join(NORMALIZED_FORMS_FILE)),
+        word_ids_out: Some(work_dir.join(WORD_IDS_FILE)),
+        synonym_groups_out: Some(work_dir.join(SYNONYM_GROUPS_FILE)),
+        on_error: ErrorPolicy::Fail,
+        rejects_out: None,
+        nooovbow: NoOovBowPolicy::Emulate,
+    };
+    let (stats, sidecars) = run_convert(&convert)?;
+    println!(
+        "[build] lex rows: written={}, normalized_pos_rows={}, fallback_pos_rows={}, fallback_ctype_rows={}, fallback_cform_rows={}, unresolved_base_refs={}",
+        stats.written,
+        stats.normalized_pos_rows,
+        stats.fallback_pos_rows,
+        stats.fallback_ctype_rows,
+        stats.fallback_cform_rows,
+        stats.unresolved_base_refs
+    );
+
+    let synonyms_out = match &inputs.synonyms {
+        Some(synonyms) => {
+            let dictionary = SynonymDictionary::from_path(synonyms)?;
+            let missing = sidecars
+                .synonym_groups
+                .group_ids()
+                .into_iter()
+                .filter(|group_id| dictionary.group(*group_id).is_empty())
+                .count();
+            println!(
+                "[build] synonyms: groups={}, lexicon groups missing from {}={}",
+                dictionary.len(),
+                SYNONYMS_FILE,
+                missing
+            );
+            let synonyms_out = work_dir.join(SYNONYMS_FILE);
+            fs::copy(synonyms, &synonyms_out)
+                .with_context(|| format!("failed to copy {}", synonyms.display()))?;
+            Some(synonyms_out)
+        }
+        None => None,
+    };
+
+    println!("[build] lint char.def/unk.def");
+    let report = lint_paths(
+        slice::from_ref(&convert.char_out),
+        slice::from_ref(&convert.unk_out),
+    )?;
+    print_lint_report(&report, "[build] lint:")?;
+
+    println!("[build] compile and verify");
+    let compile = CompileArgs {
+        lex_in: convert.lex_out.clone(),
+        matrix_in: matrix,
+        char_in: convert.char_out.clone(),
+        unk_in: convert.unk_out.clone(),
+        out: work_dir.join(DICTIONARY_FILE),
+        smoke_text: manifest.build.smoke_text.clone(),
+    };
+    for (text, surfaces) in compile.smoke_text.iter().zip(run_compile(&compile)?) {
+        println!("[build] smoke: {} -> {}", text, surfaces.join("|"));
+    }
+
+    println!("[build] package");
+    let asset_path = args.out_dir.join(manifest.asset_name());
+    let package = PackageArgs {
+        dict_in: compile.out.clone(),
+        stats_in: stats_json_out,
+        license_in: inputs.license.clone(),
+        legal_in: inputs.legal.clone(),
+        rewrite_in: convert.rewrite_out.clone(),
+        sidecar_in: [
+            &convert.split_info_out,
+            &convert.word_units_out,
+            &convert.normalized_forms_out,
+            &convert.word_ids_out,
+            &convert.synonym_groups_out,
+            &synonyms_out,
+        ]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect(),
+        out: asset_path.clone(),
+        sudachidict_repo: manifest.source.sudachidict_repo.clone(),
+        sudachidict_release_tag: manifest.source.sudachidict_release_tag.clone(),
+        dict_version: manifest.source.dict_version.clone(),
+        edition: manifest.build.edition.clone(),
+        sudachi_repo: manifest.source.sudachi_repo.clone(),
+        sudachi_version: manifest.source.sudachi_version.clone(),
+        sudachi_tag: manifest.source.sudachi_tag.clone(),
+        vibrato_ref: manifest.build.vibrato_ref.clone(),
+        compat_target: manifest.build.compat_target.clone(),
+        compat_mode: manifest.build.compat_mode.clone(),
+        rules_profile: manifest.build.rules_profile.clone(),
+        built_at_utc: match &args.built_at_utc {
+            Some(built_at_utc) => built_at_utc.clone(),
+            None => built_at_utc()?,
+        },
+    };
+    run_package(&package, Some(&manifest))?;
+    let bundle = Bundle::from_path(&asset_path)?;
+    let stats = bundle
+        .stats()?
+        .ok_or_else(|| anyhow!("bundle {} has no {}", bundle.root, STATS_FILE))?;
+
+    Ok(BuildOutputs {
+        release_tag: manifest.release_tag(),
+        release_title: manifest.release_title(),
+        asset_path,
+        metadata: bundle.metadata,
+        stats,
+    })
+}
+
+/// Defaults to mecab9, or jpreprocess12 when accent sources are given.
+pub fn convert_options(
+    feature_schema: Option<&str>,
+    accent_in: &[PathBuf],
+    collect_unmatched_accent: bool,
+    pos_map: Option<&Path>,
+) -> Result<ConvertOptions> {
+    let default_schema = if accent_in.is_empty() {
+        "mecab9"
+    } else {
+        "jpreprocess12"
+    };
+    let mut options = ConvertOptions {
+        feature_schema: feature_schema_by_name(feature_schema.unwrap_or(default_schema))?,
+        accent: AccentDictionary::from_paths(accent_in)?,
+        collect_unmatched_accent,
+        ..ConvertOptions::default()
+    };
+    if !accent_in.is_empty() && options.feature_schema.name() != Jpreprocess12.name() {
+        return Err(anyhow!(
+            "--accent-in requires --feature-schema jpreprocess12, got {}",
+            options.feature_schema.name()
+        ));
+    }
+    if let Some(pos_map) = pos_map {
+        options.pos_mapping = PosMapping::from_path(pos_map)?;
+    }
+    Ok(options)
+}
+
+/// `SOURCE_DATE_EPOCH` when set, otherwise the current time.
+fn built_at_utc() -> Result<String> {
+    let seconds = match env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) => value
+            .trim()
+            .parse::<u64>()
+            .with_context(|| format!("failed to parse SOURCE_DATE_EPOCH='{}'", value))?,
+        Err(_) => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+    };
+    Ok(format_utc(seconds))
+}
+
+/// Formats Unix seconds as `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn format_utc(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let time = seconds % 86_400;
+
+    // Civil-from-days (proleptic Gregorian), counting eras of 400 years from 0000-03-01.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
+
+fn read(path: &Path) -> Result<Vec<u8>> {
+    fs::read(path).with_context(|| format!("failed to read {}", path.display()))
+}
+
+fn read_lexicon_sources(paths: &[PathBuf]) -> Result<Vec<LexiconSource>> {
+    paths.iter().map(LexiconSource::from_path).collect()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
@@ -0,0 +1,150 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+
+use crate::accent::{mora_count, AccentEntry};
+
+/// Features of one lexicon or unknown-word row, before they are laid out.
+///
+/// `pos`/`ctype`/`cform` are the IPADIC values; `source_*` keep what Sudachi
+/// had so that UniDic-style schemas can emit them unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeatureRow {
+    pub surface: String,
+    pub source_pos: [String; 4],
+    pub source_ctype: String,
+    pub source_cform: String,
+    pub pos: [String; 4],
+    pub ctype: String,
+    pub cform: String,
+    pub base: String,
+    pub read: String,
+    pub pron: String,
+    /// Joined from an accent source; only laid out by schemas with accent columns.
+    pub accent: Option<AccentEntry>,
+}
+
+/// Layout of the feature columns that follow `surface,left_id,right_id,cost`.
+pub trait FeatureSchema: Debug + Send + Sync {
+    /// Versioned name recorded in stats and bundle metadata (e.g. `mecab9-v1`).
+    fn name(&self) -> &'static str;
+
+    fn column_names(&self) -> &'static [&'static str];
+
+    fn features(&self, row: &FeatureRow) -> Vec<String>;
+}
+
+/// `pos1..4,ctype,cform,base,read,pron`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mecab9;
+
+impl FeatureSchema for Mecab9 {
+    fn name(&self) -> &'static str {
+        "mecab9-v1"
+    }
+
+    fn column_names(&self) -> &'static [&'static str] {
+        &[
+            "pos1", "pos2", "pos3", "pos4", "ctype", "cform", "base", "read", "pron",
+        ]
+    }
+
+    fn features(&self, row: &FeatureRow) -> Vec<String> {
+        let mut features = row.pos.to_vec();
+        features.extend([
+            row.ctype.clone(),
+            row.cform.clone(),
+            row.base.clone(),
+            row.read.clone(),
+            row.pron.clone(),
+        ]);
+        features
+    }
+}
+
+/// `mecab9` followed by jpreprocess' `acc/mora,chain_rule,chain_flag`.
+///
+/// Rows without a joined accent (or whose `pron` is not countable) get `*/*,*,*`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Jpreprocess12;
+
+impl FeatureSchema for Jpreprocess12 {
+    fn name(&self) -> &'static str {
+        "jpreprocess12-v1"
+    }
+
+    fn column_names(&self) -> &'static [&'static str] {
+        &[
+            "pos1",
+            "pos2",
+            "pos3",
+            "pos4",
+            "ctype",
+            "cform",
+            "base",
+            "read",
+            "pron",
+            "acc/mora",
+            "chain_rule",
+            "chain_flag",
+        ]
+    }
+
+    fn features(&self, row: &FeatureRow) -> Vec<String> {
+        let mut features = Mecab9.features(row);
+        match (&row.accent, mora_count(&row.pron)) {
+            (Some(accent), Some(mora)) => features.extend([
+                format!("{}/{}", accent.accent, mora),
+                accent.chain_rule.clone(),
+                accent.chain_flag.clone(),
+            ]),
+            _ => features.extend(["*/*".to_string(), "*".to_string(), "*".to_string()]),
+        }
+        features
+    }
+}
+
+/// The first ten UniDic columns with Sudachi's own POS and conjugation labels.
+///
+/// `lForm` is the row reading; Sudachi does not carry the lemma reading.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Unidic;
+
+impl FeatureSchema for Unidic {
+    fn name(&self) -> &'static str {
+        "unidic-v1"
+    }
+
+    fn column_names(&self) -> &'static [&'static str] {
+        &[
+            "pos1", "pos2", "pos3", "pos4", "cType", "cForm", "lForm", "lemma", "orth", "pron",
+        ]
+    }
+
+    fn features(&self, row: &FeatureRow) -> Vec<String> {
+        let mut features = row.source_pos.to_vec();
+        features.extend([
+            row.source_ctype.clone(),
+            row.source_cform.clone(),
+            row.read.clone(),
+            row.base.clone(),
+            row.surface.clone(),
+            row.pron.clone(),
+        ]);
+        features
+    }
+}
+
+/// Resolves a `--feature-schema` value; the `-v1` suffix is optional.
+pub fn feature_schema_by_name(name: &str) -> Result<Arc<dyn FeatureSchema>> {
+    match name.trim() {
+        "mecab9" | "mecab9-v1" => Ok(Arc::new(Mecab9)),
+        "jpreprocess12" | "jpreprocess12-v1" => Ok(Arc::new(Jpreprocess12)),
+        "unidic" | "unidic-v1" => Ok(Arc::new(Unidic)),
+        other => Err(anyhow!(
+            "unknown feature schema '{}': expected mecab9, jpreprocess12 or unidic",
+            other
+        )),
+    }
+}
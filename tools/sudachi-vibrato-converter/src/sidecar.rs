@@ -0,0 +1,133 @@
+use anyhow::{anyhow, bail, Result};
+
+/// Starts a binary sidecar: four magic bytes, then the format version.
+pub(crate) fn write_header(out: &mut Vec<u8>, magic: &[u8; 4], version: u32) {
+    out.extend_from_slice(magic);
+    put_u32(out, version);
+}
+
+pub(crate) fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Converts a length or index to the `u32` the sidecars store.
+pub(crate) fn to_u32(value: usize, what: &str) -> Result<u32> {
+    u32::try_from(value).map_err(|_| anyhow!("{} {} does not fit in u32", what, value))
+}
+
+/// Little-endian reader over a sidecar file.
+pub(crate) struct SidecarReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SidecarReader<'a> {
+    /// Checks the magic and version written by [`write_header`].
+    pub(crate) fn new(bytes: &'a [u8], magic: &[u8; 4], version: u32) -> Result<Self> {
+        let mut reader = Self { bytes, pos: 0 };
+        let found = reader.take(magic.len())?;
+        if found != magic {
+            bail!(
+                "bad magic {:?}, expected {:?}",
+                String::from_utf8_lossy(found),
+                String::from_utf8_lossy(magic)
+            );
+        }
+        let found = reader.u32()?;
+        if found != version {
+            bail!("unsupported version {}, expected {}", found, version);
+        }
+        Ok(reader)
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let slice = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| anyhow!("unexpected end of data at offset {}", self.pos))?;
+        self.pos += len;
+        Ok(slice)
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    /// Fails when bytes are left after the last field.
+    pub(crate) fn finish(self) -> Result<()> {
+        if self.pos == self.bytes.len() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{} trailing bytes at offset {}",
+                self.bytes.len() - self.pos,
+                self.pos
+            ))
+        }
+    }
+}
+
+/// Strings stored back to back, addressed by index.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct StringTable {
+    /// End of each string in `text`.
+    ends: Vec<u32>,
+    text: String,
+}
+
+impl StringTable {
+    pub(crate) fn push(&mut self, value: &str) {
+        self.text.push_str(value);
+        self.ends.push(self.text.len() as u32);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Option<&str> {
+        let end = *self.ends.get(index)? as usize;
+        let start = index
+            .checked_sub(1)
+            .map_or(0, |prev| self.ends[prev] as usize);
+        Some(&self.text[start..end])
+    }
+
+    /// String count, the ends, then the UTF-8 text with its length.
+    pub(crate) fn write(&self, out: &mut Vec<u8>) -> Result<()> {
+        put_u32(out, to_u32(self.ends.len(), "string count")?);
+        for end in &self.ends {
+            put_u32(out, *end);
+        }
+        put_u32(out, to_u32(self.text.len(), "string table size")?);
+        out.extend_from_slice(self.text.as_bytes());
+        Ok(())
+    }
+
+    pub(crate) fn read(reader: &mut SidecarReader<'_>) -> Result<Self> {
+        let count = reader.u32()?;
+        let ends: Vec<u32> = (0..count).map(|_| reader.u32()).collect::<Result<_>>()?;
+        let len = reader.u32()? as usize;
+        let text = std::str::from_utf8(reader.take(len)?)
+            .map_err(|err| anyhow!("invalid UTF-8 in string table: {}", err))?
+            .to_string();
+
+        let mut start = 0;
+        for (index, end) in ends.iter().enumerate() {
+            let end = *end as usize;
+            if end < start || end > len || !text.is_char_boundary(end) {
+                bail!("invalid end of string {}", index);
+            }
+            start = end;
+        }
+        Ok(Self { ends, text })
+    }
+}
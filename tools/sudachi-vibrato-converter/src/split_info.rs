@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+
+use crate::normalize::normalize_text_or_star;
+use crate::options::ConvertOptions;
+use crate::schema::FeatureRow;
+
+/// Leading columns of `split_info.csv`; the converted features follow.
+const HEADER: [&str; 10] = [
+    "word_id",
+    "surface",
+    "pos1",
+    "pos2",
+    "pos3",
+    "pos4",
+    "ctype",
+    "cform",
+    "reading",
+    "normalized_form",
+];
+
+/// A Sudachi row with `-1` connection ids: not a lexicon entry, but referenced
+/// by the A/B split columns of other rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitOnlyEntry {
+    pub word_id: u32,
+    pub surface: String,
+    /// Sudachi POS (`col5..col8`).
+    pub pos: [String; 4],
+    pub ctype: String,
+    pub cform: String,
+    pub reading: String,
+    pub normalized_form: String,
+    /// Feature columns the row would have had in the lexicon.
+    pub features: Vec<String>,
+}
+
+impl SplitOnlyEntry {
+    pub(crate) fn new(
+        word_id: usize,
+        record: &StringRecord,
+        features: &FeatureRow,
+        options: &ConvertOptions,
+    ) -> Self {
+        let field = |index: usize| normalize_text_or_star(record.get(index).unwrap_or(""));
+        Self {
+            word_id: word_id as u32,
+            surface: features.surface.clone(),
+            pos: features.source_pos.clone(),
+            ctype: features.source_ctype.clone(),
+            cform: features.source_cform.clone(),
+            reading: field(11),
+            normalized_form: field(12),
+            features: options.feature_schema.features(features),
+        }
+    }
+
+    /// The features joined as they would appear in a Vibrato token.
+    pub fn feature(&self) -> String {
+        let mut writer = WriterBuilder::new()
+            .has_headers(false)
+            .terminator(csv::Terminator::Any(b'\n'))
+            .from_writer(Vec::new());
+        // Writing to a Vec cannot fail.
+        writer.write_record(&self.features).unwrap();
+        let mut feature = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        feature.pop();
+        feature
+    }
+}
+
+/// Split-only entries keyed by Sudachi word id, stored as `split_info.csv`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SplitInfo {
+    entries: BTreeMap<u32, SplitOnlyEntry>,
+}
+
+impl SplitInfo {
+    pub fn insert(&mut self, entry: SplitOnlyEntry) {
+        self.entries.insert(entry.word_id, entry);
+    }
+
+    pub fn get(&self, word_id: u32) -> Option<&SplitOnlyEntry> {
+        self.entries.get(&word_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries in word id order.
+    pub fn iter(&self) -> impl Iterator<Item = &SplitOnlyEntry> {
+        self.entries.values()
+    }
+
+    pub fn write_csv<W: Write>(&self, output: W) -> Result<()> {
+        let mut writer = WriterBuilder::new().flexible(true).from_writer(output);
+        writer.write_record(HEADER)?;
+        for entry in self.iter() {
+            let word_id = entry.word_id.to_string();
+            let mut row = vec![word_id.as_str(), &entry.surface];
+            row.extend(entry.pos.iter().map(String::as_str));
+            row.extend([
+                entry.ctype.as_str(),
+                &entry.cform,
+                &entry.reading,
+                &entry.normalized_form,
+            ]);
+            row.extend(entry.features.iter().map(String::as_str));
+            writer.write_record(&row)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let file =
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        self.write_csv(file)
+    }
+
+    pub fn from_reader<R: Read>(input: R) -> Result<Self> {
+        let mut reader = ReaderBuilder::new().flexible(true).from_reader(input);
+        let mut info = Self::default();
+        for (index, record) in reader.records().enumerate() {
+            // Line 1 is the header.
+            let line_no = index + 2;
+            let record =
+                record.with_context(|| format!("failed to read split info line {}", line_no))?;
+            if record.len() < HEADER.len() {
+                return Err(anyhow!(
+                    "invalid split info row at line {}: expected >={} columns, got {}",
+                    line_no,
+                    HEADER.len(),
+                    record.len()
+                ));
+            }
+            let field = |index: usize| record.get(index).unwrap_or("").to_string();
+            let word_id = record.get(0).unwrap_or("").trim();
+            info.insert(SplitOnlyEntry {
+                word_id: word_id.parse().with_context(|| {
+                    format!("failed to parse word_id='{}' at line {}", word_id, line_no)
+                })?,
+                surface: field(1),
+                pos: std::array::from_fn(|i| field(2 + i)),
+                ctype: field(6),
+                cform: field(7),
+                reading: field(8),
+                normalized_form: field(9),
+                features: record
+                    .iter()
+                    .skip(HEADER.len())
+                    .map(str::to_string)
+                    .collect(),
+            });
+        }
+        Ok(info)
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        Self::from_reader(file).with_context(|| format!("invalid split info {}", path.display()))
+    }
+}
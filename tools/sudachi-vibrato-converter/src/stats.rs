@@ -1,30 +1,370 @@
-use std::fs::File;
-use std::io::Write;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
 use std::path::Path;
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Error, Result};
+use csv::{StringRecord, WriterBuilder};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+use crate::bundle::sha256_file;
+use crate::options::ErrorPolicy;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ConversionStats {
+    /// Name of the feature schema the lexicon was written with.
+    pub feature_schema: String,
     pub written: usize,
+    /// Rows with a negative connection id other than the split-only `-1,-1`.
     pub skipped_negative_conn_ids: usize,
+    /// Rows with `-1,-1` connection ids, only referenced from A/B splits.
+    pub split_only_rows: usize,
+    /// Non-numeric synonym group ids (`col18`) left out of `synonym_groups.bin`.
+    pub invalid_synonym_group_ids: usize,
+    /// Written rows whose A or B split could not be resolved and was left out of `word_units.bin`.
+    pub unresolved_split_rows: usize,
+    /// Skipped lexicon rows keyed by reason.
+    pub skipped_rows: BTreeMap<String, usize>,
     pub normalized_pos_rows: usize,
     pub fallback_ctype_rows: usize,
     pub fallback_cform_rows: usize,
+    /// Rows whose source POS matched no mapping rule, or only a catch-all one.
+    pub fallback_pos_rows: usize,
+    /// Raw conjugation types that fell back to `*`.
+    pub fallback_ctype_values: BTreeMap<String, FallbackHit>,
+    /// Raw conjugation forms that fell back to `*`.
+    pub fallback_cform_values: BTreeMap<String, FallbackHit>,
+    /// Comma-joined source POS tuples counted in `fallback_pos_rows`.
+    pub fallback_pos_values: BTreeMap<String, FallbackHit>,
+    /// Rows whose dictionary-form word id did not point at a known row.
+    pub unresolved_base_refs: usize,
+    /// User dictionary rows whose connection ids were taken from their POS.
+    pub resolved_connection_id_rows: usize,
+    /// Lexicon rows an accent was joined onto.
+    pub accent_matched_rows: usize,
+    /// Lexicon rows without an accent source entry, or whose `pron` has no mora
+    /// count (only counted when a source is given).
+    pub accent_unmatched_rows: usize,
+    /// Keyed by the comma-joined source POS tuple.
+    pub pos_mapping: BTreeMap<String, PosMappingHit>,
+    /// Rows appended from `--lex-append` files.
+    pub lex_appended_rows: usize,
+    pub unk_rows: usize,
+    /// Rows appended from `--unk-append` files.
+    pub unk_appended_rows: usize,
+    /// char.def range lines dropped because NOOOVBOW was their only category.
+    pub char_dropped_ranges: usize,
+    /// char.def range lines flagged NOOOVBOW.
+    pub char_nooovbow_ranges: usize,
+    /// unk.def rows added for `NOOOVBOW_<BASE>` categories.
+    pub unk_nooovbow_rows: usize,
+    /// Category and range lines merged from `--char-append` files.
+    pub char_appended_lines: usize,
+    /// Every input file of the run, in the order it was read.
+    pub inputs: Vec<InputDigest>,
+    /// Malformed lexicon and unk rows dropped under `--on-error=skip|collect`, keyed by file.
+    pub rejected_rows: BTreeMap<String, usize>,
+    /// The dropped rows themselves, under `--on-error=collect`.
+    #[serde(skip)]
+    pub rejects: Vec<RejectedRow>,
+}
+
+/// A malformed row dropped instead of aborting the run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedRow {
+    pub file: String,
+    pub line: usize,
+    pub reason: String,
+    /// The row's fields joined with `,`.
+    pub raw: String,
+}
+
+/// Which POS mapping rule a distinct source tuple resolved to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PosMappingHit {
+    pub source: [String; 4],
+    pub target: [String; 4],
+    /// Table line of the winning rule; `None` when no rule matched.
+    pub rule_line: Option<usize>,
+    pub rows: usize,
+}
+
+/// Surfaces kept per fallback value.
+pub const FALLBACK_EXAMPLES: usize = 3;
+
+/// Rows a distinct unmapped value occurred on.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FallbackHit {
+    pub rows: usize,
+    /// First distinct surfaces, at most [`FALLBACK_EXAMPLES`].
+    pub examples: Vec<String>,
+}
+
+impl FallbackHit {
+    pub fn record(&mut self, surface: &str) {
+        self.rows += 1;
+        if self.examples.len() < FALLBACK_EXAMPLES && !self.examples.iter().any(|s| s == surface) {
+            self.examples.push(surface.to_string());
+        }
+    }
+}
+
+/// One line of the fallback report.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FallbackEntry {
+    /// `pos`, `ctype` or `cform`.
+    pub kind: &'static str,
+    pub value: String,
+    pub rows: usize,
+    /// `rows` over the lexicon rows mapped, written plus split-only.
+    pub rate: f64,
+    pub examples: Vec<String>,
+}
+
+/// SHA-256 of one input file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputDigest {
+    /// What the file was given as, e.g. `lex` or `unk_append`.
+    pub role: String,
+    pub file_name: String,
+    pub sha256: String,
 }
 
 impl ConversionStats {
+    pub(crate) fn record_skipped(&mut self, reason: &str) {
+        *self.skipped_rows.entry(reason.to_string()).or_default() += 1;
+    }
+
+    /// Applies `policy` to a malformed row: returns `error` under `Fail`, records the row otherwise.
+    pub(crate) fn reject(
+        &mut self,
+        policy: ErrorPolicy,
+        file: &str,
+        line: usize,
+        record: &StringRecord,
+        error: Error,
+    ) -> Result<()> {
+        if policy == ErrorPolicy::Fail {
+            return Err(error);
+        }
+        *self.rejected_rows.entry(file.to_string()).or_default() += 1;
+        if policy == ErrorPolicy::Collect {
+            self.rejects.push(RejectedRow {
+                file: file.to_string(),
+                line,
+                reason: error.to_string(),
+                raw: record.iter().collect::<Vec<_>>().join(","),
+            });
+        }
+        Ok(())
+    }
+
+    /// Hashes `path` and appends it to [`Self::inputs`].
+    pub fn record_input<P: AsRef<Path>>(&mut self, role: &str, path: P) -> Result<()> {
+        let path = path.as_ref();
+        self.inputs.push(InputDigest {
+            role: role.to_string(),
+            file_name: path.file_name().map_or_else(
+                || path.display().to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+            sha256: sha256_file(path)?,
+        });
+        Ok(())
+    }
+
+    /// Writes the full report, including the breakdowns the env file leaves out.
+    pub fn write_json_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let file =
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a report written by [`Self::write_json_file`].
+    pub fn read_json_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content =
+            fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_slice(&content)
+            .with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    /// Every distinct unmapped value, grouped by kind and most frequent first.
+    pub fn fallback_report(&self) -> Vec<FallbackEntry> {
+        let mut entries = Vec::new();
+        for (kind, values) in self.fallback_values() {
+            let start = entries.len();
+            entries.extend(values.iter().map(|(value, hit)| FallbackEntry {
+                kind,
+                value: value.clone(),
+                rows: hit.rows,
+                rate: self.fallback_rate(hit.rows),
+                examples: hit.examples.clone(),
+            }));
+            entries[start..].sort_by(|a, b| b.rows.cmp(&a.rows).then(a.value.cmp(&b.value)));
+        }
+        entries
+    }
+
+    /// Writes [`Self::fallback_report`] as JSON when `path` ends with `.json`, CSV otherwise.
+    ///
+    /// CSV examples are joined with `|`.
+    pub fn write_fallback_report<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let entries = self.fallback_report();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            let file = File::create(path)
+                .with_context(|| format!("failed to create {}", path.display()))?;
+            let mut writer = BufWriter::new(file);
+            serde_json::to_writer_pretty(&mut writer, &entries)?;
+            writeln!(writer)?;
+            writer.flush()?;
+            return Ok(());
+        }
+
+        let mut writer = WriterBuilder::new().from_path(path)?;
+        writer.write_record(["kind", "value", "rows", "rate", "examples"])?;
+        for entry in &entries {
+            writer.write_record([
+                entry.kind,
+                &entry.value,
+                &entry.rows.to_string(),
+                &format!("{:.6}", entry.rate),
+                &entry.examples.join("|"),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Fails when the POS, ctype or cform fallback rows exceed `max_rate` of the mapped rows.
+    pub fn check_fallback_rate(&self, max_rate: f64) -> Result<()> {
+        let exceeded: Vec<String> = [
+            ("pos", self.fallback_pos_rows),
+            ("ctype", self.fallback_ctype_rows),
+            ("cform", self.fallback_cform_rows),
+        ]
+        .into_iter()
+        .filter(|(_, rows)| self.fallback_rate(*rows) > max_rate)
+        .map(|(kind, rows)| {
+            format!(
+                "{} {}/{} ({:.6})",
+                kind,
+                rows,
+                self.mapped_rows(),
+                self.fallback_rate(rows)
+            )
+        })
+        .collect();
+        if exceeded.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "fallback rate exceeds --max-fallback-rate {}: {}",
+                max_rate,
+                exceeded.join(", ")
+            ))
+        }
+    }
+
+    fn fallback_values(&self) -> [(&'static str, &BTreeMap<String, FallbackHit>); 3] {
+        [
+            ("pos", &self.fallback_pos_values),
+            ("ctype", &self.fallback_ctype_values),
+            ("cform", &self.fallback_cform_values),
+        ]
+    }
+
+    /// Rows whose features were mapped: the written ones and the split-only ones.
+    fn mapped_rows(&self) -> usize {
+        self.written + self.split_only_rows
+    }
+
+    fn fallback_rate(&self, rows: usize) -> f64 {
+        if self.mapped_rows() == 0 {
+            0.0
+        } else {
+            rows as f64 / self.mapped_rows() as f64
+        }
+    }
+
     pub fn write_env_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let mut file = File::create(path)?;
+        writeln!(file, "feature_schema={}", self.feature_schema)?;
         writeln!(file, "written={}", self.written)?;
         writeln!(
             file,
             "skipped_negative_conn_ids={}",
             self.skipped_negative_conn_ids
         )?;
+        writeln!(file, "split_only_rows={}", self.split_only_rows)?;
         writeln!(file, "normalized_pos_rows={}", self.normalized_pos_rows)?;
         writeln!(file, "fallback_ctype_rows={}", self.fallback_ctype_rows)?;
         writeln!(file, "fallback_cform_rows={}", self.fallback_cform_rows)?;
+        writeln!(file, "fallback_pos_rows={}", self.fallback_pos_rows)?;
+        writeln!(file, "unresolved_base_refs={}", self.unresolved_base_refs)?;
+        writeln!(
+            file,
+            "resolved_connection_id_rows={}",
+            self.resolved_connection_id_rows
+        )?;
+        writeln!(file, "accent_matched_rows={}", self.accent_matched_rows)?;
+        writeln!(file, "accent_unmatched_rows={}", self.accent_unmatched_rows)?;
+        writeln!(
+            file,
+            "rejected_rows={}",
+            self.rejected_rows.values().sum::<usize>()
+        )?;
+        Ok(())
+    }
+
+    pub fn write_pos_mapping_report<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = WriterBuilder::new().from_path(path)?;
+        writer.write_record([
+            "src_pos1",
+            "src_pos2",
+            "src_pos3",
+            "src_pos4",
+            "dst_pos1",
+            "dst_pos2",
+            "dst_pos3",
+            "dst_pos4",
+            "rule_line",
+            "rows",
+        ])?;
+        for hit in self.pos_mapping.values() {
+            let rule_line = hit
+                .rule_line
+                .map_or_else(|| "*".to_string(), |line| line.to_string());
+            let mut row: Vec<&str> = hit.source.iter().map(String::as_str).collect();
+            row.extend(hit.target.iter().map(String::as_str));
+            row.push(&rule_line);
+            let rows = hit.rows.to_string();
+            row.push(&rows);
+            writer.write_record(&row)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn write_rejects_report<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = WriterBuilder::new().from_path(path)?;
+        writer.write_record(["file", "line", "reason", "raw"])?;
+        for reject in &self.rejects {
+            writer.write_record([
+                reject.file.as_str(),
+                &reject.line.to_string(),
+                &reject.reason,
+                &reject.raw,
+            ])?;
+        }
+        writer.flush()?;
         Ok(())
     }
 }
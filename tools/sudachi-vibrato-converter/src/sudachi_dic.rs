@@ -0,0 +1,651 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use csv::WriterBuilder;
+
+use crate::lexicon::LexiconSource;
+
+pub const SYSTEM_DICT_VERSION_1: u64 = 0x7366_d3f1_8bd1_11e7;
+pub const SYSTEM_DICT_VERSION_2: u64 = 0xce9f_011a_9239_4434;
+
+const DESCRIPTION_SIZE: usize = 256;
+const HEADER_SIZE: usize = 8 + 8 + DESCRIPTION_SIZE;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DictionaryHeader {
+    pub version: u64,
+    pub create_time: u64,
+    pub description: String,
+}
+
+impl DictionaryHeader {
+    /// Only system dictionary v2 stores synonym group ids in word infos.
+    pub fn has_synonym_group_ids(&self) -> bool {
+        self.version == SYSTEM_DICT_VERSION_2
+    }
+}
+
+/// POS table and connection matrix.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Grammar {
+    /// `pos1..4,ctype,cform` indexed by POS id.
+    pub pos: Vec<[String; 6]>,
+    pub left_size: usize,
+    pub right_size: usize,
+    /// Costs indexed by `left + left_size * right`, as Sudachi stores them.
+    pub matrix: Vec<i16>,
+}
+
+impl Grammar {
+    pub fn cost(&self, left: usize, right: usize) -> i16 {
+        self.matrix[left + self.left_size * right]
+    }
+
+    /// Writes the matrix in MeCab `matrix.def` format.
+    pub fn write_matrix_def<W: Write>(&self, mut output: W) -> Result<()> {
+        writeln!(output, "{} {}", self.left_size, self.right_size)?;
+        for left in 0..self.left_size {
+            for right in 0..self.right_size {
+                writeln!(output, "{} {} {}", left, right, self.cost(left, right))?;
+            }
+        }
+        output.flush()?;
+        Ok(())
+    }
+}
+
+/// One lexicon entry; its index in [`SudachiDictionary::words`] is its word id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SudachiWord {
+    /// Trie key (raw CSV `col0`).
+    pub surface: String,
+    pub left_id: i16,
+    pub right_id: i16,
+    pub cost: i16,
+    /// Display headword (raw CSV `col4`).
+    pub headword: String,
+    pub pos_id: u16,
+    pub normalized_form: String,
+    /// `-1` when the word is its own dictionary form.
+    pub dictionary_form_word_id: i32,
+    pub reading_form: String,
+    pub a_unit_split: Vec<i32>,
+    pub b_unit_split: Vec<i32>,
+    pub word_structure: Vec<i32>,
+    pub synonym_group_ids: Vec<i32>,
+}
+
+/// A Sudachi binary system dictionary (`system_*.dic`).
+///
+/// Layout: header, grammar (POS table and connection matrix), then the lexicon
+/// (darts-clone trie, word id table, word parameters, word info offsets and
+/// word infos). Strings are UTF-16LE with a one- or two-byte length prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SudachiDictionary {
+    pub header: DictionaryHeader,
+    pub grammar: Grammar,
+    pub words: Vec<SudachiWord>,
+}
+
+impl SudachiDictionary {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::from_bytes(&bytes)
+            .with_context(|| format!("invalid Sudachi dictionary {}", path.display()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut cursor = ByteCursor::new(bytes);
+
+        let version = cursor.u64()?;
+        if version != SYSTEM_DICT_VERSION_1 && version != SYSTEM_DICT_VERSION_2 {
+            bail!("unsupported dictionary version 0x{:016x}", version);
+        }
+        let create_time = cursor.u64()?;
+        let description = cursor.take(DESCRIPTION_SIZE)?;
+        let description_len = description
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(DESCRIPTION_SIZE);
+        let header = DictionaryHeader {
+            version,
+            create_time,
+            description: String::from_utf8_lossy(&description[..description_len]).into_owned(),
+        };
+
+        let grammar = read_grammar(&mut cursor)?;
+
+        let trie_size = cursor.u32()? as usize;
+        let trie: Vec<u32> = (0..trie_size)
+            .map(|_| cursor.u32())
+            .collect::<Result<_>>()?;
+        let word_id_table_size = cursor.u32()? as usize;
+        let word_id_table = cursor.take(word_id_table_size)?;
+        let word_size = cursor.u32()? as usize;
+        let params: Vec<[i16; 3]> = (0..word_size)
+            .map(|_| Ok([cursor.i16()?, cursor.i16()?, cursor.i16()?]))
+            .collect::<Result<_>>()?;
+        let offsets: Vec<usize> = (0..word_size)
+            .map(|_| cursor.u32().map(|offset| offset as usize))
+            .collect::<Result<_>>()?;
+
+        let mut words = Vec::with_capacity(word_size);
+        for (word_id, (offset, [left_id, right_id, cost])) in
+            offsets.into_iter().zip(params).enumerate()
+        {
+            let mut info = ByteCursor::at(bytes, offset);
+            let word = read_word_info(
+                &mut info,
+                header.has_synonym_group_ids(),
+                left_id,
+                right_id,
+                cost,
+            )
+            .with_context(|| format!("failed to read word info {}", word_id))?;
+            if usize::from(word.pos_id) >= grammar.pos.len() {
+                bail!("word {} has unknown pos id {}", word_id, word.pos_id);
+            }
+            words.push(word);
+        }
+
+        for (key, value) in trie_entries(&trie)? {
+            let mut table = ByteCursor::at(word_id_table, value as usize);
+            let count = table.u8()?;
+            for _ in 0..count {
+                let word_id = table.u32()? as usize;
+                let word = words
+                    .get_mut(word_id)
+                    .ok_or_else(|| anyhow!("trie points at unknown word id {}", word_id))?;
+                word.surface = String::from_utf8_lossy(&key).into_owned();
+            }
+        }
+
+        Ok(Self {
+            header,
+            grammar,
+            words,
+        })
+    }
+
+    /// Serializes the dictionary, including a darts-clone trie over the surfaces.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        out.extend(self.header.version.to_le_bytes());
+        out.extend(self.header.create_time.to_le_bytes());
+        let description = self.header.description.as_bytes();
+        if description.len() > DESCRIPTION_SIZE {
+            bail!("description is longer than {} bytes", DESCRIPTION_SIZE);
+        }
+        out.extend(description);
+        out.resize(HEADER_SIZE, 0);
+
+        write_grammar(&mut out, &self.grammar)?;
+
+        let mut keys: BTreeMap<&[u8], Vec<u32>> = BTreeMap::new();
+        for (word_id, word) in self.words.iter().enumerate() {
+            keys.entry(word.surface.as_bytes())
+                .or_default()
+                .push(word_id as u32);
+        }
+        let mut word_id_table = Vec::new();
+        let mut trie_values = Vec::with_capacity(keys.len());
+        for (key, word_ids) in &keys {
+            let count = u8::try_from(word_ids.len())
+                .map_err(|_| anyhow!("more than 255 words share one surface"))?;
+            trie_values.push((*key, word_id_table.len() as u32));
+            word_id_table.push(count);
+            for word_id in word_ids {
+                word_id_table.extend(word_id.to_le_bytes());
+            }
+        }
+        let trie = build_trie(&trie_values)?;
+
+        out.extend((trie.len() as u32).to_le_bytes());
+        for unit in &trie {
+            out.extend(unit.to_le_bytes());
+        }
+        out.extend((word_id_table.len() as u32).to_le_bytes());
+        out.extend(&word_id_table);
+        out.extend((self.words.len() as u32).to_le_bytes());
+        for word in &self.words {
+            out.extend(word.left_id.to_le_bytes());
+            out.extend(word.right_id.to_le_bytes());
+            out.extend(word.cost.to_le_bytes());
+        }
+
+        let offsets_start = out.len();
+        out.resize(offsets_start + 4 * self.words.len(), 0);
+        for (word_id, word) in self.words.iter().enumerate() {
+            let offset = u32::try_from(out.len()).map_err(|_| anyhow!("dictionary too large"))?;
+            out[offsets_start + 4 * word_id..][..4].copy_from_slice(&offset.to_le_bytes());
+            write_word_info(&mut out, word, self.header.has_synonym_group_ids())?;
+        }
+
+        Ok(out)
+    }
+
+    /// Renders the lexicon as Sudachi raw CSV (19 columns), in word id order.
+    ///
+    /// The split type (`col14`) is not stored in the binary format and is
+    /// derived from which split columns are present.
+    pub fn to_lexicon_csv(&self) -> Result<Vec<u8>> {
+        let mut writer = WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(Vec::new());
+        for word in &self.words {
+            let pos = &self.grammar.pos[usize::from(word.pos_id)];
+            let split_type = match (word.a_unit_split.is_empty(), word.b_unit_split.is_empty()) {
+                (true, _) => "A",
+                (false, true) => "B",
+                (false, false) => "C",
+            };
+            let dictionary_form = match word.dictionary_form_word_id {
+                id if id < 0 => "*".to_string(),
+                id => id.to_string(),
+            };
+            writer.write_record([
+                word.surface.as_str(),
+                &word.left_id.to_string(),
+                &word.right_id.to_string(),
+                &word.cost.to_string(),
+                &word.headword,
+                &pos[0],
+                &pos[1],
+                &pos[2],
+                &pos[3],
+                &pos[4],
+                &pos[5],
+                &word.reading_form,
+                &word.normalized_form,
+                &dictionary_form,
+                split_type,
+                &join_ids(&word.a_unit_split),
+                &join_ids(&word.b_unit_split),
+                &join_ids(&word.word_structure),
+                &join_ids(&word.synonym_group_ids),
+            ])?;
+        }
+        writer
+            .into_inner()
+            .map_err(|err| anyhow!("failed to render lexicon csv: {}", err))
+    }
+
+    pub fn to_lexicon_source<S: Into<String>>(&self, name: S) -> Result<LexiconSource> {
+        Ok(LexiconSource::new(name, self.to_lexicon_csv()?))
+    }
+}
+
+fn join_ids(ids: &[i32]) -> String {
+    if ids.is_empty() {
+        return "*".to_string();
+    }
+    ids.iter().map(i32::to_string).collect::<Vec<_>>().join("/")
+}
+
+fn read_grammar(cursor: &mut ByteCursor<'_>) -> Result<Grammar> {
+    let pos_size = usize::try_from(cursor.i16()?).map_err(|_| anyhow!("negative pos size"))?;
+    let mut pos = Vec::with_capacity(pos_size);
+    for _ in 0..pos_size {
+        pos.push([
+            cursor.string()?,
+            cursor.string()?,
+            cursor.string()?,
+            cursor.string()?,
+            cursor.string()?,
+            cursor.string()?,
+        ]);
+    }
+
+    let left_size = usize::try_from(cursor.i16()?).map_err(|_| anyhow!("negative left size"))?;
+    let right_size = usize::try_from(cursor.i16()?).map_err(|_| anyhow!("negative right size"))?;
+    let matrix = (0..left_size * right_size)
+        .map(|_| cursor.i16())
+        .collect::<Result<_>>()?;
+
+    Ok(Grammar {
+        pos,
+        left_size,
+        right_size,
+        matrix,
+    })
+}
+
+fn write_grammar(out: &mut Vec<u8>, grammar: &Grammar) -> Result<()> {
+    let size = |value: usize, name: &str| {
+        i16::try_from(value).map_err(|_| anyhow!("{} {} does not fit in i16", name, value))
+    };
+    if grammar.matrix.len() != grammar.left_size * grammar.right_size {
+        bail!(
+            "matrix has {} costs, expected {}x{}",
+            grammar.matrix.len(),
+            grammar.left_size,
+            grammar.right_size
+        );
+    }
+
+    out.extend(size(grammar.pos.len(), "pos size")?.to_le_bytes());
+    for pos in &grammar.pos {
+        for value in pos {
+            write_string(out, value)?;
+        }
+    }
+    out.extend(size(grammar.left_size, "left size")?.to_le_bytes());
+    out.extend(size(grammar.right_size, "right size")?.to_le_bytes());
+    for cost in &grammar.matrix {
+        out.extend(cost.to_le_bytes());
+    }
+    Ok(())
+}
+
+fn read_word_info(
+    cursor: &mut ByteCursor<'_>,
+    has_synonym_group_ids: bool,
+    left_id: i16,
+    right_id: i16,
+    cost: i16,
+) -> Result<SudachiWord> {
+    let headword = cursor.string()?;
+    let _headword_length = cursor.string_length()?;
+    let pos_id = cursor.u16()?;
+    let normalized_form = cursor.string()?;
+    let dictionary_form_word_id = cursor.i32()?;
+    let reading_form = cursor.string()?;
+    let a_unit_split = cursor.int_array()?;
+    let b_unit_split = cursor.int_array()?;
+    let word_structure = cursor.int_array()?;
+    let synonym_group_ids = if has_synonym_group_ids {
+        cursor.int_array()?
+    } else {
+        Vec::new()
+    };
+
+    Ok(SudachiWord {
+        surface: headword.clone(),
+        left_id,
+        right_id,
+        cost,
+        normalized_form: if normalized_form.is_empty() {
+            headword.clone()
+        } else {
+            normalized_form
+        },
+        reading_form: if reading_form.is_empty() {
+            headword.clone()
+        } else {
+            reading_form
+        },
+        headword,
+        pos_id,
+        dictionary_form_word_id,
+        a_unit_split,
+        b_unit_split,
+        word_structure,
+        synonym_group_ids,
+    })
+}
+
+fn write_word_info(
+    out: &mut Vec<u8>,
+    word: &SudachiWord,
+    has_synonym_group_ids: bool,
+) -> Result<()> {
+    write_string(out, &word.headword)?;
+    write_string_length(out, word.surface.len())?;
+    out.extend(word.pos_id.to_le_bytes());
+    write_string(out, &word.normalized_form)?;
+    out.extend(word.dictionary_form_word_id.to_le_bytes());
+    write_string(out, &word.reading_form)?;
+    write_int_array(out, &word.a_unit_split)?;
+    write_int_array(out, &word.b_unit_split)?;
+    write_int_array(out, &word.word_structure)?;
+    if has_synonym_group_ids {
+        write_int_array(out, &word.synonym_group_ids)?;
+    }
+    Ok(())
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) -> Result<()> {
+    let units: Vec<u16> = value.encode_utf16().collect();
+    write_string_length(out, units.len())?;
+    for unit in units {
+        out.extend(unit.to_le_bytes());
+    }
+    Ok(())
+}
+
+fn write_string_length(out: &mut Vec<u8>, len: usize) -> Result<()> {
+    match len {
+        0..=0x7f => out.push(len as u8),
+        0x80..=0x7fff => out.extend([(len >> 8) as u8 | 0x80, len as u8]),
+        _ => bail!("string of {} units is too long", len),
+    }
+    Ok(())
+}
+
+fn write_int_array(out: &mut Vec<u8>, values: &[i32]) -> Result<()> {
+    let len = u8::try_from(values.len()).map_err(|_| anyhow!("more than 255 split entries"))?;
+    out.push(len);
+    for value in values {
+        out.extend(value.to_le_bytes());
+    }
+    Ok(())
+}
+
+struct ByteCursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteCursor<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self::at(bytes, 0)
+    }
+
+    fn at(bytes: &'a [u8], pos: usize) -> Self {
+        Self { bytes, pos }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let slice = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| anyhow!("unexpected end of data at offset {}", self.pos))?;
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn i16(&mut self) -> Result<i16> {
+        Ok(i16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn string_length(&mut self) -> Result<usize> {
+        let first = self.u8()?;
+        if first < 0x80 {
+            Ok(usize::from(first))
+        } else {
+            Ok((usize::from(first & 0x7f) << 8) | usize::from(self.u8()?))
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.string_length()?;
+        let units: Vec<u16> = self
+            .take(2 * len)?
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .collect();
+        String::from_utf16(&units).map_err(|err| anyhow!("invalid UTF-16 string: {}", err))
+    }
+
+    fn int_array(&mut self) -> Result<Vec<i32>> {
+        let len = self.u8()?;
+        (0..len).map(|_| self.i32()).collect()
+    }
+}
+
+// darts-clone unit layout: label in bits 0..8, has_leaf in bit 8, offset from
+// bit 10 (scaled by 256 when bit 9 is set); value units set bit 31.
+
+fn unit_offset(unit: u32) -> usize {
+    ((unit >> 10) << ((unit & (1 << 9)) >> 6)) as usize
+}
+
+fn unit_has_leaf(unit: u32) -> bool {
+    (unit >> 8) & 1 == 1
+}
+
+fn unit_label(unit: u32) -> u32 {
+    unit & ((1 << 31) | 0xff)
+}
+
+/// Enumerates every `(key, value)` stored in a darts-clone double array.
+fn trie_entries(units: &[u32]) -> Result<Vec<(Vec<u8>, u32)>> {
+    let mut entries = Vec::new();
+    if units.is_empty() {
+        return Ok(entries);
+    }
+
+    let mut stack = vec![(0usize, Vec::new())];
+    while let Some((pos, key)) = stack.pop() {
+        let unit = units[pos];
+        let base = pos ^ unit_offset(unit);
+        if unit_has_leaf(unit) {
+            let value = units
+                .get(base)
+                .ok_or_else(|| anyhow!("trie value out of range at unit {}", pos))?;
+            entries.push((key.clone(), value & 0x7fff_ffff));
+        }
+        for label in 1..=0xffu32 {
+            let child = base ^ label as usize;
+            if units
+                .get(child)
+                .is_some_and(|unit| unit_label(*unit) == label)
+            {
+                let mut child_key = key.clone();
+                child_key.push(label as u8);
+                stack.push((child, child_key));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Builds a darts-clone compatible double array for sorted, unique keys.
+///
+/// This is a straightforward first-fit placement meant for fixtures and small
+/// dictionaries, not a replacement for Sudachi's own builder.
+fn build_trie(entries: &[(&[u8], u32)]) -> Result<Vec<u32>> {
+    #[derive(Default)]
+    struct Node {
+        children: BTreeMap<u8, usize>,
+        value: Option<u32>,
+    }
+
+    let mut nodes = vec![Node::default()];
+    for (key, value) in entries {
+        let mut node = 0;
+        for byte in *key {
+            if *byte == 0 {
+                bail!("trie keys must not contain NUL");
+            }
+            let next = nodes.len();
+            node = match nodes[node].children.get(byte) {
+                Some(child) => *child,
+                None => {
+                    nodes[node].children.insert(*byte, next);
+                    nodes.push(Node::default());
+                    next
+                }
+            };
+        }
+        if *value >= 1 << 31 {
+            bail!("trie value {} does not fit in 31 bits", value);
+        }
+        nodes[node].value = Some(*value);
+    }
+
+    let mut units = vec![0u32];
+    let mut used = vec![true];
+    let mut used_bases = HashSet::new();
+    let mut queue = VecDeque::from([(0usize, 0usize)]);
+
+    while let Some((node, pos)) = queue.pop_front() {
+        let node = &nodes[node];
+        let mut labels: Vec<usize> = node
+            .children
+            .keys()
+            .map(|label| usize::from(*label))
+            .collect();
+        if node.value.is_some() {
+            labels.push(0);
+        }
+        if labels.is_empty() {
+            continue;
+        }
+
+        let base = (1..)
+            .find(|base: &usize| {
+                *base != pos
+                    && !used_bases.contains(base)
+                    && labels
+                        .iter()
+                        .all(|label| !used.get(base ^ label).copied().unwrap_or(false))
+            })
+            .expect("an unbounded search always finds a free base");
+        let offset = base ^ pos;
+        if offset >= 1 << 21 {
+            bail!("trie offset {} is too large for this builder", offset);
+        }
+        used_bases.insert(base);
+
+        let needed = labels
+            .iter()
+            .map(|label| (base ^ label) + 1)
+            .max()
+            .unwrap_or(0);
+        if units.len() < needed {
+            units.resize(needed, 0);
+            used.resize(needed, false);
+        }
+
+        units[pos] |= (offset as u32) << 10;
+        if let Some(value) = node.value {
+            units[pos] |= 1 << 8;
+            units[base] = value | (1 << 31);
+            used[base] = true;
+        }
+        for (label, child) in &node.children {
+            let child_pos = base ^ usize::from(*label);
+            units[child_pos] = u32::from(*label);
+            used[child_pos] = true;
+            queue.push_back((*child, child_pos));
+        }
+    }
+
+    Ok(units)
+}
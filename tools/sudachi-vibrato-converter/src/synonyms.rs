@@ -0,0 +1,342 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use csv::{ReaderBuilder, StringRecord};
+use vibrato::dictionary::LexType;
+use vibrato::token::Token;
+
+use crate::sidecar::{put_u32, to_u32, write_header, SidecarReader};
+
+const MAGIC: &[u8; 4] = b"SVSG";
+const VERSION: u32 = 1;
+
+/// Synonym group ids (`col18`) of each converted lexicon row, stored as
+/// `synonym_groups.bin`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SynonymGroups {
+    /// End of each row's ids in `ids`.
+    ends: Vec<u32>,
+    ids: Vec<u32>,
+}
+
+impl SynonymGroups {
+    /// Appends the next row; returns the number of ids that were not numbers.
+    pub(crate) fn push(&mut self, column: &str) -> usize {
+        let column = column.trim();
+        let mut invalid = 0;
+        if !column.is_empty() && column != "*" {
+            for id in column.split('/') {
+                match id.trim().parse() {
+                    Ok(id) => self.ids.push(id),
+                    Err(_) => invalid += 1,
+                }
+            }
+        }
+        self.ends.push(self.ids.len() as u32);
+        invalid
+    }
+
+    /// Number of rows covered.
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// Group ids of a system lexicon row.
+    pub fn get(&self, row: u32) -> &[u32] {
+        let row = row as usize;
+        let Some(end) = self.ends.get(row) else {
+            return &[];
+        };
+        let start = row.checked_sub(1).map_or(0, |prev| self.ends[prev]);
+        &self.ids[start as usize..*end as usize]
+    }
+
+    /// Every distinct group id, sorted.
+    pub fn group_ids(&self) -> Vec<u32> {
+        let mut ids = self.ids.clone();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    /// `SVSG`, version, row and id counts, the per-row ends, then the ids.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        write_header(&mut out, MAGIC, VERSION);
+        put_u32(&mut out, to_u32(self.ends.len(), "row count")?);
+        put_u32(&mut out, to_u32(self.ids.len(), "group id count")?);
+        for value in self.ends.iter().chain(&self.ids) {
+            put_u32(&mut out, *value);
+        }
+        Ok(out)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = SidecarReader::new(bytes, MAGIC, VERSION)?;
+        let rows = reader.u32()?;
+        let ids = reader.u32()?;
+
+        let mut groups = Self::default();
+        let mut last = 0;
+        for row in 0..rows {
+            let end = reader.u32()?;
+            if end < last || end > ids {
+                return Err(anyhow!("invalid group id range of row {}", row));
+            }
+            last = end;
+            groups.ends.push(end);
+        }
+        for _ in 0..ids {
+            groups.ids.push(reader.u32()?);
+        }
+        reader.finish()?;
+        Ok(groups)
+    }
+
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_bytes()?)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::from_bytes(&bytes)
+            .with_context(|| format!("invalid synonym groups {}", path.display()))
+    }
+}
+
+/// 体言/用言 flag (`col1` of `synonyms.txt`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaigenYougen {
+    /// `1`
+    Taigen,
+    /// `2`
+    Yougen,
+}
+
+/// 展開制御 flag (`col2`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpansionControl {
+    /// `0`: always used.
+    Always,
+    /// `1`: an expansion target, but does not trigger expansion itself.
+    NoTrigger,
+    /// `2`: never used.
+    Never,
+}
+
+/// 語形種別 within a lexeme (`col4`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LexemeForm {
+    /// `0`
+    Representative,
+    /// `1`: 対訳
+    Translation,
+    /// `2`: 別称
+    Alias,
+    /// `3`: 旧称
+    OldName,
+    /// `4`: 誤用
+    Misuse,
+}
+
+/// One line of `synonyms.txt`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Synonym {
+    pub group_id: u32,
+    pub taigen_yougen: TaigenYougen,
+    pub expansion: ExpansionControl,
+    /// 語彙素番号 within the group (`col3`).
+    pub lexeme_id: u32,
+    pub form: LexemeForm,
+    /// 略語 flag (`col5`): 0 none, 1 alphabetic abbreviation, 2 abbreviation.
+    pub abbreviation: u8,
+    /// 表記揺れ flag (`col6`): 0 none, 1 alphabet, 2 variant, 3 misspelling.
+    pub spelling_variant: u8,
+    /// 分野 (`col7`), e.g. `(IT)`, without the parentheses.
+    pub domains: Vec<String>,
+    pub headword: String,
+}
+
+/// A parsed Sudachi `synonyms.txt`, keyed by group id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SynonymDictionary {
+    groups: BTreeMap<u32, Vec<Synonym>>,
+}
+
+impl SynonymDictionary {
+    pub fn from_reader<R: Read>(name: &str, input: R) -> Result<Self> {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(input);
+
+        let mut dictionary = Self::default();
+        for record in reader.records() {
+            let record = record.with_context(|| format!("failed to read {}", name))?;
+            let line_no = record.position().map_or(0, |position| position.line());
+            if record.iter().all(|field| field.trim().is_empty()) {
+                continue;
+            }
+            let synonym = parse_synonym(&record, name, line_no)?;
+            dictionary
+                .groups
+                .entry(synonym.group_id)
+                .or_default()
+                .push(synonym);
+        }
+        Ok(dictionary)
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        Self::from_reader(&name, file)
+    }
+
+    /// Number of groups.
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Entries of a group in file order.
+    pub fn group(&self, group_id: u32) -> &[Synonym] {
+        self.groups.get(&group_id).map_or(&[], Vec::as_slice)
+    }
+}
+
+fn parse_synonym(record: &StringRecord, name: &str, line_no: u64) -> Result<Synonym> {
+    if record.len() < 9 {
+        return Err(anyhow!(
+            "invalid synonym row at {} line {}: expected >=9 columns, got {}",
+            name,
+            line_no,
+            record.len()
+        ));
+    }
+    let number = |index: usize, column: &str| -> Result<u32> {
+        let value = record.get(index).unwrap_or("").trim();
+        value.parse().with_context(|| {
+            format!(
+                "failed to parse {}='{}' at {} line {}",
+                column, value, name, line_no
+            )
+        })
+    };
+    let invalid = |column: &str, value: u32| {
+        anyhow!("unknown {} {} at {} line {}", column, value, name, line_no)
+    };
+
+    let taigen_yougen = match number(1, "taigen_yougen")? {
+        1 => TaigenYougen::Taigen,
+        2 => TaigenYougen::Yougen,
+        value => return Err(invalid("taigen_yougen", value)),
+    };
+    let expansion = match number(2, "expansion")? {
+        0 => ExpansionControl::Always,
+        1 => ExpansionControl::NoTrigger,
+        2 => ExpansionControl::Never,
+        value => return Err(invalid("expansion", value)),
+    };
+    let form = match number(4, "form")? {
+        0 => LexemeForm::Representative,
+        1 => LexemeForm::Translation,
+        2 => LexemeForm::Alias,
+        3 => LexemeForm::OldName,
+        4 => LexemeForm::Misuse,
+        value => return Err(invalid("form", value)),
+    };
+    let flag = |index: usize, column: &str| -> Result<u8> {
+        let value = number(index, column)?;
+        u8::try_from(value).map_err(|_| invalid(column, value))
+    };
+    let domains = record.get(7).unwrap_or("").trim();
+    let domains = domains
+        .strip_prefix('(')
+        .and_then(|domains| domains.strip_suffix(')'))
+        .unwrap_or(domains);
+
+    Ok(Synonym {
+        group_id: number(0, "group_id")?,
+        taigen_yougen,
+        expansion,
+        lexeme_id: number(3, "lexeme_id")?,
+        form,
+        abbreviation: flag(5, "abbreviation")?,
+        spelling_variant: flag(6, "spelling_variant")?,
+        domains: domains
+            .split('/')
+            .filter(|domain| !domain.is_empty())
+            .map(str::to_string)
+            .collect(),
+        headword: record.get(8).unwrap_or("").trim().to_string(),
+    })
+}
+
+/// Expands lexicon words into the synonyms of their groups.
+#[derive(Debug, Clone, Copy)]
+pub struct SynonymExpander<'a> {
+    groups: &'a SynonymGroups,
+    synonyms: &'a SynonymDictionary,
+}
+
+impl<'a> SynonymExpander<'a> {
+    pub fn new(groups: &'a SynonymGroups, synonyms: &'a SynonymDictionary) -> Self {
+        Self { groups, synonyms }
+    }
+
+    /// Synonyms of a system lexicon row that appeared as `written`.
+    ///
+    /// A group is skipped when its entry for `written` does not trigger expansion.
+    /// `Never` entries and `written` itself are left out; headwords are distinct,
+    /// in group then file order.
+    pub fn expand_row(&self, row: u32, written: &str) -> Vec<&'a Synonym> {
+        let mut seen = HashSet::from([written]);
+        let mut expanded = Vec::new();
+        for group_id in self.groups.get(row) {
+            let group = self.synonyms.group(*group_id);
+            let triggers = group
+                .iter()
+                .filter(|synonym| synonym.headword == written)
+                .all(|synonym| synonym.expansion == ExpansionControl::Always);
+            if !triggers {
+                continue;
+            }
+            for synonym in group {
+                if synonym.expansion != ExpansionControl::Never
+                    && seen.insert(synonym.headword.as_str())
+                {
+                    expanded.push(synonym);
+                }
+            }
+        }
+        expanded
+    }
+
+    /// [`Self::expand_row`] for a token's surface; empty for unknown and user words.
+    pub fn expand(&self, token: &Token<'_, '_>) -> Vec<&'a Synonym> {
+        let word_idx = token.word_idx();
+        match word_idx.lex_type {
+            LexType::System => self.expand_row(word_idx.word_id, token.surface()),
+            _ => Vec::new(),
+        }
+    }
+}
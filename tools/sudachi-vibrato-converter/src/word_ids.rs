@@ -0,0 +1,146 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use vibrato::dictionary::LexType;
+use vibrato::token::Token;
+
+use crate::sidecar::{put_u16, put_u32, to_u32, write_header, SidecarReader, StringTable};
+use crate::word_units::{SplitToken, SplitWord};
+
+const MAGIC: &[u8; 4] = b"SVWI";
+const VERSION: u32 = 1;
+/// Stored word id of rows that are not from a Sudachi lexicon.
+const NO_WORD_ID: u32 = u32::MAX;
+
+/// Where a lexicon row came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WordOrigin<'a> {
+    /// Sudachi word id; `None` for appended rows.
+    pub word_id: Option<u32>,
+    /// File name of the lexicon or append file.
+    pub source: &'a str,
+}
+
+/// Sudachi word id and source file of each lexicon row, stored as `word_ids.bin`.
+///
+/// Rows are Vibrato system word ids: converted rows in Sudachi word id order,
+/// then appended rows.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WordIds {
+    sources: StringTable,
+    /// `(word id, source index)` per row.
+    rows: Vec<(u32, u16)>,
+}
+
+impl WordIds {
+    /// Appends the next row.
+    pub(crate) fn push(&mut self, source: &str, word_id: Option<u32>) -> Result<()> {
+        let index = (0..self.sources.len())
+            .rev()
+            .find(|index| self.sources.get(*index) == Some(source));
+        let index = match index {
+            Some(index) => index,
+            None => {
+                self.sources.push(source);
+                self.sources.len() - 1
+            }
+        };
+        let index = u16::try_from(index).map_err(|_| anyhow!("more than {} sources", u16::MAX))?;
+        self.rows.push((word_id.unwrap_or(NO_WORD_ID), index));
+        Ok(())
+    }
+
+    /// Number of rows covered.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Origin of a system lexicon row.
+    pub fn get(&self, row: u32) -> Option<WordOrigin<'_>> {
+        let (word_id, source) = *self.rows.get(row as usize)?;
+        Some(WordOrigin {
+            word_id: (word_id != NO_WORD_ID).then_some(word_id),
+            source: self.sources.get(usize::from(source))?,
+        })
+    }
+
+    /// Origin of a token; `None` for unknown and user dictionary words.
+    pub fn of_token(&self, token: &Token<'_, '_>) -> Option<WordOrigin<'_>> {
+        let word_idx = token.word_idx();
+        match word_idx.lex_type {
+            LexType::System => self.get(word_idx.word_id),
+            _ => None,
+        }
+    }
+
+    /// Sudachi word id of a token from [`crate::Resplitter`], including split-only units.
+    pub fn of_split_token(&self, token: &SplitToken) -> Option<u32> {
+        match token.word {
+            SplitWord::Lexicon(word_idx) if word_idx.lex_type == LexType::System => {
+                self.get(word_idx.word_id)?.word_id
+            }
+            SplitWord::Lexicon(_) => None,
+            SplitWord::SplitOnly(word_id) => Some(word_id),
+        }
+    }
+
+    /// Lexicon row of a Sudachi word id; `None` for split-only and skipped rows.
+    pub fn row_of(&self, word_id: u32) -> Option<u32> {
+        // Converted rows come first, in increasing word id order.
+        let converted = self.rows.partition_point(|(id, _)| *id != NO_WORD_ID);
+        let rows = &self.rows[..converted];
+        let row = rows.partition_point(|(id, _)| *id < word_id);
+        (rows.get(row)?.0 == word_id).then_some(row as u32)
+    }
+
+    /// `SVWI`, version, the source names as a string table, the row count, then
+    /// `(word id, source index)` per row; appended rows have word id `u32::MAX`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        write_header(&mut out, MAGIC, VERSION);
+        self.sources.write(&mut out)?;
+        put_u32(&mut out, to_u32(self.rows.len(), "row count")?);
+        for (word_id, source) in &self.rows {
+            put_u32(&mut out, *word_id);
+            put_u16(&mut out, *source);
+        }
+        Ok(out)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = SidecarReader::new(bytes, MAGIC, VERSION)?;
+        let sources = StringTable::read(&mut reader)?;
+        let rows = reader.u32()?;
+        let mut word_ids = Self {
+            sources,
+            rows: Vec::with_capacity(rows as usize),
+        };
+        for row in 0..rows {
+            let word_id = reader.u32()?;
+            let source = reader.u16()?;
+            if usize::from(source) >= word_ids.sources.len() {
+                return Err(anyhow!("invalid source index {} of row {}", source, row));
+            }
+            word_ids.rows.push((word_id, source));
+        }
+        reader.finish()?;
+        Ok(word_ids)
+    }
+
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_bytes()?)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::from_bytes(&bytes).with_context(|| format!("invalid word ids {}", path.display()))
+    }
+}
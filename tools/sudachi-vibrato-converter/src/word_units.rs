@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Error, Result};
+use vibrato::dictionary::{LexType, WordIdx};
+use vibrato::token::Token;
+use vibrato::tokenizer::worker::Worker;
+use vibrato::Dictionary;
+
+use crate::lexicon::WordIndex;
+use crate::sidecar::{put_u16, put_u32, to_u32, write_header, SidecarReader};
+use crate::split_info::SplitInfo;
+
+const MAGIC: &[u8; 4] = b"SVWU";
+const VERSION: u32 = 1;
+/// Set on a stored target that is a split-only Sudachi word id, not a lexicon row.
+const SPLIT_ONLY_FLAG: u32 = 1 << 31;
+
+/// Sudachi analysis granularity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitMode {
+    /// Shortest units (`col15`).
+    A,
+    /// Middle units (`col16`).
+    B,
+    /// Tokens as Vibrato yields them.
+    C,
+}
+
+impl FromStr for SplitMode {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "A" | "a" => Ok(Self::A),
+            "B" | "b" => Ok(Self::B),
+            "C" | "c" => Ok(Self::C),
+            _ => Err(anyhow!(
+                "unknown split mode '{}': expected A, B or C",
+                value
+            )),
+        }
+    }
+}
+
+/// What a unit refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitTarget {
+    /// A system lexicon row, i.e. a Vibrato word id.
+    Row(u32),
+    /// A `split_info.csv` entry, by Sudachi word id.
+    SplitOnly(u32),
+}
+
+/// One A or B unit of a lexicon row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WordUnit {
+    pub target: UnitTarget,
+    /// Length of the unit surface in characters.
+    pub chars: u16,
+}
+
+/// Split columns of a written lexicon row, kept until every row is known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PendingSplits {
+    pub(crate) word_id: u32,
+    pub(crate) surface: String,
+    pub(crate) a: String,
+    pub(crate) b: String,
+}
+
+/// A and B units of each converted lexicon row, stored as `word_units.bin`.
+///
+/// A row without a split for a mode, or past the end (such as appended rows),
+/// is its own unit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WordUnits {
+    /// End of the A units and of the B units of each row in `units`.
+    ends: Vec<[u32; 2]>,
+    units: Vec<WordUnit>,
+}
+
+impl WordUnits {
+    /// Resolves split word ids (`col15`/`col16`) to lexicon rows or split-only entries.
+    ///
+    /// A split is dropped, and its row counted in the returned number, when a unit
+    /// is neither, or the unit surfaces do not spell the row surface.
+    pub(crate) fn resolve(
+        pending: &[PendingSplits],
+        word_index: &WordIndex,
+        split_info: &SplitInfo,
+    ) -> (Self, usize) {
+        let rows: HashMap<u32, u32> = pending
+            .iter()
+            .enumerate()
+            .map(|(row, splits)| (splits.word_id, row as u32))
+            .collect();
+
+        let mut word_units = Self::default();
+        let mut unresolved_rows = 0;
+        for splits in pending {
+            let mut ends = [0; 2];
+            let mut unresolved = false;
+            for (end, column) in ends.iter_mut().zip([&splits.a, &splits.b]) {
+                match resolve_split(column, &splits.surface, &rows, word_index, split_info) {
+                    Some(units) => word_units.units.extend(units),
+                    None => unresolved = true,
+                }
+                *end = word_units.units.len() as u32;
+            }
+            word_units.ends.push(ends);
+            unresolved_rows += usize::from(unresolved);
+        }
+        (word_units, unresolved_rows)
+    }
+
+    /// Number of rows covered.
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// Units of a system lexicon row; empty when the row is its own unit.
+    pub fn units(&self, row: u32, mode: SplitMode) -> &[WordUnit] {
+        let row = row as usize;
+        let Some([a_end, b_end]) = self.ends.get(row) else {
+            return &[];
+        };
+        let start = row.checked_sub(1).map_or(0, |prev| self.ends[prev][1]);
+        let range = match mode {
+            SplitMode::A => start..*a_end,
+            SplitMode::B => *a_end..*b_end,
+            SplitMode::C => return &[],
+        };
+        &self.units[range.start as usize..range.end as usize]
+    }
+
+    /// `SVWU`, version, row and unit counts, the per-row ends, then `(target, chars)` per unit.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        write_header(&mut out, MAGIC, VERSION);
+        put_u32(&mut out, to_u32(self.ends.len(), "row count")?);
+        put_u32(&mut out, to_u32(self.units.len(), "unit count")?);
+        for [a_end, b_end] in &self.ends {
+            put_u32(&mut out, *a_end);
+            put_u32(&mut out, *b_end);
+        }
+        for unit in &self.units {
+            let target = match unit.target {
+                UnitTarget::Row(row) => row,
+                UnitTarget::SplitOnly(word_id) => word_id | SPLIT_ONLY_FLAG,
+            };
+            put_u32(&mut out, target);
+            put_u16(&mut out, unit.chars);
+        }
+        Ok(out)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = SidecarReader::new(bytes, MAGIC, VERSION)?;
+        let rows = reader.u32()? as usize;
+        let units = reader.u32()?;
+
+        let mut word_units = Self::default();
+        let mut last = 0;
+        for row in 0..rows {
+            let ends = [reader.u32()?, reader.u32()?];
+            if ends[0] < last || ends[1] < ends[0] || ends[1] > units {
+                return Err(anyhow!("invalid unit range of row {}", row));
+            }
+            last = ends[1];
+            word_units.ends.push(ends);
+        }
+        for _ in 0..units {
+            let target = reader.u32()?;
+            let target = if target & SPLIT_ONLY_FLAG == 0 {
+                UnitTarget::Row(target)
+            } else {
+                UnitTarget::SplitOnly(target & !SPLIT_ONLY_FLAG)
+            };
+            let chars = reader.u16()?;
+            word_units.units.push(WordUnit { target, chars });
+        }
+        reader.finish()?;
+        Ok(word_units)
+    }
+
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_bytes()?)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::from_bytes(&bytes).with_context(|| format!("invalid word units {}", path.display()))
+    }
+}
+
+fn resolve_split(
+    column: &str,
+    surface: &str,
+    rows: &HashMap<u32, u32>,
+    word_index: &WordIndex,
+    split_info: &SplitInfo,
+) -> Option<Vec<WordUnit>> {
+    let column = column.trim();
+    if column.is_empty() || column == "*" {
+        return Some(Vec::new());
+    }
+
+    let mut spelled = String::new();
+    let mut units = Vec::new();
+    for word_id in column.split('/') {
+        let word_id: u32 = word_id.trim().parse().ok()?;
+        let target = match rows.get(&word_id) {
+            Some(row) => UnitTarget::Row(*row),
+            None if word_id & SPLIT_ONLY_FLAG == 0 && split_info.get(word_id).is_some() => {
+                UnitTarget::SplitOnly(word_id)
+            }
+            None => return None,
+        };
+        let unit_surface = word_index.surface(word_id as usize)?;
+        spelled.push_str(unit_surface);
+        units.push(WordUnit {
+            target,
+            chars: u16::try_from(unit_surface.chars().count()).ok()?,
+        });
+    }
+    (spelled == surface).then_some(units)
+}
+
+/// A token, or one of its A/B units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitToken {
+    pub surface: String,
+    pub range_char: Range<usize>,
+    pub range_byte: Range<usize>,
+    pub feature: String,
+    pub word: SplitWord,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitWord {
+    /// A word of the Vibrato dictionary.
+    Lexicon(WordIdx),
+    /// A `split_info.csv` entry, by Sudachi word id.
+    SplitOnly(u32),
+}
+
+/// Re-splits Vibrato tokens into Sudachi A or B units.
+///
+/// `units` and `split_info` must come from the build that produced `dictionary`.
+/// Tokens that are not system lexicon words, have no split for the mode, or whose
+/// units do not cover them are returned whole.
+#[derive(Clone, Copy)]
+pub struct Resplitter<'a> {
+    dictionary: &'a Dictionary,
+    units: &'a WordUnits,
+    split_info: &'a SplitInfo,
+}
+
+impl<'a> Resplitter<'a> {
+    pub fn new(
+        dictionary: &'a Dictionary,
+        units: &'a WordUnits,
+        split_info: &'a SplitInfo,
+    ) -> Self {
+        Self {
+            dictionary,
+            units,
+            split_info,
+        }
+    }
+
+    pub fn split(&self, token: &Token<'_, '_>, mode: SplitMode) -> Vec<SplitToken> {
+        self.split_units(token, mode).unwrap_or_else(|| {
+            vec![SplitToken {
+                surface: token.surface().to_string(),
+                range_char: token.range_char(),
+                range_byte: token.range_byte(),
+                feature: token.feature().to_string(),
+                word: SplitWord::Lexicon(token.word_idx()),
+            }]
+        })
+    }
+
+    /// Splits every token of the last tokenized sentence.
+    pub fn split_worker(&self, worker: &Worker<'_>, mode: SplitMode) -> Vec<SplitToken> {
+        (0..worker.num_tokens())
+            .flat_map(|i| self.split(&worker.token(i), mode))
+            .collect()
+    }
+
+    fn split_units(&self, token: &Token<'_, '_>, mode: SplitMode) -> Option<Vec<SplitToken>> {
+        let word_idx = token.word_idx();
+        if word_idx.lex_type != LexType::System {
+            return None;
+        }
+        let units = self.units.units(word_idx.word_id, mode);
+        let surface = token.surface();
+        // Byte offset of every character boundary in the surface.
+        let boundaries: Vec<usize> = surface
+            .char_indices()
+            .map(|(offset, _)| offset)
+            .chain([surface.len()])
+            .collect();
+        let chars: usize = units.iter().map(|unit| usize::from(unit.chars)).sum();
+        if units.is_empty() || chars != boundaries.len() - 1 {
+            return None;
+        }
+
+        let range_char = token.range_char();
+        let range_byte = token.range_byte();
+        let mut start = 0;
+        let mut tokens = Vec::with_capacity(units.len());
+        for unit in units {
+            let end = start + usize::from(unit.chars);
+            let (feature, word) = match unit.target {
+                UnitTarget::Row(row) => {
+                    let word_idx = WordIdx {
+                        lex_type: LexType::System,
+                        word_id: row,
+                    };
+                    (
+                        self.dictionary.word_feature(word_idx).to_string(),
+                        SplitWord::Lexicon(word_idx),
+                    )
+                }
+                UnitTarget::SplitOnly(word_id) => (
+                    self.split_info.get(word_id)?.feature(),
+                    SplitWord::SplitOnly(word_id),
+                ),
+            };
+            tokens.push(SplitToken {
+                surface: surface[boundaries[start]..boundaries[end]].to_string(),
+                range_char: range_char.start + start..range_char.start + end,
+                range_byte: range_byte.start + boundaries[start]
+                    ..range_byte.start + boundaries[end],
+                feature,
+                word,
+            });
+            start = end;
+        }
+        Some(tokens)
+    }
+}
@@ -0,0 +1,284 @@
+use std::fs;
+
+use anyhow::Result;
+use sudachi_vibrato_converter::bundle::{
+    sha256_hex, DICTIONARY_FILE, NORMALIZED_FORMS_FILE, SPLIT_INFO_FILE, SYNONYM_GROUPS_FILE,
+    WORD_IDS_FILE, WORD_UNITS_FILE,
+};
+use sudachi_vibrato_converter::cli::BuildArgs;
+use sudachi_vibrato_converter::compile::{check_vibrato_ref, VIBRATO_VERSION};
+use sudachi_vibrato_converter::pipeline::{format_utc, run_build};
+use sudachi_vibrato_converter::{BuildManifest, Bundle, BundleMetadata, ConversionStats};
+use tempfile::tempdir;
+
+fn metadata() -> BundleMetadata {
+    BundleMetadata {
+        sudachidict_dict_version: "20250129".to_string(),
+        edition: "full".to_string(),
+        vibrato_ref: "v0.5.2".to_string(),
+        built_at_utc: "2025-02-01T00:00:00Z".to_string(),
+        dictionary_file: DICTIONARY_FILE.to_string(),
+        ..BundleMetadata::default()
+    }
+}
+
+#[test]
+fn bundle_round_trips_with_digests_and_stats() -> Result<()> {
+    let dir = tempdir()?;
+    let stats_path = dir.path().join("stats.json");
+    let stats = ConversionStats {
+        feature_schema: "mecab9-v1".to_string(),
+        written: 10,
+        normalized_pos_rows: 3,
+        unresolved_base_refs: 1,
+        ..ConversionStats::default()
+    };
+    stats.write_json_file(&stats_path)?;
+
+    let mut metadata = metadata();
+    metadata.record_stats(&ConversionStats::read_json_file(&stats_path)?);
+    let mut bundle = Bundle::new("sudachidict-20250129-full+vibrato-v0_5_2", metadata);
+    bundle.add_file(DICTIONARY_FILE, b"dictionary".to_vec());
+    bundle.add_file("LEGAL", b"legal".to_vec());
+    bundle.add_file("rewrite.def", b"rewrite".to_vec());
+
+    let path = dir.path().join("bundle.tar.xz");
+    bundle.write_to_path(&path)?;
+    let read = Bundle::from_path(&path)?;
+
+    assert_eq!(read, bundle);
+    assert_eq!(read.metadata.feature_schema, "mecab9-v1");
+    assert_eq!(read.metadata.lex_rows_written, 10);
+    assert_eq!(read.metadata.normalized_pos_rows, 3);
+    assert_eq!(read.metadata.unresolved_base_refs, 1);
+    assert!(read.metadata.rewrite_def_included);
+    assert_eq!(read.metadata.sha256["LEGAL"], sha256_hex(b"legal"));
+    assert_eq!(
+        read.file_names().collect::<Vec<_>>(),
+        ["LEGAL", "rewrite.def", "system.dic.zst"]
+    );
+    Ok(())
+}
+
+#[test]
+fn bundle_tarball_is_deterministic() -> Result<()> {
+    let mut first = Bundle::new("bundle", metadata());
+    first.add_file(DICTIONARY_FILE, b"dictionary".to_vec());
+    first.add_file("LEGAL", b"legal".to_vec());
+
+    let mut second = Bundle::new("bundle", metadata());
+    second.add_file("LEGAL", b"legal".to_vec());
+    second.add_file(DICTIONARY_FILE, b"dictionary".to_vec());
+
+    let mut first_bytes = Vec::new();
+    first.write_tar_xz(&mut first_bytes)?;
+    let mut second_bytes = Vec::new();
+    second.write_tar_xz(&mut second_bytes)?;
+    assert_eq!(first_bytes, second_bytes);
+    Ok(())
+}
+
+#[test]
+fn bundle_validation_rejects_digest_mismatch_and_missing_dictionary() {
+    let mut bundle = Bundle::new("bundle", metadata());
+    bundle.add_file("LEGAL", b"legal".to_vec());
+    assert!(bundle.validate().is_err());
+
+    bundle.add_file(DICTIONARY_FILE, b"dictionary".to_vec());
+    assert!(bundle.validate().is_ok());
+
+    bundle
+        .metadata
+        .sha256
+        .insert("LEGAL".to_string(), sha256_hex(b"other"));
+    let error = bundle.validate().unwrap_err();
+    assert!(error.to_string().contains("sha256 mismatch for LEGAL"));
+}
+
+#[test]
+fn build_runs_the_pipeline_from_a_manifest() -> Result<()> {
+    let dir = tempdir()?;
+    let root = dir.path();
+    fs::write(
+        root.join("lex.csv"),
+        concat!(
+            "東京都,0,0,100,東京都,名詞,固有名詞,地名,一般,*,*,トウキョウト,東京都,*,A,*,*,*,*\n",
+            "に,0,0,100,に,助詞,格助詞,*,*,*,*,ニ,に,*,A,*,*,*,*\n",
+            "行く,0,0,100,行く,動詞,非自立可能,*,*,五段-カ行,終止形-一般,イク,行く,*,A,*,*,*,*\n",
+        ),
+    )?;
+    fs::write(root.join("matrix.def"), "1 1\n0 0 0\n")?;
+    fs::write(root.join("char.def"), "DEFAULT 0 1 0\n")?;
+    fs::write(
+        root.join("unk.def"),
+        "DEFAULT,0,0,100,補助記号,一般,*,*,*,*\n",
+    )?;
+    fs::write(root.join("LICENSE-2.0.txt"), "license")?;
+    fs::write(root.join("LEGAL"), "legal")?;
+    let rules = root.join("rules/empty");
+    fs::create_dir_all(&rules)?;
+    for name in ["lex.append.csv", "char.append.def", "unk.append.def"] {
+        fs::write(rules.join(name), "")?;
+    }
+
+    let manifest = r#"
+[build]
+edition = "full"
+vibrato_ref = "v0.5.2"
+compat_target = "jpreprocess"
+compat_mode = "safe-normalized"
+feature_schema = "mecab9"
+rules_profile = "empty"
+
+[source]
+sudachidict_repo = "WorksApplications/SudachiDict"
+sudachidict_release_tag = "v20250129"
+dict_version = "20250129"
+sudachi_repo = "WorksApplications/Sudachi"
+sudachi_version = "0.7.5"
+sudachi_tag = "v0.7.5"
+
+[inputs]
+lex = ["lex.csv"]
+matrix = "matrix.def"
+char = "char.def"
+unk = "unk.def"
+license = "LICENSE-2.0.txt"
+legal = "LEGAL"
+"#;
+    fs::write(root.join("build.toml"), manifest)?;
+
+    let outputs = run_build(&BuildArgs {
+        manifest: root.join("build.toml"),
+        out_dir: root.join("dist"),
+        work_dir: None,
+        built_at_utc: Some("2025-02-01T00:00:00Z".to_string()),
+        outputs_out: None,
+        release_notes_out: None,
+    })?;
+
+    assert_eq!(outputs.release_tag, "sudachi-20250129-full-vibrato-v0_5_2");
+    assert_eq!(
+        outputs.asset_path,
+        root.join("dist/sudachidict-20250129-full+vibrato-v0_5_2.tar.xz")
+    );
+    let bundle = Bundle::from_path(&outputs.asset_path)?;
+    assert_eq!(bundle.root, "sudachidict-20250129-full+vibrato-v0_5_2");
+    assert_eq!(bundle.metadata.feature_schema, "mecab9-v1");
+    assert_eq!(bundle.metadata.lex_rows_written, 3);
+    assert!(!bundle.metadata.rewrite_def_included);
+
+    assert!(bundle.file(SPLIT_INFO_FILE).is_some());
+    assert!(bundle.file(WORD_UNITS_FILE).is_some());
+    assert!(bundle.file(NORMALIZED_FORMS_FILE).is_some());
+    assert!(bundle.file(WORD_IDS_FILE).is_some());
+    assert!(bundle.file(SYNONYM_GROUPS_FILE).is_some());
+    let stats = bundle.stats()?.expect("stats.json is bundled");
+    assert_eq!(stats, outputs.stats);
+    assert_eq!(stats.unk_rows, 1);
+    assert_eq!(
+        stats
+            .inputs
+            .iter()
+            .map(|input| input.role.as_str())
+            .collect::<Vec<_>>(),
+        [
+            "unk",
+            "char",
+            "lex",
+            "lex_append",
+            "unk_append",
+            "char_append"
+        ]
+    );
+    assert_eq!(
+        stats.inputs[2].sha256,
+        sha256_hex(&fs::read(root.join("lex.csv"))?)
+    );
+    let notes = outputs.release_notes();
+    assert!(notes.contains("- dict_version: 20250129\n"));
+    assert!(notes.contains("- lex_rows_written: 3\n"));
+    assert!(notes.contains(&format!("| lex | lex.csv | `{}` |", stats.inputs[2].sha256)));
+
+    let echoed = bundle.metadata.manifest.expect("manifest is echoed");
+    let parsed = BuildManifest::from_toml(manifest)?;
+    assert_eq!(echoed.build, parsed.build);
+    assert_eq!(echoed.source, parsed.source);
+    assert_eq!(echoed.build.smoke_text, ["東京都に行く"]);
+    Ok(())
+}
+
+#[test]
+fn build_manifest_requires_lexicon_inputs() {
+    let manifest = r#"
+[build]
+edition = "full"
+vibrato_ref = "v0.5.2"
+compat_target = "jpreprocess"
+compat_mode = "safe-normalized"
+feature_schema = "mecab9"
+rules_profile = "empty"
+
+[source]
+sudachidict_repo = "r"
+sudachidict_release_tag = "t"
+dict_version = "20250129"
+sudachi_repo = "r"
+sudachi_version = "0.7.5"
+sudachi_tag = "v0.7.5"
+
+[inputs]
+lex = ["lex.csv"]
+char = "char.def"
+unk = "unk.def"
+license = "LICENSE-2.0.txt"
+legal = "LEGAL"
+"#;
+    let error = BuildManifest::from_toml(manifest).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("inputs.lex requires inputs.matrix"));
+}
+
+#[test]
+fn build_time_is_formatted_as_utc() {
+    assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+    assert_eq!(format_utc(1_738_368_000), "2025-02-01T00:00:00Z");
+    assert_eq!(format_utc(951_825_599), "2000-02-29T11:59:59Z");
+}
+
+#[test]
+fn build_manifest_rejects_a_vibrato_ref_other_than_the_linked_crate() {
+    let manifest = r#"
+[build]
+edition = "full"
+vibrato_ref = "v0.6.0"
+compat_target = "jpreprocess"
+compat_mode = "safe-normalized"
+feature_schema = "mecab9"
+rules_profile = "empty"
+
+[source]
+sudachidict_repo = "r"
+sudachidict_release_tag = "t"
+dict_version = "20250129"
+sudachi_repo = "r"
+sudachi_version = "0.7.5"
+sudachi_tag = "v0.7.5"
+
+[inputs]
+char = "char.def"
+unk = "unk.def"
+license = "LICENSE-2.0.txt"
+legal = "LEGAL"
+"#;
+    let error = BuildManifest::from_toml(manifest).unwrap_err();
+    assert!(format!("{:#}", error).contains("does not match the linked vibrato crate 0.5.2"));
+    assert!(check_vibrato_ref("v0.5.2").is_ok());
+}
+
+#[test]
+fn vibrato_version_matches_the_cargo_pin() {
+    let manifest = include_str!("../Cargo.toml");
+    assert!(manifest.contains(&format!("vibrato = \"={}\"", VIBRATO_VERSION)));
+}
@@ -2,10 +2,21 @@ use std::fs;
 use std::io::Cursor;
 
 use anyhow::Result;
+use clap::Parser;
 use csv::ReaderBuilder;
+use sudachi_vibrato_converter::bundle::sha256_hex;
+use sudachi_vibrato_converter::cli::{Cli, Commands};
+use sudachi_vibrato_converter::pipeline::{run_convert, run_gen_char, run_lint};
 use sudachi_vibrato_converter::{
-    append_text_files_as_lines, append_unknown_definitions, convert_char_definition,
-    convert_lexicon, convert_unknown_dictionary, write_rewrite_definition, ConversionStats,
+    append_lexicon_rows, append_text_files_as_lines, append_unknown_definitions,
+    convert_char_definition, convert_lexicon, convert_lexicon_sources,
+    convert_lexicon_with_options, convert_unknown_dictionary,
+    convert_unknown_dictionary_with_options, convert_unknown_dictionary_with_stats,
+    convert_user_lexicon, feature_schema_by_name, generate_char_definition, infer_cform,
+    infer_ctype, lint, lint_paths, mora_count, pronunciation_from_reading,
+    resolve_unknown_append_ids, unicode_category, write_rewrite_definition, AccentDictionary,
+    CharDefinition, CharGenMode, CharMergeCounts, CharRange, ConversionStats, ConvertOptions,
+    ErrorPolicy, LexiconSource, LintReport, MatrixSize, PosMapping, RejectedRow, SystemLexicon,
 };
 use tempfile::tempdir;
 
@@ -31,7 +42,7 @@ fn lex_uses_pos_5_to_8_and_reading_11() -> Result<()> {
     assert_eq!(row[5], "自立");
     assert_eq!(row[6], "*");
     assert_eq!(row[7], "*");
-    assert_eq!(row[8], "五段・ワ行ウ音便");
+    assert_eq!(row[8], "五段・ワ行促音便");
     assert_eq!(row[9], "基本形");
     assert_eq!(row[10], "原形");
     assert_eq!(row[11], "ヨミ");
@@ -92,7 +103,7 @@ fn unk_is_converted_to_mecab_minimum_schema() -> Result<()> {
 }
 
 #[test]
-fn char_definition_strips_nooovbow_entries() -> Result<()> {
+fn char_definition_strips_nooovbow_entries_and_comments() -> Result<()> {
     let input = concat!(
         "# comment\n",
         "0x0041..0x005A ALPHA NOOOVBOW #A-Z\n",
@@ -104,10 +115,7 @@ fn char_definition_strips_nooovbow_entries() -> Result<()> {
     convert_char_definition(Cursor::new(input.as_bytes()), &mut output)?;
     let output = String::from_utf8(output)?;
 
-    assert!(output.contains("# comment\n"));
-    assert!(output.contains("0x0041..0x005A ALPHA #A-Z\n"));
-    assert!(!output.contains("0x0030..0x0039"));
-    assert!(output.contains("DEFAULT 0 1 0\n"));
+    assert_eq!(output, "DEFAULT 0 1 0\n\n0x0041..0x005A ALPHA\n");
     Ok(())
 }
 
@@ -130,6 +138,326 @@ fn char_definition_can_append_custom_rules() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn char_definition_appends_override_categories_and_split_ranges() -> Result<()> {
+    let base = concat!(
+        "DEFAULT 0 1 0\n",
+        "SPACE 0 1 0\n",
+        "NUMERIC 1 1 0\n",
+        "ALPHA 1 1 0\n",
+        "0x0020 SPACE\n",
+        "0x0030..0x007A ALPHA # digits are re-mapped below\n",
+        "0x0030..0x0039 NUMERIC\n",
+        "0xFF10..0xFF19 NUMERIC\n",
+    );
+    let dir = tempdir()?;
+    let append_path = dir.path().join("char.append.def");
+    fs::copy(
+        concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../../rules/ipadic-numeric-merge/char.append.def"
+        ),
+        &append_path,
+    )?;
+
+    let mut definition = CharDefinition::from_reader("char.def", base.as_bytes())?;
+    let counts = definition.merge_path(&append_path)?;
+    assert_eq!(
+        counts,
+        CharMergeCounts {
+            lines: 3,
+            dropped_ranges: 0
+        }
+    );
+    let numeric = definition.category("NUMERIC").expect("NUMERIC is declared");
+    assert!(numeric.invoke && !numeric.group);
+    assert_eq!(
+        definition.categories_of(0x003A),
+        Some(&["ALPHA".to_string()][..])
+    );
+    assert_eq!(definition.categories_of(0x3042), None);
+
+    let mut output = Vec::new();
+    definition.write(&mut output)?;
+    let output = String::from_utf8(output)?;
+    assert_eq!(
+        output,
+        concat!(
+            "DEFAULT 0 1 0\n",
+            "SPACE 0 1 0\n",
+            "NUMERIC 1 0 0\n",
+            "ALPHA 1 1 0\n",
+            "\n",
+            "0x0020 SPACE\n",
+            "0x0030..0x0039 NUMERIC\n",
+            "0x003A..0x007A ALPHA\n",
+            "0xFF10..0xFF19 NUMERIC\n",
+        )
+    );
+
+    // Re-reading the canonical form is a fixed point.
+    let mut again = Vec::new();
+    CharDefinition::from_reader("char.def", output.as_bytes())?.write(&mut again)?;
+    assert_eq!(String::from_utf8(again)?, output);
+    Ok(())
+}
+
+#[test]
+fn char_definition_coalesces_adjacent_ranges() -> Result<()> {
+    let mut definition = CharDefinition::from_reader(
+        "char.def",
+        "DEFAULT 0 1 0\nALPHA 1 1 0\n0x0041..0x0045 ALPHA ALPHA\n0x0046..0x005A ALPHA\n".as_bytes(),
+    )?;
+    definition.set_range(CharRange {
+        start: 0x0043,
+        end: 0x0043,
+        categories: vec!["DEFAULT".to_string()],
+    });
+    assert_eq!(
+        definition.ranges(),
+        [
+            CharRange {
+                start: 0x0041,
+                end: 0x0042,
+                categories: vec!["ALPHA".to_string()],
+            },
+            CharRange {
+                start: 0x0043,
+                end: 0x0043,
+                categories: vec!["DEFAULT".to_string()],
+            },
+            CharRange {
+                start: 0x0044,
+                end: 0x005A,
+                categories: vec!["ALPHA".to_string()],
+            },
+        ]
+    );
+
+    let error = CharDefinition::from_reader("char.def", "ALPHA 1 2 0\n".as_bytes()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to parse group='2' at char.def line 1"
+    );
+    Ok(())
+}
+
+#[test]
+fn lint_reports_char_and_unk_definition_problems() -> Result<()> {
+    let char_def = concat!(
+        "DEFAULT 0 1 0\n",
+        "SPACE 0 1 0\n",
+        "ALPHA 1 1 0\n",
+        "KANJI 0 0 2\n",
+        "0x0030..0x007A ALPHA\n",
+        "0x3005 NOOOVBOW\n",
+        "0x4E00..0x9FFF KANJI\n",
+        "0x20000..0x2A6DF KANJI\n",
+    );
+    let char_append = "0x0030..0x0039 NUMERIC\n0x0041..0x005A ALPHA\n";
+    let unk_def = concat!(
+        "DEFAULT,0,0,100,補助記号,一般,*,*,*,*\n",
+        "# comment\n",
+        "ALPHA,0,0,100,名詞,普通名詞,一般,*,*,*\n",
+        "SPACE,0,0,100,空白,*,*,*,*,*\n",
+        "HIRAGANA,0,0,100,名詞,普通名詞,一般,*,*,*\n",
+    );
+
+    let report = lint(
+        vec![
+            ("char.def".to_string(), char_def.as_bytes()),
+            ("char.append.def".to_string(), char_append.as_bytes()),
+        ],
+        vec![("unk.def".to_string(), unk_def.as_bytes())],
+    )?;
+    assert_eq!(report.errors(), 4);
+    assert_eq!(
+        report.lines(),
+        [
+            "error: char.append.def line 1: undeclared category NUMERIC",
+            "error: char.def line 8: range 0x20000..0x2A6DF is beyond U+FFFF",
+            "error: unk.def line 5: unk.def row for undeclared category HIRAGANA",
+            "error: category KANJI has no unk.def row",
+            "warning: category SPACE is declared but no range uses it",
+            "overlap: char.append.def line 1: 0x0030..0x0039 re-maps 0x0030..0x0039 from ALPHA (char.def line 5) to NUMERIC",
+        ]
+    );
+
+    // Vibrato 0.5 stores the length in 4 bits and the categories in an 18-bit set.
+    let mut char_def = String::from("DEFAULT 0 1 0\nKANJI 0 0 16\n0x4E00..0x9FFF KANJI\n");
+    let mut unk_def =
+        String::from("DEFAULT,0,0,100,補助記号,一般,*,*,*,*\nKANJI,0,0,100,名詞,*,*,*,*,*\n");
+    for i in 1..=17 {
+        char_def.push_str(&format!("C{} 0 1 0\n0x{:04X} C{}\n", i, 0x40 + i, i));
+        unk_def.push_str(&format!("C{},0,0,100,記号,*,*,*,*,*\n", i));
+    }
+    char_def.push_str("KANJI 0 0 2\n");
+    let report = lint(
+        vec![("char.def".to_string(), char_def.as_bytes())],
+        vec![("unk.def".to_string(), unk_def.as_bytes())],
+    )?;
+    assert_eq!(report.errors(), 2);
+    assert_eq!(
+        report.lines(),
+        [
+            "error: char.def line 2: category KANJI has length 16, above 15",
+            "error: char.def line 36: category C17 is beyond the 18 categories Vibrato supports",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn lint_subcommand_fails_on_errors() -> Result<()> {
+    let dir = tempdir()?;
+    let char_path = dir.path().join("char.def");
+    let unk_path = dir.path().join("unk.def");
+    fs::write(
+        &char_path,
+        "DEFAULT 0 1 0\nALPHA 1 1 0\n0x0041..0x005A ALPHA\n",
+    )?;
+    fs::write(&unk_path, "DEFAULT,0,0,100,補助記号,一般,*,*,*,*\n")?;
+
+    let cli = Cli::try_parse_from([
+        "sudachi-vibrato-converter",
+        "lint",
+        "--char-in",
+        char_path.to_str().unwrap(),
+        "--unk-in",
+        unk_path.to_str().unwrap(),
+    ])?;
+    let Commands::Lint(args) = cli.command else {
+        panic!("expected lint");
+    };
+    let error = run_lint(&args).unwrap_err();
+    assert_eq!(error.to_string(), "char.def/unk.def lint found 1 errors");
+
+    fs::write(
+        &unk_path,
+        "DEFAULT,0,0,100,補助記号,一般,*,*,*,*\nALPHA,0,0,100,名詞,普通名詞,一般,*,*,*\n",
+    )?;
+    assert_eq!(run_lint(&args)?, LintReport::default());
+    Ok(())
+}
+
+#[test]
+fn gen_char_assigns_unicode_categories_to_default_code_points() -> Result<()> {
+    let base = CharDefinition::from_reader(
+        "char.def",
+        concat!(
+            "DEFAULT 0 1 0\n",
+            "SYMBOL 1 1 0\n",
+            "KANJI 0 0 2\n",
+            "KANJINUMERIC 0 1 0\n",
+            "0x2600..0x26FF SYMBOL\n",
+            "0x4E00..0x9FFF KANJI\n",
+            "0x4E00 KANJINUMERIC KANJI\n",
+        )
+        .as_bytes(),
+    )?;
+    let categories = [
+        unicode_category("KANJI").unwrap(),
+        unicode_category("EMOJI").unwrap(),
+    ];
+
+    let fill = generate_char_definition(&base, &categories, CharGenMode::Fill)?;
+    let overlay = &fill.overlay;
+    assert_eq!(
+        overlay.categories_of(0x3400),
+        Some(&["KANJI".to_string()][..])
+    );
+    assert_eq!(overlay.categories_of(0x4E00), None);
+    assert_eq!(overlay.categories_of(0x2600), None);
+    assert_eq!(
+        overlay.categories_of(0x00A9),
+        Some(&["EMOJI".to_string()][..])
+    );
+    assert!(overlay.ranges().iter().all(|range| range.end <= 0xFFFF));
+    assert_eq!(fill.new_categories, ["EMOJI"]);
+    assert_eq!(overlay.category("EMOJI").map(|c| c.group), Some(true));
+
+    let ext_b = fill
+        .changes
+        .iter()
+        .find(|change| change.start == 0x20000)
+        .unwrap();
+    assert!(ext_b.beyond_bmp());
+    assert_eq!(
+        (ext_b.from.clone(), ext_b.to.clone()),
+        (vec!["DEFAULT".to_string()], vec!["KANJI".to_string()])
+    );
+    let lines = fill.lines();
+    assert!(lines
+        .iter()
+        .any(|line| line.starts_with("beyond U+FFFF, not written: DEFAULT -> KANJI: ")));
+    assert_eq!(
+        lines.last().map(String::as_str),
+        Some("new category EMOJI needs an unk.def row")
+    );
+
+    let replace = generate_char_definition(&base, &categories, CharGenMode::Override)?;
+    assert_eq!(
+        replace.overlay.categories_of(0x2600),
+        Some(&["EMOJI".to_string(), "SYMBOL".to_string()][..])
+    );
+    assert_eq!(
+        replace.overlay.categories_of(0x4E00),
+        Some(&["KANJI".to_string(), "KANJINUMERIC".to_string()][..])
+    );
+    assert_eq!(replace.overlay.categories_of(0x4E01), None);
+    Ok(())
+}
+
+#[test]
+fn gen_char_subcommand_writes_an_append_file_that_lints_clean() -> Result<()> {
+    let dir = tempdir()?;
+    let char_path = dir.path().join("char.def");
+    let unk_path = dir.path().join("unk.def");
+    let out_path = dir.path().join("char.unicode.def");
+    let report_path = dir.path().join("coverage.csv");
+    fs::write(
+        &char_path,
+        "DEFAULT 0 1 0\nHIRAGANA 0 1 2\n0x3041..0x3096 HIRAGANA\n0x309D NOOOVBOW\n",
+    )?;
+    fs::write(
+        &unk_path,
+        "DEFAULT,0,0,100,補助記号,一般,*,*,*,*\nHIRAGANA,0,0,100,名詞,普通名詞,一般,*,*,*\n",
+    )?;
+
+    let cli = Cli::try_parse_from([
+        "sudachi-vibrato-converter",
+        "gen-char",
+        "--char-in",
+        char_path.to_str().unwrap(),
+        "--out",
+        out_path.to_str().unwrap(),
+        "--report-out",
+        report_path.to_str().unwrap(),
+        "--category",
+        "HIRAGANA",
+    ])?;
+    let Commands::GenChar(args) = cli.command else {
+        panic!("expected gen-char");
+    };
+    let coverage = run_gen_char(&args)?;
+    assert!(coverage.new_categories.is_empty());
+
+    let overlay = CharDefinition::from_path(&out_path)?;
+    assert_eq!(
+        overlay.categories_of(0x309D),
+        Some(&["HIRAGANA".to_string()][..])
+    );
+    assert_eq!(overlay.categories_of(0x3041), None);
+    let report = fs::read_to_string(&report_path)?;
+    assert!(report.starts_with("code_points,count,from,to,written\n"));
+    assert!(report.contains("0x309D..0x309F,3,DEFAULT,HIRAGANA,true\n"));
+    assert!(report.contains(",DEFAULT,HIRAGANA,false\n"));
+
+    let report = lint_paths(&[char_path, out_path], &[unk_path])?;
+    assert_eq!(report.errors(), 0);
+    Ok(())
+}
+
 #[test]
 fn unk_definition_can_append_custom_rows_with_normalization() -> Result<()> {
     let base = "ALPHA,0,0,100,名詞,普通名詞,一般,*,*,*\n";
@@ -151,6 +479,41 @@ fn unk_definition_can_append_custom_rows_with_normalization() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn unk_append_ids_are_taken_from_the_row_with_the_same_features() -> Result<()> {
+    let base = concat!(
+        "DEFAULT,5968,5968,3857,補助記号,一般,*,*,*,*\n",
+        "SYMBOL,5968,5968,9000,補助記号,一般,*,*,*,*\n",
+        "ALPHA,5139,5139,11633,名詞,普通名詞,一般,*,*,*\n",
+    );
+    let append = concat!(
+        "# ids from DEFAULT\n",
+        "EMOJI,*,*,*,補助記号,一般,*,*,*,*\n",
+        "LATIN,*,*,20000,名詞,普通名詞,一般,*,*,*\n",
+        "KEPT,1,2,3,名詞,数詞,*,*,*,*\n",
+    );
+    let resolved = resolve_unknown_append_ids(base.as_bytes(), append.as_bytes())?;
+    assert_eq!(
+        String::from_utf8(resolved)?,
+        concat!(
+            "# ids from DEFAULT\n",
+            "EMOJI,5968,5968,3857,補助記号,一般,*,*,*,*\n",
+            "LATIN,5139,5139,20000,名詞,普通名詞,一般,*,*,*\n",
+            "KEPT,1,2,3,名詞,数詞,*,*,*,*\n",
+        )
+    );
+
+    let error = resolve_unknown_append_ids(
+        base.as_bytes(),
+        "NUMERIC,*,*,*,名詞,数詞,*,*,*,*\n".as_bytes(),
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains(
+        "no unk row with features 名詞,数詞,*,*,*,* to take the ids of NUMERIC at line 1"
+    ));
+    Ok(())
+}
+
 #[test]
 fn lex_numeric_pos_is_normalized_to_meishi_kazu() -> Result<()> {
     let input = "123,1,2,3,123,名詞,数詞,*,*,*,*,ヒャクニジュウサン\n";
@@ -171,6 +534,531 @@ fn lex_numeric_pos_is_normalized_to_meishi_kazu() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn lex_keeps_fine_grained_ipadic_pos() -> Result<()> {
+    let input = concat!(
+        "東京,0,0,100,東京,名詞,固有名詞,地名,一般,*,*,トウキョウ\n",
+        "勉強,0,0,100,勉強,名詞,普通名詞,サ変可能,*,*,*,ベンキョウ\n",
+        "は,0,0,100,は,助詞,係助詞,*,*,*,*,ハ\n",
+        "。,0,0,100,。,補助記号,句点,*,*,*,*,*\n"
+    );
+
+    let mut output = Vec::new();
+    let mut stats = ConversionStats::default();
+    convert_lexicon(Cursor::new(input.as_bytes()), &mut output, &mut stats)?;
+
+    let rows = parse_csv_rows(&output)?;
+    let pos: Vec<String> = rows.iter().map(|row| row[4..8].join(",")).collect();
+    assert_eq!(
+        pos,
+        [
+            "名詞,固有名詞,地域,一般",
+            "名詞,サ変接続,*,*",
+            "助詞,係助詞,*,*",
+            "記号,句点,*,*"
+        ]
+    );
+
+    let hit = &stats.pos_mapping["名詞,固有名詞,地名,一般"];
+    assert_eq!(hit.rows, 1);
+    assert!(hit.rule_line.is_some());
+    Ok(())
+}
+
+#[test]
+fn pos_mapping_prefers_priority_then_specificity() -> Result<()> {
+    let table = concat!(
+        "# comment\n",
+        "名詞,*,*,*,名詞,一般,*,*\n",
+        "名詞,普通名詞,*,*,名詞,サ変接続,*,*\n",
+        "名詞,*,*,*,名詞,固有名詞,一般,*,10\n",
+        "名詞,普通名詞,副詞可能,*,名詞,副詞可能,*,*,10\n"
+    );
+    let mapping = PosMapping::from_reader(table.as_bytes())?;
+    assert_eq!(mapping.rules().len(), 4);
+
+    let (pos, rule) = mapping.map(["名詞", "普通名詞", "一般", "*"]);
+    assert_eq!(pos, ["名詞", "固有名詞", "一般", "*"]);
+    assert_eq!(rule.map(|rule| rule.line), Some(4));
+
+    let (pos, rule) = mapping.map(["名詞", "普通名詞", "副詞可能", ""]);
+    assert_eq!(pos, ["名詞", "副詞可能", "*", "*"]);
+    assert_eq!(rule.map(|rule| rule.line), Some(5));
+
+    let (pos, rule) = mapping.map(["動詞", "一般", "*", "*"]);
+    assert_eq!(pos, ["その他", "*", "*", "*"]);
+    assert!(rule.is_none());
+    Ok(())
+}
+
+#[test]
+fn lex_uses_custom_pos_mapping_and_reports_hits() -> Result<()> {
+    let input = concat!(
+        "行く,0,0,100,行く,動詞,非自立可能,*,*,*,*,イク\n",
+        "来る,0,0,100,来る,動詞,非自立可能,*,*,*,*,クル\n",
+        "猫,0,0,100,猫,名詞,普通名詞,一般,*,*,*,ネコ\n"
+    );
+    let options = ConvertOptions {
+        pos_mapping: PosMapping::from_reader("動詞,非自立可能,*,*,動詞,非自立,*,*\n".as_bytes())?,
+        ..ConvertOptions::default()
+    };
+
+    let mut output = Vec::new();
+    let mut stats = ConversionStats::default();
+    convert_lexicon_with_options(
+        Cursor::new(input.as_bytes()),
+        &mut output,
+        &options,
+        &mut stats,
+    )?;
+
+    let rows = parse_csv_rows(&output)?;
+    assert_eq!(rows[0][5], "非自立");
+    assert_eq!(rows[2][4], "その他");
+
+    let dir = tempdir()?;
+    let report_path = dir.path().join("pos-map-report.csv");
+    stats.write_pos_mapping_report(&report_path)?;
+    let report = fs::read_to_string(&report_path)?;
+    assert!(report.contains("動詞,非自立可能,*,*,動詞,非自立,*,*,1,2\n"));
+    assert!(report.contains("名詞,普通名詞,一般,*,その他,*,*,*,*,1\n"));
+    Ok(())
+}
+
+#[test]
+fn lex_resolves_base_from_dictionary_form_word_id_across_sources() -> Result<()> {
+    let small =
+        "行く,1,1,10,行く,動詞,非自立可能,*,*,五段-カ行,終止形-一般,イク,行く,*,A,*,*,*,*\n";
+    let core = concat!(
+        "行っ,1,1,10,行っ,動詞,非自立可能,*,*,五段-カ行,連用形-促音便,イッ,行く,0,A,*,*,*,*\n",
+        "いっ,1,1,10,行っ,動詞,非自立可能,*,*,五段-カ行,連用形-促音便,イッ,行く,0,A,*,*,*,*\n",
+    );
+    let sources = vec![
+        LexiconSource::new("small_lex.csv", small.as_bytes().to_vec()),
+        LexiconSource::new("core_lex.csv", core.as_bytes().to_vec()),
+    ];
+
+    let mut output = Vec::new();
+    let mut stats = ConversionStats::default();
+    convert_lexicon_sources(
+        &sources,
+        &mut output,
+        &ConvertOptions::default(),
+        &mut stats,
+    )?;
+
+    let rows = parse_csv_rows(&output)?;
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0][10], "行く");
+    assert_eq!(rows[1][10], "行く");
+    assert_eq!(rows[2][10], "行く");
+    assert_eq!(stats.unresolved_base_refs, 0);
+    Ok(())
+}
+
+#[test]
+fn lex_counts_unresolved_dictionary_form_references() -> Result<()> {
+    let input =
+        "行っ,1,1,10,行っ,動詞,非自立可能,*,*,五段-カ行,連用形-促音便,イッ,行く,42,A,*,*,*,*\n";
+
+    let mut output = Vec::new();
+    let mut stats = ConversionStats::default();
+    convert_lexicon(Cursor::new(input.as_bytes()), &mut output, &mut stats)?;
+
+    let rows = parse_csv_rows(&output)?;
+    assert_eq!(rows[0][10], "行っ");
+    assert_eq!(stats.unresolved_base_refs, 1);
+    Ok(())
+}
+
+#[test]
+fn pron_is_derived_from_reading() {
+    let cases = [
+        ("トウキョウ", "名詞", "トーキョー"),
+        ("センセイ", "名詞", "センセー"),
+        ("オオキイ", "形容詞", "オーキイ"),
+        ("クウキ", "名詞", "クーキ"),
+        ("オカアサン", "名詞", "オカーサン"),
+        ("ツヅク", "動詞", "ツズク"),
+        ("ハナヂ", "名詞", "ハナジ"),
+        ("オモウ", "動詞", "オモウ"),
+        ("ハ", "助詞", "ワ"),
+        ("ヘ", "助詞", "エ"),
+        ("ニハ", "助詞", "ニワ"),
+        ("ハ", "名詞", "ハ"),
+        ("コンニチハ", "感動詞", "コンニチワ"),
+        ("*", "名詞", "*"),
+    ];
+    for (read, pos1, expected) in cases {
+        assert_eq!(
+            pronunciation_from_reading(read, pos1),
+            expected,
+            "{read} ({pos1})"
+        );
+    }
+}
+
+#[test]
+fn lex_and_appended_rows_get_derived_pron() -> Result<()> {
+    let input = "東京,1,1,10,東京,名詞,固有名詞,地名,一般,*,*,トウキョウ,東京\n";
+    let mut output = Vec::new();
+    let mut stats = ConversionStats::default();
+    convert_lexicon(Cursor::new(input.as_bytes()), &mut output, &mut stats)?;
+
+    let dir = tempdir()?;
+    let append_path = dir.path().join("lex.append.csv");
+    fs::write(
+        &append_path,
+        concat!(
+            "は,1,1,10,助詞,係助詞,*,*,*,*,は,ハ,ハ\n",
+            "へ,1,1,10,助詞,格助詞,一般,*,*,*,へ,ヘ\n",
+            "経緯,1,1,10,名詞,一般,*,*,*,*,経緯,ケイイ,イキサツ\n",
+        ),
+    )?;
+    append_lexicon_rows(&mut output, &[append_path])?;
+
+    let rows = parse_csv_rows(&output)?;
+    assert_eq!(rows[0][12], "トーキョー");
+    assert_eq!(rows[1][12], "ワ");
+    assert_eq!(rows[2].len(), 13);
+    assert_eq!(rows[2][12], "エ");
+    assert_eq!(rows[3][12], "イキサツ");
+    Ok(())
+}
+
+#[test]
+fn ctype_is_inferred_from_sudachi_type_and_lemma() {
+    let cases = [
+        ("五段-カ行", "書く", "カク", "五段・カ行イ音便"),
+        ("五段-カ行", "行く", "イッ", "五段・カ行促音便"),
+        ("五段-カ行", "出て行く", "デテイク", "五段・カ行促音便"),
+        ("五段-カ行", "行く", "ユク", "五段・カ行促音便ユク"),
+        ("五段-カ行", "ゆく", "ユキ", "五段・カ行促音便ユク"),
+        ("五段-ラ行", "走る", "ハシル", "五段・ラ行"),
+        ("五段-ラ行", "くださる", "クダサイ", "五段・ラ行特殊"),
+        (
+            "五段-ラ行",
+            "いらっしゃる",
+            "イラッシャル",
+            "五段・ラ行特殊",
+        ),
+        ("五段-ラ行", "ある", "アル", "五段・ラ行アル"),
+        ("五段-ワア行", "言う", "イウ", "五段・ワ行促音便"),
+        ("五段-ワア行", "問う", "トウ", "五段・ワ行ウ音便"),
+        ("上一段-カ行", "起きる", "オキル", "一段"),
+        ("下一段-ラ行", "くれる", "クレ", "一段・クレル"),
+        ("カ行変格", "来る", "キ", "カ変・来ル"),
+        ("サ行変格", "する", "シ", "サ変・スル"),
+        ("サ行変格", "勉強する", "ベンキョウスル", "サ変・−スル"),
+        ("サ行変格", "信ずる", "シンズル", "サ変・−ズル"),
+        ("形容詞", "高い", "タカイ", "形容詞・アウオ段"),
+        ("形容詞", "美しい", "ウツクシイ", "形容詞・イ段"),
+        ("形容詞", "いい", "イイ", "形容詞・イイ"),
+        ("助動詞-マス", "ます", "マス", "特殊・マス"),
+        ("文語下二段-ダ行", "出づ", "イヅ", "下二・ダ行"),
+        ("無変化型", "ぬ", "ヌ", "不変化型"),
+        ("不明", "謎", "ナゾ", "*"),
+    ];
+    for (ctype, base, read, expected) in cases {
+        assert_eq!(infer_ctype(ctype, base, read).0, expected, "{ctype} {base}");
+    }
+    assert!(infer_ctype("不明", "謎", "ナゾ").1);
+    assert!(!infer_ctype("*", "謎", "ナゾ").1);
+}
+
+/// `(surface, sudachi cform, ipadic cform)`
+type Paradigm = &'static [(&'static str, &'static str, &'static str)];
+
+#[test]
+fn cform_follows_ipadic_paradigms() {
+    let paradigms: &[(&str, Paradigm)] = &[
+        (
+            "五段・カ行イ音便",
+            &[
+                ("書か", "未然形-一般", "未然形"),
+                ("書こ", "意志推量形", "未然ウ接続"),
+                ("書き", "連用形-一般", "連用形"),
+                ("書い", "連用形-イ音便", "連用タ接続"),
+                ("書く", "終止形-一般", "基本形"),
+                ("書け", "仮定形-一般", "仮定形"),
+                ("書きゃ", "仮定形-融合", "仮定縮約１"),
+                ("書け", "命令形", "命令ｅ"),
+            ],
+        ),
+        (
+            "五段・ラ行",
+            &[
+                ("分かっ", "連用形-促音便", "連用タ接続"),
+                ("分かん", "未然形-撥音便", "未然特殊"),
+                ("分かん", "連体形-撥音便", "体言接続特殊"),
+            ],
+        ),
+        ("五段・マ行", &[("読ん", "連用形-撥音便", "連用タ接続")]),
+        (
+            "五段・ラ行特殊",
+            &[
+                ("ください", "連用形-イ音便", "連用形"),
+                ("くださっ", "連用形-促音便", "連用タ接続"),
+                ("ください", "命令形", "命令ｉ"),
+            ],
+        ),
+        (
+            "一段",
+            &[
+                ("起き", "未然形-一般", "未然形"),
+                ("起きろ", "命令形", "命令ｒｏ"),
+                ("起きよ", "命令形", "命令ｙｏ"),
+            ],
+        ),
+        ("カ変・来ル", &[("来い", "命令形", "命令ｉ")]),
+        (
+            "サ変・スル",
+            &[
+                ("さ", "未然形-サ", "未然レル接続"),
+                ("せ", "未然形-セ", "未然ヌ接続"),
+                ("しろ", "命令形", "命令ｒｏ"),
+                ("せよ", "命令形", "命令ｙｏ"),
+            ],
+        ),
+        (
+            "形容詞・アウオ段",
+            &[
+                ("高かろ", "意志推量形", "未然ウ接続"),
+                ("高く", "連用形-一般", "連用テ接続"),
+                ("高かっ", "連用形-促音便", "連用タ接続"),
+                ("高う", "連用形-ウ音便", "連用ゴザイ接続"),
+                ("高い", "終止形-一般", "基本形"),
+                ("高き", "連体形-一般", "体言接続"),
+                ("高けれ", "仮定形-一般", "仮定形"),
+                ("高けりゃ", "仮定形-融合", "仮定縮約１"),
+                ("高きゃ", "仮定形-融合", "仮定縮約２"),
+                ("高", "語幹-一般", "ガル接続"),
+            ],
+        ),
+        (
+            "特殊・ナイ",
+            &[
+                ("なく", "連用形-一般", "連用テ接続"),
+                ("なかっ", "連用形-促音便", "連用タ接続"),
+                ("ないで", "連用形-一般", "連用デ接続"),
+            ],
+        ),
+        (
+            "特殊・ダ",
+            &[
+                ("だろ", "意志推量形", "未然ウ接続"),
+                ("だっ", "連用形-促音便", "連用タ接続"),
+                ("で", "連用形-一般", "連用形"),
+                ("な", "連体形-一般", "体言接続"),
+            ],
+        ),
+        ("特殊・ヌ", &[("ず", "連用形-一般", "連用ニ接続")]),
+    ];
+
+    for (ctype, forms) in paradigms {
+        for (surface, cform, expected) in *forms {
+            let (actual, fallback) = infer_cform(cform, ctype, surface);
+            assert_eq!(actual, *expected, "{ctype} {surface} {cform}");
+            assert!(!fallback);
+        }
+    }
+    assert_eq!(infer_cform("不明", "*", "謎"), ("*".to_string(), true));
+}
+
+#[test]
+fn unidic_schema_keeps_sudachi_labels_for_lex_and_unk() -> Result<()> {
+    let options = ConvertOptions {
+        feature_schema: feature_schema_by_name("unidic")?,
+        ..ConvertOptions::default()
+    };
+
+    let input = "書い,1,1,10,書く,動詞,一般,*,*,五段-カ行,連用形-イ音便,カイ,書く,*\n";
+    let mut output = Vec::new();
+    let mut stats = ConversionStats::default();
+    convert_lexicon_with_options(
+        Cursor::new(input.as_bytes()),
+        &mut output,
+        &options,
+        &mut stats,
+    )?;
+    let rows = parse_csv_rows(&output)?;
+    assert_eq!(
+        rows[0][4..],
+        [
+            "動詞",
+            "一般",
+            "*",
+            "*",
+            "五段-カ行",
+            "連用形-イ音便",
+            "カイ",
+            "書く",
+            "書い",
+            "カイ"
+        ]
+    );
+    assert_eq!(stats.feature_schema, "unidic-v1");
+
+    let unk = "KANJI,0,0,100,名詞,普通名詞,一般,*,*,*\n";
+    let mut output = Vec::new();
+    convert_unknown_dictionary_with_options(Cursor::new(unk.as_bytes()), &mut output, &options)?;
+    let rows = parse_csv_rows(&output)?;
+    assert_eq!(rows[0].len(), 14);
+    assert_eq!(rows[0][4..7], ["名詞", "普通名詞", "一般"]);
+
+    assert!(feature_schema_by_name("ipadic").is_err());
+    Ok(())
+}
+
+#[test]
+fn mora_count_merges_small_kana() {
+    assert_eq!(mora_count("トーキョー"), Some(4));
+    assert_eq!(mora_count("キャッチ"), Some(3));
+    assert_eq!(mora_count("シンブン"), Some(4));
+    assert_eq!(mora_count("ヴァイオリン"), Some(5));
+    assert_eq!(mora_count("*"), None);
+    assert_eq!(mora_count("abc"), None);
+}
+
+#[test]
+fn lex_joins_accent_and_reports_unmatched_rows() -> Result<()> {
+    let accent = concat!(
+        "# surface,read,pos,accent,chain_rule,chain_flag\n",
+        "東京,トウキョウ,名詞,0,C1,*\n",
+        "箸,ハシ,*,1\n",
+        "橋,ハシ,動詞,2\n",
+        "\"あ,い\",アイ,名詞,1\n",
+        "ＡＢ,ＡＢ,*,1\n",
+    );
+    let options = ConvertOptions {
+        feature_schema: feature_schema_by_name("jpreprocess12")?,
+        accent: AccentDictionary::from_reader(accent.as_bytes())?,
+        collect_unmatched_accent: true,
+        ..ConvertOptions::default()
+    };
+    let input = concat!(
+        "東京,1,1,10,東京,名詞,固有名詞,地名,一般,*,*,トウキョウ,東京\n",
+        "箸,1,1,10,箸,名詞,普通名詞,一般,*,*,*,ハシ,箸\n",
+        "橋,1,1,10,橋,名詞,普通名詞,一般,*,*,*,ハシ,橋\n",
+        "\"あ,い\",1,1,10,\"あ,い\",名詞,普通名詞,一般,*,*,*,アイ,\"あ,い\"\n",
+        "ＡＢ,1,1,10,ＡＢ,名詞,普通名詞,一般,*,*,*,ＡＢ,ＡＢ\n",
+    );
+
+    let mut output = Vec::new();
+    let mut stats = ConversionStats::default();
+    let sidecars = convert_lexicon_with_options(
+        Cursor::new(input.as_bytes()),
+        &mut output,
+        &options,
+        &mut stats,
+    )?;
+
+    let rows = parse_csv_rows(&output)?;
+    assert_eq!(rows[0][12..], ["トーキョー", "0/4", "C1", "*"]);
+    assert_eq!(rows[1][13..], ["1/2", "*", "*"]);
+    assert_eq!(rows[2][13..], ["*/*", "*", "*"]);
+    assert_eq!(rows[3][13..], ["1/2", "*", "*"]);
+    // An entry whose row has no mora count gives no accent and is not matched.
+    assert_eq!(rows[4][13..], ["*/*", "*", "*"]);
+    assert_eq!(stats.accent_matched_rows, 3);
+    assert_eq!(stats.accent_unmatched_rows, 2);
+
+    let dir = tempdir()?;
+    let report = dir.path().join("accent-unmatched.csv");
+    sidecars.write_unmatched_accent_report(&report)?;
+    assert_eq!(
+        fs::read_to_string(report)?,
+        "surface,read,pos,rows\n橋,ハシ,名詞-一般,1\nＡＢ,ＡＢ,名詞-一般,1\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn accent_lookup_prefers_the_most_specific_pos() -> Result<()> {
+    let accent = concat!(
+        "日本,ニホン,名詞,0\n",
+        "日本,ニホン,名詞-固有名詞-地域,2\n",
+        "日本,ニホン,*,1\n",
+    );
+    let dictionary = AccentDictionary::from_reader(accent.as_bytes())?;
+    let pos = |levels: [&str; 4]| levels.map(str::to_string);
+    let lookup = |levels: [&str; 4]| {
+        dictionary
+            .lookup("日本", "ニホン", &pos(levels))
+            .map(|entry| entry.accent)
+    };
+    assert_eq!(lookup(["名詞", "固有名詞", "地域", "国"]), Some(2));
+    assert_eq!(lookup(["名詞", "一般", "*", "*"]), Some(0));
+    assert_eq!(lookup(["副詞", "一般", "*", "*"]), Some(1));
+    assert_eq!(dictionary.len(), 3);
+    Ok(())
+}
+
+#[test]
+fn user_lexicon_resolves_connection_ids_against_the_system_dictionary() -> Result<()> {
+    let system_lex = concat!(
+        "東京,5,6,100,東京,名詞,固有名詞,地名,一般,*,*,トウキョウ,東京,*,A,*,*,*,*\n",
+        "大阪,5,6,100,大阪,名詞,固有名詞,地名,一般,*,*,オオサカ,大阪,*,A,*,*,*,*\n",
+        "京都,7,7,100,京都,名詞,固有名詞,地名,一般,*,*,キョウト,京都,*,A,*,*,*,*\n",
+        "行く,2,2,100,行く,動詞,非自立可能,*,*,五段-カ行,終止形-一般,イク,行く,*,A,*,*,*,*\n",
+    );
+    let system = SystemLexicon::from_sources(
+        &[LexiconSource::new(
+            "system.csv",
+            system_lex.as_bytes().to_vec(),
+        )],
+        MatrixSize::from_matrix_def(Cursor::new("8 9\n0 0 0\n"))?,
+    )?;
+    assert_eq!(
+        system.connection_ids(&["名詞", "固有名詞", "地名", "一般", "*", "*"]),
+        Some((5, 6))
+    );
+
+    let user_lex = concat!(
+        "品川,*,*,50,品川,名詞,固有名詞,地名,一般,*,*,シナガワ,品川,*,A,*,*,*,*\n",
+        "いく,3,3,50,いく,動詞,非自立可能,*,*,五段-カ行,終止形-一般,イク,行く,3,A,*,*,*,*\n",
+        "いっ,3,3,50,いっ,動詞,非自立可能,*,*,五段-カ行,連用形-促音便,イッ,行く,U1,A,*,*,*,*\n",
+    );
+    let mut output = Vec::new();
+    let mut stats = ConversionStats::default();
+    convert_user_lexicon(
+        &[LexiconSource::new("user.csv", user_lex.as_bytes().to_vec())],
+        &system,
+        &mut output,
+        &ConvertOptions::default(),
+        &mut stats,
+    )?;
+
+    let rows = parse_csv_rows(&output)?;
+    assert_eq!(rows[0][..4], ["品川", "5", "6", "50"]);
+    assert_eq!(rows[1][10], "行く");
+    assert_eq!(rows[2][10], "いく");
+    assert_eq!(stats.resolved_connection_id_rows, 1);
+    assert_eq!(stats.unresolved_base_refs, 0);
+
+    for (row, message) in [
+        (
+            "梅田,9,1,50,梅田,名詞,固有名詞,地名,一般,*,*,ウメダ,梅田,*,A,*,*,*,*\n",
+            "exceed the system matrix size",
+        ),
+        (
+            "梅田,*,*,50,梅田,名詞,固有名詞,人名,一般,*,*,ウメダ,梅田,*,A,*,*,*,*\n",
+            "no connection id for POS",
+        ),
+    ] {
+        let error = convert_user_lexicon(
+            &[LexiconSource::new("user.csv", row.as_bytes().to_vec())],
+            &system,
+            Vec::new(),
+            &ConvertOptions::default(),
+            &mut ConversionStats::default(),
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", error).contains(message), "{:#}", error);
+    }
+    Ok(())
+}
+
 #[test]
 fn rewrite_definition_is_copied_and_appended() -> Result<()> {
     let dir = tempdir()?;
@@ -224,3 +1112,224 @@ fn parse_csv_rows(bytes: &[u8]) -> Result<Vec<Vec<String>>> {
 
     Ok(rows)
 }
+
+#[test]
+fn stats_json_report_breaks_down_fallbacks_and_skips() -> Result<()> {
+    let input = concat!(
+        "語,1,2,3,語,動詞,一般,*,*,謎活用,謎形,ゴ,語\n",
+        "語る,1,2,3,語る,動詞,一般,*,*,謎活用,終止形-一般,カタル,語る\n",
+        "負,-1,2,3,負,名詞,普通名詞,一般,*,*,*,フ,負\n",
+    );
+
+    let mut stats = ConversionStats::default();
+    convert_lexicon(Cursor::new(input.as_bytes()), Vec::new(), &mut stats)?;
+    assert_eq!(stats.fallback_ctype_values["謎活用"].rows, 2);
+    assert_eq!(stats.fallback_cform_values["謎形"].rows, 1);
+    assert_eq!(stats.skipped_rows["negative_conn_ids"], 1);
+    assert_eq!(stats.pos_mapping["動詞,一般,*,*"].rows, 2);
+
+    let dir = tempdir()?;
+    let unk_path = dir.path().join("unk.def");
+    let unk = "DEFAULT,0,0,100,補助記号,一般,*,*,*,*\n";
+    fs::write(&unk_path, unk)?;
+    stats.record_input("unk", &unk_path)?;
+    assert_eq!(stats.inputs[0].role, "unk");
+    assert_eq!(stats.inputs[0].file_name, "unk.def");
+    assert_eq!(stats.inputs[0].sha256, sha256_hex(unk.as_bytes()));
+
+    let json_path = dir.path().join("stats.json");
+    stats.write_json_file(&json_path)?;
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&json_path)?)?;
+    assert_eq!(json["fallback_ctype_values"]["謎活用"]["rows"], 2);
+    assert_eq!(json["skipped_rows"]["negative_conn_ids"], 1);
+    assert_eq!(ConversionStats::read_json_file(&json_path)?, stats);
+    Ok(())
+}
+
+#[test]
+fn unk_char_and_append_converters_report_counts() -> Result<()> {
+    let unk = "DEFAULT,0,0,100,補助記号,一般,*,*,*,*\n# comment\nALPHA,0,0,100,名詞,普通名詞,一般,*,*,*\n";
+    assert_eq!(
+        convert_unknown_dictionary(Cursor::new(unk.as_bytes()), Vec::new())?,
+        2
+    );
+
+    let char_def = "DEFAULT 0 1 0\n0x0030..0x0039 NOOOVBOW\n0x0041..0x005A ALPHA NOOOVBOW\n";
+    assert_eq!(
+        convert_char_definition(Cursor::new(char_def.as_bytes()), Vec::new())?,
+        1
+    );
+
+    let dir = tempdir()?;
+    let append_path = dir.path().join("char.append.def");
+    fs::write(
+        &append_path,
+        "0xFF10..0xFF19 NUMERIC\n0xFF21..0xFF3A ALPHA\n",
+    )?;
+    assert_eq!(
+        append_text_files_as_lines(&mut Vec::new(), &[append_path])?,
+        2
+    );
+    Ok(())
+}
+
+#[test]
+fn fallback_report_lists_unmapped_values_with_examples() -> Result<()> {
+    let input = concat!(
+        "語,1,2,3,語,動詞,一般,*,*,謎活用,謎形,ゴ,語\n",
+        "語る,1,2,3,語る,動詞,一般,*,*,謎活用,終止形-一般,カタル,語る\n",
+        "語る,1,2,3,語る,動詞,一般,*,*,謎活用,終止形-一般,カタル,語る\n",
+        "謎,1,2,3,謎,新品詞,*,*,*,*,*,ナゾ,謎\n",
+    );
+
+    let mut stats = ConversionStats::default();
+    convert_lexicon(Cursor::new(input.as_bytes()), Vec::new(), &mut stats)?;
+    assert_eq!(stats.fallback_pos_rows, 1);
+    assert_eq!(
+        stats.fallback_ctype_values["謎活用"].examples,
+        ["語", "語る"]
+    );
+
+    let report = stats.fallback_report();
+    let rows: Vec<_> = report
+        .iter()
+        .map(|entry| (entry.kind, entry.value.as_str(), entry.rows))
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            ("pos", "新品詞,*,*,*", 1),
+            ("ctype", "謎活用", 3),
+            ("cform", "謎形", 1),
+        ]
+    );
+    assert_eq!(report[1].rate, 0.75);
+
+    let dir = tempdir()?;
+    let csv_path = dir.path().join("fallback_report.csv");
+    stats.write_fallback_report(&csv_path)?;
+    let csv = fs::read_to_string(&csv_path)?;
+    assert!(csv.starts_with("kind,value,rows,rate,examples\n"));
+    assert!(csv.contains("ctype,謎活用,3,0.750000,語|語る\n"));
+
+    let json_path = dir.path().join("fallback_report.json");
+    stats.write_fallback_report(&json_path)?;
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&json_path)?)?;
+    assert_eq!(json[0]["kind"], "pos");
+    assert_eq!(json[0]["examples"][0], "謎");
+
+    assert!(stats.check_fallback_rate(0.75).is_ok());
+    let error = stats.check_fallback_rate(0.5).unwrap_err();
+    assert!(error.to_string().contains("ctype 3/4 (0.750000)"));
+    assert!(!error.to_string().contains("pos"));
+    Ok(())
+}
+
+#[test]
+fn lenient_error_policies_drop_malformed_rows() -> Result<()> {
+    let lex = concat!(
+        "語,1,2,3,語,名詞,普通名詞,一般,*,*,*,ゴ,語\n",
+        "短い,1,2\n",
+        "壊れ,x,2,3,壊れ,名詞,普通名詞,一般,*,*,*,コワレ,壊れ\n",
+    );
+    let strict = convert_lexicon(
+        Cursor::new(lex.as_bytes()),
+        Vec::new(),
+        &mut ConversionStats::default(),
+    );
+    assert!(strict.is_err());
+
+    let options = ConvertOptions {
+        on_error: ErrorPolicy::Skip,
+        ..ConvertOptions::default()
+    };
+    let mut stats = ConversionStats::default();
+    convert_lexicon_with_options(
+        Cursor::new(lex.as_bytes()),
+        Vec::new(),
+        &options,
+        &mut stats,
+    )?;
+    assert_eq!(stats.written, 1);
+    assert_eq!(stats.rejected_rows["lex.csv"], 2);
+    assert!(stats.rejects.is_empty());
+
+    let options = ConvertOptions {
+        on_error: ErrorPolicy::Collect,
+        ..ConvertOptions::default()
+    };
+    let unk = "DEFAULT,0,0,100,補助記号,一般,*,*,*,*\nALPHA,0,zero,100,名詞,普通名詞,一般,*,*,*\n";
+    let mut output = Vec::new();
+    let written = convert_unknown_dictionary_with_stats(
+        "unk.def",
+        Cursor::new(unk.as_bytes()),
+        &mut output,
+        &options,
+        &mut stats,
+    )?;
+    assert_eq!(written, 1);
+    assert_eq!(stats.rejected_rows["unk.def"], 1);
+    assert_eq!(
+        stats.rejects,
+        [RejectedRow {
+            file: "unk.def".to_string(),
+            line: 2,
+            reason: "failed to parse right_id='zero' at line 2".to_string(),
+            raw: "ALPHA,0,zero,100,名詞,普通名詞,一般,*,*,*".to_string(),
+        }]
+    );
+    Ok(())
+}
+
+#[test]
+fn convert_collects_rejected_rows_into_a_sidecar() -> Result<()> {
+    let dir = tempdir()?;
+    let path = |name: &str| dir.path().join(name).display().to_string();
+    fs::write(
+        path("lex.csv"),
+        "語,1,2,3,語,名詞,普通名詞,一般,*,*,*,ゴ,語\n短い,1,2\n",
+    )?;
+    fs::write(path("unk.def"), "DEFAULT,0,0,100,補助記号,一般,*,*,*,*\n")?;
+    fs::write(path("char.def"), "DEFAULT 0 1 0\n")?;
+
+    let mut argv = vec![
+        "sudachi-vibrato-converter".to_string(),
+        "convert".to_string(),
+    ];
+    for (flag, name) in [
+        ("--lex-in", "lex.csv"),
+        ("--lex-out", "lex.out.csv"),
+        ("--unk-in", "unk.def"),
+        ("--unk-out", "unk.out.def"),
+        ("--char-in", "char.def"),
+        ("--char-out", "char.out.def"),
+        ("--stats-out", "stats.env"),
+        ("--stats-json-out", "stats.json"),
+    ] {
+        argv.push(flag.to_string());
+        argv.push(path(name));
+    }
+    argv.push("--on-error=collect".to_string());
+    assert!(
+        Cli::try_parse_from(&argv).is_err(),
+        "collect requires --rejects-out"
+    );
+
+    argv.push("--rejects-out".to_string());
+    argv.push(path("rejects.csv"));
+    let Commands::Convert(args) = Cli::try_parse_from(&argv)?.command else {
+        panic!("expected the convert subcommand");
+    };
+    let (stats, _) = run_convert(&args)?;
+    assert_eq!(stats.written, 1);
+
+    let rejects = fs::read_to_string(path("rejects.csv"))?;
+    assert_eq!(
+        rejects,
+        "file,line,reason,raw\nlex.csv,2,\"invalid lex row at lex.csv line 2: expected >=11 columns, got 3\",\"短い,1,2\"\n"
+    );
+    assert!(fs::read_to_string(path("stats.env"))?.contains("rejected_rows=1\n"));
+    let json = ConversionStats::read_json_file(path("stats.json"))?;
+    assert_eq!(json.rejected_rows["lex.csv"], 1);
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use anyhow::Result;
+use csv::ReaderBuilder;
+use sudachi_vibrato_converter::sudachi_dic::SYSTEM_DICT_VERSION_2;
+use sudachi_vibrato_converter::{
+    convert_lexicon_sources, ConversionStats, ConvertOptions, DictionaryHeader, Grammar,
+    SudachiDictionary, SudachiWord,
+};
+
+fn pos(values: [&str; 6]) -> [String; 6] {
+    values.map(str::to_string)
+}
+
+fn word(surface: &str, headword: &str, pos_id: u16, reading: &str) -> SudachiWord {
+    SudachiWord {
+        surface: surface.to_string(),
+        left_id: 1,
+        right_id: 1,
+        cost: 100,
+        headword: headword.to_string(),
+        pos_id,
+        normalized_form: headword.to_string(),
+        dictionary_form_word_id: -1,
+        reading_form: reading.to_string(),
+        ..SudachiWord::default()
+    }
+}
+
+fn fixture() -> SudachiDictionary {
+    let mut iku = word("行く", "行く", 0, "イク");
+    iku.synonym_group_ids = vec![7];
+    let mut itt = word("行っ", "行っ", 1, "イッ");
+    itt.dictionary_form_word_id = 0;
+    let mut tokyo_to = word("東京都", "東京都", 2, "トウキョウト");
+    tokyo_to.a_unit_split = vec![3, 4];
+    let tokyo = word("東京", "東京", 2, "トウキョウ");
+    let to = word("都", "都", 3, "ト");
+    let mut miyako = word("都", "都", 3, "ミヤコ");
+    miyako.left_id = -1;
+
+    SudachiDictionary {
+        header: DictionaryHeader {
+            version: SYSTEM_DICT_VERSION_2,
+            create_time: 1_700_000_000,
+            description: "fixture".to_string(),
+        },
+        grammar: Grammar {
+            pos: vec![
+                pos(["動詞", "非自立可能", "*", "*", "五段-カ行", "終止形-一般"]),
+                pos(["動詞", "非自立可能", "*", "*", "五段-カ行", "連用形-促音便"]),
+                pos(["名詞", "固有名詞", "地名", "一般", "*", "*"]),
+                pos(["接尾辞", "名詞的", "一般", "*", "*", "*"]),
+            ],
+            left_size: 2,
+            right_size: 3,
+            matrix: vec![0, 1, 2, 3, 4, 5],
+        },
+        words: vec![iku, itt, tokyo_to, tokyo, to, miyako],
+    }
+}
+
+#[test]
+fn binary_dictionary_round_trips_through_writer_and_reader() -> Result<()> {
+    let dictionary = fixture();
+    let bytes = dictionary.to_bytes()?;
+    let read = SudachiDictionary::from_bytes(&bytes)?;
+    assert_eq!(read, dictionary);
+    assert_eq!(read.grammar.cost(1, 2), 5);
+    Ok(())
+}
+
+#[test]
+fn binary_dictionary_feeds_conversion_and_matrix_def() -> Result<()> {
+    let dictionary = SudachiDictionary::from_bytes(&fixture().to_bytes()?)?;
+
+    let raw = parse_csv_rows(&dictionary.to_lexicon_csv()?)?;
+    assert_eq!(raw.len(), 6);
+    assert_eq!(raw[0].len(), 19);
+    assert_eq!(raw[0][18], "7");
+    assert_eq!(raw[1][13], "0");
+    assert_eq!(raw[2][14..16], ["B", "3/4"]);
+
+    let mut output = Vec::new();
+    let mut stats = ConversionStats::default();
+    convert_lexicon_sources(
+        &[dictionary.to_lexicon_source("system_fixture.dic")?],
+        &mut output,
+        &ConvertOptions::default(),
+        &mut stats,
+    )?;
+    let rows = parse_csv_rows(&output)?;
+    assert_eq!(stats.written, 5);
+    assert_eq!(stats.skipped_negative_conn_ids, 1);
+    assert_eq!(rows[1][0], "行っ");
+    assert_eq!(rows[1][8..11], ["五段・カ行促音便", "連用タ接続", "行く"]);
+
+    let mut matrix_def = Vec::new();
+    dictionary.grammar.write_matrix_def(&mut matrix_def)?;
+    assert_eq!(
+        String::from_utf8(matrix_def)?,
+        "2 3\n0 0 0\n0 1 2\n0 2 4\n1 0 1\n1 1 3\n1 2 5\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn binary_dictionary_rejects_unknown_versions() {
+    let mut bytes = fixture().to_bytes().unwrap();
+    bytes[..8].copy_from_slice(&0u64.to_le_bytes());
+    assert!(SudachiDictionary::from_bytes(&bytes).is_err());
+}
+
+fn parse_csv_rows(bytes: &[u8]) -> Result<Vec<Vec<String>>> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(bytes);
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        rows.push(record?.iter().map(str::to_string).collect());
+    }
+    Ok(rows)
+}
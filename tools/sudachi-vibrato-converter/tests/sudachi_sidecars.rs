@@ -0,0 +1,468 @@
+use std::fs;
+use std::io::Cursor;
+
+use anyhow::Result;
+use clap::Parser;
+use sudachi_vibrato_converter::cli::{Cli, Commands};
+use sudachi_vibrato_converter::pipeline::run_convert;
+use sudachi_vibrato_converter::{
+    compile_dictionary, convert_lexicon_sources, AccentDictionary, ConversionStats, ConvertOptions,
+    ExpansionControl, LexemeForm, LexiconSidecars, LexiconSource, NormalizedForms, Resplitter,
+    SplitInfo, SplitMode, SplitWord, SynonymDictionary, SynonymExpander, SynonymGroups,
+    TaigenYougen, UnitTarget, WordIds, WordOrigin, WordUnit, WordUnits,
+};
+use tempfile::tempdir;
+use vibrato::dictionary::{LexType, WordIdx};
+use vibrato::Tokenizer;
+
+const LEX: &str = concat!(
+    "東京都,5,5,100,東京都,名詞,固有名詞,地名,一般,*,*,トウキョウト,東京都,*,B,1/2,*,1/2,*\n",
+    "東京,-1,-1,0,東京,名詞,固有名詞,地名,一般,*,*,トウキョウ,東京,*,A,*,*,*,*\n",
+    "都,-1,-1,0,都,名詞,普通名詞,一般,*,*,*,ト,都,*,A,*,*,*,*\n",
+    "壊れ,-1,3,0,壊れ,名詞,普通名詞,一般,*,*,*,コワレ,壊れ,*,A,*,*,*,*\n",
+);
+
+fn convert(options: &ConvertOptions) -> Result<(String, ConversionStats, LexiconSidecars)> {
+    let source = LexiconSource::new("small_lex.csv", LEX.as_bytes().to_vec());
+    let mut output = Vec::new();
+    let mut stats = ConversionStats::default();
+    let sidecars = convert_lexicon_sources(&[source], &mut output, options, &mut stats)?;
+    Ok((String::from_utf8(output)?, stats, sidecars))
+}
+
+#[test]
+fn split_only_rows_are_kept_in_split_info() -> Result<()> {
+    let options = ConvertOptions {
+        collect_split_info: true,
+        ..ConvertOptions::default()
+    };
+    let (lex, stats, sidecars) = convert(&options)?;
+
+    assert_eq!(lex.lines().count(), 1);
+    assert_eq!(stats.skipped_negative_conn_ids, 1);
+    assert_eq!(stats.split_only_rows, 2);
+    assert_eq!(stats.skipped_rows["split_only"], 2);
+    assert_eq!(stats.skipped_rows["negative_conn_ids"], 1);
+    // Split-only rows do not count towards the lexicon breakdowns.
+    assert_eq!(
+        stats
+            .pos_mapping
+            .values()
+            .map(|hit| hit.rows)
+            .sum::<usize>(),
+        1
+    );
+
+    let info = &sidecars.split_info;
+    assert_eq!(info.len(), 2);
+    assert!(info.get(0).is_none());
+    assert!(info.get(3).is_none());
+    let tokyo = info.get(1).expect("word 1 is split-only");
+    assert_eq!(tokyo.surface, "東京");
+    assert_eq!(tokyo.pos, ["名詞", "固有名詞", "地名", "一般"]);
+    assert_eq!(tokyo.reading, "トウキョウ");
+    assert_eq!(tokyo.normalized_form, "東京");
+    assert_eq!(
+        tokyo.feature(),
+        "名詞,固有名詞,地域,一般,*,*,東京,トウキョウ,トーキョー"
+    );
+
+    let mut csv = Vec::new();
+    info.write_csv(&mut csv)?;
+    assert!(String::from_utf8(csv.clone())?.starts_with(
+        "word_id,surface,pos1,pos2,pos3,pos4,ctype,cform,reading,normalized_form\n1,東京,"
+    ));
+    assert_eq!(&SplitInfo::from_reader(Cursor::new(csv))?, info);
+    Ok(())
+}
+
+#[test]
+fn split_info_is_only_collected_on_request() -> Result<()> {
+    let (_, stats, sidecars) = convert(&ConvertOptions::default())?;
+    assert_eq!(stats.split_only_rows, 2);
+    assert!(sidecars.split_info.is_empty());
+    Ok(())
+}
+
+#[test]
+fn split_only_rows_count_towards_fallbacks() -> Result<()> {
+    let lex_input = concat!(
+        "行く,0,0,100,行く,動詞,非自立可能,*,*,五段-カ行,終止形-一般,イク,行く,*,A,*,*,*,*\n",
+        "謎る,-1,-1,0,謎る,動詞,一般,*,*,謎活用,終止形-一般,ナゾル,謎る,99,A,*,*,*,*\n",
+    );
+    let options = ConvertOptions {
+        accent: AccentDictionary::from_reader("謎る,ナゾル,*,1\n".as_bytes())?,
+        collect_unmatched_accent: true,
+        collect_split_info: true,
+        ..ConvertOptions::default()
+    };
+    let mut stats = ConversionStats::default();
+    let sidecars = convert_lexicon_sources(
+        &[LexiconSource::new(
+            "small_lex.csv",
+            lex_input.as_bytes().to_vec(),
+        )],
+        Vec::new(),
+        &options,
+        &mut stats,
+    )?;
+    assert_eq!(stats.written, 1);
+    assert_eq!(stats.fallback_ctype_rows, 1);
+    assert_eq!(stats.fallback_ctype_values["謎活用"].examples, ["謎る"]);
+    // Nothing but the fallbacks is counted for the split-only row.
+    assert_eq!(stats.normalized_pos_rows, 1);
+    assert_eq!(
+        stats
+            .pos_mapping
+            .values()
+            .map(|hit| hit.rows)
+            .sum::<usize>(),
+        1
+    );
+    assert_eq!(stats.unresolved_base_refs, 0);
+    assert_eq!(stats.accent_matched_rows, 0);
+    assert_eq!(stats.accent_unmatched_rows, 1);
+    assert_eq!(
+        sidecars.unmatched_accent.keys().collect::<Vec<_>>(),
+        [&[
+            "行く".to_string(),
+            "イク".to_string(),
+            "動詞-自立".to_string()
+        ]]
+    );
+    // It is still collected for split_info.csv.
+    assert_eq!(sidecars.split_info.len(), 1);
+    // The rate is taken over the written and the split-only rows.
+    assert!(stats.check_fallback_rate(0.5).is_ok());
+    assert!(stats.check_fallback_rate(0.4).is_err());
+    Ok(())
+}
+
+const SPLIT_LEX: &str = concat!(
+    "東京都,0,0,100,東京都,名詞,固有名詞,地名,一般,*,*,トウキョウト,東京都,*,B,1/2,*,1/2,*\n",
+    "東京,-1,-1,0,東京,名詞,固有名詞,地名,一般,*,*,トウキョウ,東京,*,A,*,*,*,*\n",
+    "都,0,0,5000,都,名詞,普通名詞,一般,*,*,*,ト,都,*,A,*,*,*,*\n",
+    "に,0,0,100,に,助詞,格助詞,*,*,*,*,ニ,に,*,A,*,*,*,*\n",
+    "大阪府,0,0,100,大阪府,名詞,固有名詞,地名,一般,*,*,オオサカフ,大阪府,*,B,99/2,*,*,*\n",
+);
+
+fn convert_with_units() -> Result<(String, ConversionStats, LexiconSidecars)> {
+    let source = LexiconSource::new("small_lex.csv", SPLIT_LEX.as_bytes().to_vec());
+    let options = ConvertOptions {
+        collect_word_units: true,
+        ..ConvertOptions::default()
+    };
+    let mut output = Vec::new();
+    let mut stats = ConversionStats::default();
+    let sidecars = convert_lexicon_sources(&[source], &mut output, &options, &mut stats)?;
+    Ok((String::from_utf8(output)?, stats, sidecars))
+}
+
+#[test]
+fn word_units_point_at_rows_and_split_only_entries() -> Result<()> {
+    let (_, stats, sidecars) = convert_with_units()?;
+    let units = &sidecars.word_units;
+
+    assert_eq!(units.len(), 4);
+    // Split-only rows are collected for the units even without --split-info-out.
+    assert_eq!(sidecars.split_info.len(), 1);
+    assert_eq!(
+        units.units(0, SplitMode::A),
+        [
+            WordUnit {
+                target: UnitTarget::SplitOnly(1),
+                chars: 2
+            },
+            WordUnit {
+                target: UnitTarget::Row(1),
+                chars: 1
+            },
+        ]
+    );
+    assert!(units.units(0, SplitMode::B).is_empty());
+    assert!(units.units(0, SplitMode::C).is_empty());
+    assert!(units.units(2, SplitMode::A).is_empty());
+    // Word 99 does not exist, so 大阪府 keeps no A split.
+    assert_eq!(stats.unresolved_split_rows, 1);
+    assert!(units.units(3, SplitMode::A).is_empty());
+    assert!(units.units(4, SplitMode::A).is_empty());
+
+    assert_eq!(&WordUnits::from_bytes(&units.to_bytes()?)?, units);
+    let error = WordUnits::from_bytes(b"SVXX\x01\0\0\0").unwrap_err();
+    assert!(error.to_string().contains("bad magic"), "{}", error);
+    Ok(())
+}
+
+#[test]
+fn resplitter_yields_a_units_with_offsets_and_features() -> Result<()> {
+    let (lex, _, sidecars) = convert_with_units()?;
+    let dictionary = compile_dictionary(
+        lex.as_bytes(),
+        b"1 1\n0 0 0\n",
+        b"DEFAULT 0 1 0\n",
+        "DEFAULT,0,0,100,記号,一般,*,*,*,*,*,*,*\n".as_bytes(),
+    )?;
+    let tokenizer = Tokenizer::new(dictionary);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("東京都に");
+    worker.tokenize();
+
+    let splitter = Resplitter::new(
+        tokenizer.dictionary(),
+        &sidecars.word_units,
+        &sidecars.split_info,
+    );
+    let surfaces = |mode| -> Vec<String> {
+        splitter
+            .split_worker(&worker, mode)
+            .into_iter()
+            .map(|token| token.surface)
+            .collect()
+    };
+    assert_eq!(surfaces(SplitMode::C), ["東京都", "に"]);
+    assert_eq!(surfaces(SplitMode::B), ["東京都", "に"]);
+    assert_eq!(surfaces(SplitMode::A), ["東京", "都", "に"]);
+
+    let units = splitter.split(&worker.token(0), SplitMode::A);
+    assert_eq!(units[0].range_char, 0..2);
+    assert_eq!(units[0].range_byte, 0..6);
+    assert_eq!(units[0].word, SplitWord::SplitOnly(1));
+    assert_eq!(
+        units[0].feature,
+        sidecars.split_info.get(1).unwrap().feature()
+    );
+    assert_eq!(units[1].range_char, 2..3);
+    assert_eq!(units[1].range_byte, 6..9);
+    assert_eq!(
+        units[1].word,
+        SplitWord::Lexicon(WordIdx {
+            lex_type: LexType::System,
+            word_id: 1
+        })
+    );
+    assert!(
+        units[1].feature.starts_with("名詞,一般,"),
+        "{}",
+        units[1].feature
+    );
+    Ok(())
+}
+
+#[test]
+fn normalized_forms_fall_back_to_the_surface() -> Result<()> {
+    let lex_input = concat!(
+        "打込む,0,0,100,打込む,動詞,一般,*,*,五段-マ行,終止形-一般,ウチコム,打ち込む,*,A,*,*,*,*\n",
+        "ｶﾞｰﾃﾞﾆﾝｸﾞ,0,0,100,ｶﾞｰﾃﾞﾆﾝｸﾞ,名詞,普通名詞,一般,*,*,*,ガーデニング,ガーデニング,*,A,*,*,*,*\n",
+        "に,0,0,100,に,助詞,格助詞,*,*,*,*,ニ,に,*,A,*,*,*,*\n",
+    );
+    let options = ConvertOptions {
+        collect_normalized_forms: true,
+        ..ConvertOptions::default()
+    };
+    let mut lex = Vec::new();
+    let mut stats = ConversionStats::default();
+    let sidecars = convert_lexicon_sources(
+        &[LexiconSource::new(
+            "small_lex.csv",
+            lex_input.as_bytes().to_vec(),
+        )],
+        &mut lex,
+        &options,
+        &mut stats,
+    )?;
+    let forms = NormalizedForms::from_bytes(&sidecars.normalized_forms.to_bytes()?)?;
+    assert_eq!(forms, sidecars.normalized_forms);
+    assert_eq!(forms.len(), 3);
+    assert_eq!(forms.get(0), Some("打ち込む"));
+    assert_eq!(forms.get(2), None);
+
+    let dictionary = compile_dictionary(
+        &lex,
+        b"1 1\n0 0 0\n",
+        b"DEFAULT 0 1 0\n",
+        "DEFAULT,0,0,100,記号,一般,*,*,*,*,*,*,*\n".as_bytes(),
+    )?;
+    let tokenizer = Tokenizer::new(dictionary);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("ｶﾞｰﾃﾞﾆﾝｸﾞに打込むX");
+    worker.tokenize();
+    let normalized: Vec<&str> = (0..worker.num_tokens())
+        .map(|i| forms.of_token(&worker.token(i)))
+        .collect();
+    assert_eq!(normalized, ["ガーデニング", "に", "打ち込む", "X"]);
+
+    let split_info = SplitInfo::default();
+    let units = WordUnits::default();
+    let splitter = Resplitter::new(tokenizer.dictionary(), &units, &split_info);
+    let tokens = splitter.split_worker(&worker, SplitMode::A);
+    assert_eq!(
+        forms.of_split_token(&tokens[0], &split_info),
+        "ガーデニング"
+    );
+    assert_eq!(forms.of_split_token(&tokens[3], &split_info), "X");
+    Ok(())
+}
+
+#[test]
+fn word_ids_map_rows_back_to_sudachi_word_ids() -> Result<()> {
+    let dir = tempdir()?;
+    let path = |name: &str| dir.path().join(name).display().to_string();
+    fs::write(path("small_lex.csv"), SPLIT_LEX)?;
+    fs::write(
+        path("core_lex.csv"),
+        "行く,0,0,100,行く,動詞,非自立可能,*,*,五段-カ行,終止形-一般,イク,行く,*,A,*,*,*,*\n",
+    )?;
+    fs::write(
+        path("lex.append.csv"),
+        "ＵＲＬ,0,0,100,名詞,一般,*,*,*,*,ＵＲＬ,ユーアールエル\n",
+    )?;
+    fs::write(path("unk.def"), "DEFAULT,0,0,100,補助記号,一般,*,*,*,*\n")?;
+    fs::write(path("char.def"), "DEFAULT 0 1 0\n")?;
+
+    let mut argv = vec![
+        "sudachi-vibrato-converter".to_string(),
+        "convert".to_string(),
+    ];
+    for (flag, name) in [
+        ("--lex-in", "small_lex.csv"),
+        ("--lex-in", "core_lex.csv"),
+        ("--lex-append", "lex.append.csv"),
+        ("--lex-out", "lex.out.csv"),
+        ("--unk-in", "unk.def"),
+        ("--unk-out", "unk.out.def"),
+        ("--char-in", "char.def"),
+        ("--char-out", "char.out.def"),
+        ("--stats-out", "stats.env"),
+        ("--word-ids-out", "word_ids.bin"),
+    ] {
+        argv.push(flag.to_string());
+        argv.push(path(name));
+    }
+    let Commands::Convert(args) = Cli::try_parse_from(&argv)?.command else {
+        panic!("expected the convert subcommand");
+    };
+    run_convert(&args)?;
+
+    let word_ids = WordIds::from_path(path("word_ids.bin"))?;
+    let origin = |word_id, source| WordOrigin { word_id, source };
+    // Word 1 (東京) is split-only, so rows are renumbered after it.
+    assert_eq!(word_ids.len(), 6);
+    assert_eq!(word_ids.get(0), Some(origin(Some(0), "small_lex.csv")));
+    assert_eq!(word_ids.get(1), Some(origin(Some(2), "small_lex.csv")));
+    assert_eq!(word_ids.get(4), Some(origin(Some(5), "core_lex.csv")));
+    assert_eq!(word_ids.get(5), Some(origin(None, "lex.append.csv")));
+    assert_eq!(word_ids.get(6), None);
+    assert_eq!(word_ids.row_of(5), Some(4));
+    assert_eq!(word_ids.row_of(1), None);
+
+    let lex = fs::read(path("lex.out.csv"))?;
+    let dictionary = compile_dictionary(
+        &lex,
+        b"1 1\n0 0 0\n",
+        b"DEFAULT 0 1 0\n",
+        &fs::read(path("unk.out.def"))?,
+    )?;
+    let tokenizer = Tokenizer::new(dictionary);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("都に行くX");
+    worker.tokenize();
+    let word_id_of = |i| word_ids.of_token(&worker.token(i)).and_then(|o| o.word_id);
+    assert_eq!(
+        (0..worker.num_tokens()).map(word_id_of).collect::<Vec<_>>(),
+        [Some(2), Some(3), Some(5), None]
+    );
+    Ok(())
+}
+
+const SYNONYMS: &str = "\
+000001,1,0,1,0,0,0,(),曖昧,,
+000001,1,0,1,0,0,2,(),あいまい,,
+000001,1,2,1,0,0,3,(),あいまいさ,,
+000001,1,1,2,0,0,0,(),不明確,,
+
+000002,1,0,1,0,1,0,(IT),PC,,
+000002,1,1,1,0,0,0,(IT),パソコン,,
+000002,1,0,1,1,0,0,(IT),パーソナルコンピューター,,
+";
+
+#[test]
+fn synonym_groups_expand_tokens_with_sudachi_flags() -> Result<()> {
+    let lex_input = concat!(
+        "曖昧,0,0,100,曖昧,名詞,普通名詞,形状詞可能,*,*,*,アイマイ,曖昧,*,A,*,*,*,000001\n",
+        "に,0,0,100,に,助詞,格助詞,*,*,*,*,ニ,に,*,A,*,*,*,*\n",
+        "PC,0,0,100,PC,名詞,普通名詞,一般,*,*,*,ピーシー,PC,*,A,*,*,*,000002/x\n",
+    );
+    let options = ConvertOptions {
+        collect_synonym_groups: true,
+        ..ConvertOptions::default()
+    };
+    let mut lex = Vec::new();
+    let mut stats = ConversionStats::default();
+    let sidecars = convert_lexicon_sources(
+        &[LexiconSource::new(
+            "small_lex.csv",
+            lex_input.as_bytes().to_vec(),
+        )],
+        &mut lex,
+        &options,
+        &mut stats,
+    )?;
+    assert_eq!(stats.invalid_synonym_group_ids, 1);
+    let groups = SynonymGroups::from_bytes(&sidecars.synonym_groups.to_bytes()?)?;
+    assert_eq!(groups, sidecars.synonym_groups);
+    assert_eq!(groups.get(0), [1]);
+    assert!(groups.get(1).is_empty());
+    assert_eq!(groups.get(2), [2]);
+    assert_eq!(groups.group_ids(), [1, 2]);
+
+    let synonyms = SynonymDictionary::from_reader("synonyms.txt", SYNONYMS.as_bytes())?;
+    assert_eq!(synonyms.len(), 2);
+    let pc = &synonyms.group(2)[0];
+    assert_eq!(pc.taigen_yougen, TaigenYougen::Taigen);
+    assert_eq!(pc.expansion, ExpansionControl::Always);
+    assert_eq!(pc.abbreviation, 1);
+    assert_eq!(pc.domains, ["IT"]);
+    assert_eq!(synonyms.group(2)[2].form, LexemeForm::Translation);
+
+    let expander = SynonymExpander::new(&groups, &synonyms);
+    let headwords = |row, written| -> Vec<&str> {
+        expander
+            .expand_row(row, written)
+            .into_iter()
+            .map(|synonym| synonym.headword.as_str())
+            .collect()
+    };
+    // あいまいさ is never expanded to; 不明確 is a target but no trigger.
+    assert_eq!(headwords(0, "曖昧"), ["あいまい", "不明確"]);
+    assert!(headwords(2, "パソコン").is_empty());
+    assert!(headwords(1, "に").is_empty());
+
+    let dictionary = compile_dictionary(
+        &lex,
+        b"1 1\n0 0 0\n",
+        b"DEFAULT 0 1 0\n",
+        "DEFAULT,0,0,100,記号,一般,*,*,*,*,*,*,*\n".as_bytes(),
+    )?;
+    let tokenizer = Tokenizer::new(dictionary);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("曖昧にPC");
+    worker.tokenize();
+    let expanded: Vec<&str> = expander
+        .expand(&worker.token(2))
+        .into_iter()
+        .map(|synonym| synonym.headword.as_str())
+        .collect();
+    assert_eq!(expanded, ["パソコン", "パーソナルコンピューター"]);
+    Ok(())
+}
+
+#[test]
+fn synonym_rows_with_unknown_flags_are_rejected() {
+    let error =
+        SynonymDictionary::from_reader("synonyms.txt", "000001,3,0,1,0,0,0,(),曖昧,,\n".as_bytes())
+            .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "unknown taigen_yougen 3 at synonyms.txt line 1"
+    );
+}
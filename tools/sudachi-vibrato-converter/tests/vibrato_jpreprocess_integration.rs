@@ -1,12 +1,19 @@
 use std::fs;
 use std::io::Cursor;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Result};
+use clap::Parser;
 use jpreprocess_core::word_entry::WordEntry;
+use sudachi_vibrato_converter::cli::{Cli, Commands};
+use sudachi_vibrato_converter::pipeline::run_convert;
 use sudachi_vibrato_converter::{
-    append_text_files_as_lines, append_unknown_definitions, convert_char_definition,
-    convert_lexicon, convert_unknown_dictionary, ConversionStats,
+    append_lexicon_rows, append_text_files_as_lines, append_unknown_definitions,
+    compile_dictionary, convert_char_definition, convert_lexicon, convert_lexicon_with_options,
+    convert_unknown_dictionary, convert_unknown_dictionary_with_options, feature_schema_by_name,
+    grapheme_groups, lint_paths, merge_grapheme_clusters, read_compressed_dictionary,
+    smoke_tokenize, write_compressed_dictionary, AccentDictionary, ConversionStats, ConvertOptions,
+    PosMapping,
 };
 use tempfile::tempdir;
 use vibrato::dictionary::{LexType, SystemDictionaryBuilder};
@@ -83,6 +90,130 @@ fn vibrato_and_jpreprocess_can_consume_converted_outputs() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn compile_writes_zstd_dictionary_and_smoke_tokenizes_it() -> Result<()> {
+    let lex = concat!(
+        "東京都,0,0,100,名詞,固有名詞,地域,一般,*,*,東京都,トウキョウト,トーキョート\n",
+        "に,0,0,100,助詞,格助詞,一般,*,*,*,に,ニ,ニ\n",
+        "行く,0,0,100,動詞,自立,*,*,五段・カ行促音便,基本形,行く,イク,イク\n",
+    );
+    let unk = "DEFAULT,0,0,100,記号,一般,*,*,*,*,*,*,*\n";
+    let char_def = "DEFAULT 0 1 0\n";
+    let matrix_def = "1 1\n0 0 0\n";
+
+    let dict = compile_dictionary(
+        lex.as_bytes(),
+        matrix_def.as_bytes(),
+        char_def.as_bytes(),
+        unk.as_bytes(),
+    )?;
+    let mut compressed = Vec::new();
+    write_compressed_dictionary(&dict, &mut compressed)?;
+    assert_eq!(compressed[..4], [0x28, 0xb5, 0x2f, 0xfd]);
+
+    let dict = read_compressed_dictionary(compressed.as_slice())?;
+    let surfaces = smoke_tokenize(dict, &["東京都に行く"])?;
+    assert_eq!(surfaces, [["東京都", "に", "行く"]]);
+
+    let invalid_lex = format!("{}行け,0,1,100,動詞,自立,*,*,*,*,行く,イケ,イケ\n", lex);
+    let error = compile_dictionary(
+        invalid_lex.as_bytes(),
+        matrix_def.as_bytes(),
+        char_def.as_bytes(),
+        unk.as_bytes(),
+    )
+    .err()
+    .ok_or_else(|| anyhow!("out-of-range connection id was accepted"))?;
+    assert!(
+        format!("{:#}", error).contains("at lex.csv line 4"),
+        "{:#}",
+        error
+    );
+    Ok(())
+}
+
+#[test]
+fn jpreprocess12_schema_is_loaded_by_jpreprocess_without_padding() -> Result<()> {
+    let lex_input = concat!(
+        "東京都,0,0,100,東京都,名詞,固有名詞,地名,一般,*,*,トウキョウト,東京都\n",
+        "に,0,0,100,に,助詞,格助詞,一般,*,*,*,ニ,に\n",
+        "行く,0,0,100,行く,動詞,一般,*,*,五段-カ行,終止形-一般,イク,行く\n"
+    );
+    let unk_input = "DEFAULT,0,0,100,補助記号,一般,*,*,*,*\n";
+    let char_input = "DEFAULT 0 1 0\n";
+    let matrix_def = "1 1\n0 0 0\n";
+
+    let accent = concat!(
+        "東京都,トウキョウト,名詞,3,C1\n",
+        "に,ニ,助詞,0\n",
+        "行く,イク,動詞,0,*,1\n"
+    );
+    let options = ConvertOptions {
+        feature_schema: feature_schema_by_name("jpreprocess12")?,
+        accent: AccentDictionary::from_reader(accent.as_bytes())?,
+        ..ConvertOptions::default()
+    };
+    let mut stats = ConversionStats::default();
+    let mut lex_out = Vec::new();
+    convert_lexicon_with_options(
+        Cursor::new(lex_input.as_bytes()),
+        &mut lex_out,
+        &options,
+        &mut stats,
+    )?;
+    assert_eq!(stats.feature_schema, "jpreprocess12-v1");
+    assert_eq!(stats.accent_matched_rows, 3);
+
+    let mut unk_out = Vec::new();
+    convert_unknown_dictionary_with_options(
+        Cursor::new(unk_input.as_bytes()),
+        &mut unk_out,
+        &options,
+    )?;
+    let mut char_out = Vec::new();
+    convert_char_definition(Cursor::new(char_input.as_bytes()), &mut char_out)?;
+
+    let dict = SystemDictionaryBuilder::from_readers(
+        lex_out.as_slice(),
+        matrix_def.as_bytes(),
+        char_out.as_slice(),
+        unk_out.as_slice(),
+    )?;
+    let tokenizer = Tokenizer::new(dict);
+    let mut worker = tokenizer.new_worker();
+    worker.reset_sentence("東京都に行く☃");
+    worker.tokenize();
+    assert_eq!(worker.num_tokens(), 4);
+
+    for i in 0..worker.num_tokens() {
+        let feature = worker.token(i).feature();
+        let details: Vec<&str> = feature.split(',').collect();
+        assert_eq!(details.len(), 12, "{feature}");
+        WordEntry::load(&details).map_err(|err| anyhow!("{feature}: {err}"))?;
+        if i == 0 {
+            assert_eq!(details[9], "3/5");
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn default_pos_mapping_targets_are_accepted_by_jpreprocess() -> Result<()> {
+    for rule in PosMapping::default_table().rules() {
+        let mut details = vec!["*"; 12];
+        details[..4].copy_from_slice(&[
+            rule.target[0].as_str(),
+            rule.target[1].as_str(),
+            rule.target[2].as_str(),
+            rule.target[3].as_str(),
+        ]);
+        details[9] = "*/*";
+        WordEntry::load(&details)
+            .map_err(|err| anyhow!("line {}: {:?}: {err}", rule.line, rule.target))?;
+    }
+    Ok(())
+}
+
 fn mecab9_feature_to_jpreprocess12(feature: &str) -> Result<Vec<String>> {
     let fields: Vec<&str> = feature.split(',').collect();
     if fields.len() != 9 {
@@ -178,7 +309,7 @@ fn ipadic_numeric_merge_rules_prioritize_numeric_and_split_alpha_numeric() -> Re
     fs::write(&lex_append_path, lex_append)?;
     fs::write(&char_append_path, char_append)?;
     fs::write(&unk_append_path, unk_append)?;
-    append_text_files_as_lines(&mut lex_out, &[lex_append_path])?;
+    append_lexicon_rows(&mut lex_out, &[lex_append_path])?;
     append_text_files_as_lines(&mut char_out, &[char_append_path])?;
     append_unknown_definitions(&mut unk_out, &[unk_append_path])?;
 
@@ -259,6 +390,220 @@ fn ipadic_numeric_merge_rules_prioritize_numeric_and_split_alpha_numeric() -> Re
     Ok(())
 }
 
+#[test]
+fn nooovbow_emulation_keeps_unknown_words_from_starting_at_flagged_chars() -> Result<()> {
+    let dir = tempdir()?;
+    let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+    fs::write(path("lex.csv"), "ね,0,0,100,ね,助詞,終助詞,*,*,*,*,ネ,ね\n")?;
+    fs::write(path("matrix.def"), "1 1\n0 0 0\n")?;
+    fs::write(
+        path("char.def"),
+        concat!(
+            "DEFAULT 0 1 0\n",
+            "HIRAGANA 0 1 2\n",
+            "KATAKANA 1 1 2\n",
+            "0x3041..0x309F HIRAGANA\n",
+            "0x30A1..0x30FF KATAKANA\n",
+            "0x30FC KATAKANA HIRAGANA NOOOVBOW # ー\n",
+            "0x3063 NOOOVBOW # っ\n",
+        ),
+    )?;
+    fs::write(
+        path("unk.def"),
+        concat!(
+            "DEFAULT,0,0,100,補助記号,一般,*,*,*,*\n",
+            "HIRAGANA,0,0,100,名詞,普通名詞,一般,*,*,*\n",
+            "KATAKANA,0,0,100,名詞,普通名詞,一般,*,*,*\n",
+        ),
+    )?;
+
+    let segment = |policy: &str, sentences: &[&str]| -> Result<Vec<Vec<String>>> {
+        let out = |name: &str| path(&format!("{}.{}", policy, name));
+        let argv = vec![
+            "sudachi-vibrato-converter".to_string(),
+            "convert".to_string(),
+            "--lex-in".to_string(),
+            path("lex.csv"),
+            "--lex-out".to_string(),
+            out("lex.csv"),
+            "--unk-in".to_string(),
+            path("unk.def"),
+            "--unk-out".to_string(),
+            out("unk.def"),
+            "--char-in".to_string(),
+            path("char.def"),
+            "--char-out".to_string(),
+            out("char.def"),
+            "--stats-out".to_string(),
+            out("stats.env"),
+            "--nooovbow".to_string(),
+            policy.to_string(),
+        ];
+        let Commands::Convert(args) = Cli::try_parse_from(&argv)?.command else {
+            return Err(anyhow!("expected the convert subcommand"));
+        };
+        let (stats, _) = run_convert(&args)?;
+        assert_eq!(stats.char_nooovbow_ranges, 2);
+
+        let dict = compile_dictionary(
+            &fs::read(out("lex.csv"))?,
+            &fs::read(path("matrix.def"))?,
+            &fs::read(out("char.def"))?,
+            &fs::read(out("unk.def"))?,
+        )?;
+        let tokenizer = Tokenizer::new(dict);
+        let mut worker = tokenizer.new_worker();
+        Ok(sentences
+            .iter()
+            .map(|sentence| token_surfaces(&mut worker, sentence))
+            .collect())
+    };
+
+    // Sudachi starts no unknown word at ー; with nothing else there it falls
+    // back to one character, while katakana words still run through it.
+    let sentences = ["ねーー", "ーー", "カーー"];
+    assert_eq!(
+        segment("emulate", &sentences)?,
+        [vec!["ね", "ー", "ー"], vec!["ー", "ー"], vec!["カーー"]]
+    );
+    assert_eq!(
+        segment("strip", &sentences)?,
+        [vec!["ね", "ーー"], vec!["ーー"], vec!["カーー"]]
+    );
+
+    let char_out = fs::read_to_string(path("emulate.char.def"))?;
+    assert!(char_out.contains("NOOOVBOW_KATAKANA 0 0 0\n"));
+    assert!(char_out.contains("0x30FC NOOOVBOW_KATAKANA KATAKANA HIRAGANA\n"));
+    assert!(char_out.contains("0x3063 NOOOVBOW_HIRAGANA HIRAGANA\n"));
+    let unk_out = fs::read_to_string(path("emulate.unk.def"))?;
+    assert!(unk_out.contains("NOOOVBOW_KATAKANA,0,0,32767,"));
+    assert!(!fs::read_to_string(path("strip.char.def"))?.contains("NOOOVBOW"));
+    Ok(())
+}
+
+#[test]
+fn emoji_profile_tokens_merge_into_grapheme_clusters() -> Result<()> {
+    let dir = tempdir()?;
+    let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+    let profile = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../rules/emoji-grapheme");
+    fs::write(
+        path("lex.csv"),
+        "犬,0,0,100,犬,名詞,普通名詞,一般,*,*,*,イヌ,犬\n",
+    )?;
+    fs::write(path("matrix.def"), "1 1\n0 0 0\n")?;
+    fs::write(
+        path("char.def"),
+        "DEFAULT 0 1 0\nKANJI 0 0 2\nNUMERIC 1 1 0\n0x0030..0x0039 NUMERIC\n0x4E00..0x9FFF KANJI\n",
+    )?;
+    fs::write(
+        path("unk.def"),
+        concat!(
+            "DEFAULT,0,0,100,補助記号,一般,*,*,*,*\n",
+            "KANJI,0,0,100,名詞,普通名詞,一般,*,*,*\n",
+            "NUMERIC,0,0,100,名詞,数詞,*,*,*,*\n",
+        ),
+    )?;
+
+    let argv = vec![
+        "sudachi-vibrato-converter".to_string(),
+        "convert".to_string(),
+        "--lex-in".to_string(),
+        path("lex.csv"),
+        "--lex-out".to_string(),
+        path("lex.out.csv"),
+        "--unk-in".to_string(),
+        path("unk.def"),
+        "--unk-append".to_string(),
+        profile
+            .join("unk.append.def")
+            .to_string_lossy()
+            .into_owned(),
+        "--unk-out".to_string(),
+        path("unk.out.def"),
+        "--char-in".to_string(),
+        path("char.def"),
+        "--char-append".to_string(),
+        profile
+            .join("char.append.def")
+            .to_string_lossy()
+            .into_owned(),
+        "--char-out".to_string(),
+        path("char.out.def"),
+        "--stats-out".to_string(),
+        path("stats.env"),
+    ];
+    let Commands::Convert(args) = Cli::try_parse_from(&argv)?.command else {
+        return Err(anyhow!("expected the convert subcommand"));
+    };
+    run_convert(&args)?;
+    let report = lint_paths(
+        &[PathBuf::from(path("char.out.def"))],
+        &[PathBuf::from(path("unk.out.def"))],
+    )?;
+    assert_eq!(report.errors(), 0);
+    // The EMOJI row takes the ids and cost of the DEFAULT row.
+    assert!(fs::read_to_string(path("unk.out.def"))?
+        .lines()
+        .any(|line| line.starts_with("EMOJI,0,0,100,記号,一般,")));
+
+    let dict = compile_dictionary(
+        &fs::read(path("lex.out.csv"))?,
+        &fs::read(path("matrix.def"))?,
+        &fs::read(path("char.out.def"))?,
+        &fs::read(path("unk.out.def"))?,
+    )?;
+    let tokenizer = Tokenizer::new(dict);
+    let mut worker = tokenizer.new_worker();
+    // Emoji beyond U+FFFF are DEFAULT and group, with each other and with rare kanji.
+    let sentence = "犬👨\u{200D}👩\u{200D}👧🇯🇵𠮷👍🏽1\u{FE0F}\u{20E3}☀\u{FE0F}";
+    assert_eq!(token_surfaces(&mut worker, sentence)[5], "👧🇯🇵𠮷👍🏽");
+
+    let merged = merge_grapheme_clusters(sentence, &worker);
+    let surfaces: Vec<&str> = merged.iter().map(|token| token.surface.as_str()).collect();
+    assert_eq!(
+        surfaces,
+        [
+            "犬",
+            "👨\u{200D}👩\u{200D}👧",
+            "🇯🇵",
+            "𠮷",
+            "👍🏽",
+            "1\u{FE0F}\u{20E3}",
+            "☀\u{FE0F}",
+        ]
+    );
+    for token in &merged {
+        assert_eq!(&sentence[token.range_byte.clone()], token.surface);
+        let chars: Vec<char> = sentence.chars().collect();
+        assert_eq!(
+            chars[token.range_char.clone()].iter().collect::<String>(),
+            token.surface
+        );
+    }
+    assert_eq!(merged[1].tokens, 1..6);
+    assert_eq!(merged[2].range_char, 6..8);
+    assert_eq!(merged[3].tokens, 5..6);
+    assert!(merged[1].feature.starts_with("記号,一般,"));
+
+    // Rare kanji without emoji next to them keep grouping.
+    let sentence = "𠮷𠮷";
+    assert_eq!(token_surfaces(&mut worker, sentence), ["𠮷𠮷"]);
+    let merged = merge_grapheme_clusters(sentence, &worker);
+    assert_eq!(merged.len(), 1);
+    assert_eq!(merged[0].range_char, 0..2);
+    Ok(())
+}
+
+#[test]
+fn grapheme_groups_only_merge_adjacent_tokens() {
+    let sentence = "👍🏽 👍🏽";
+    // 👍 and 🏽 are 4 bytes each; the space is left out as with `ignore_space`.
+    let ranges = [0..4, 4..8, 9..13, 13..17];
+    assert_eq!(grapheme_groups(sentence, &ranges), [0..2, 2..4]);
+    assert_eq!(grapheme_groups(sentence, &[0..4, 9..13]), [0..1, 1..2]);
+    assert!(grapheme_groups(sentence, &[]).is_empty());
+}
+
 fn assert_token_surfaces(
     worker: &mut vibrato::tokenizer::worker::Worker<'_>,
     sentence: &str,